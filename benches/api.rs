@@ -0,0 +1,291 @@
+//! Throughput and latency of the hot endpoints, over real HTTP.
+//!
+//! `cargo bench` boots the service on an ephemeral port once per storage
+//! backend and drives every scenario with concurrent clients. The results
+//! are printed and written to `bench_output.txt` at the repository root, so
+//! two runs can be compared with `diff`. Pass a substring to run only the
+//! matching scenarios, e.g. `cargo bench -- items/{id}`.
+//!
+//! `BENCH_REQUESTS` (default 2000) sets the requests per scenario and
+//! `BENCH_CONCURRENCY` (default 16) the number of clients.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::{
+    fmt::Write as _,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use api_app::{
+    AppState,
+    storage::{MemoryStorage, SqliteStorage},
+};
+use common::server::TestServer;
+use reqwest::{Method, RequestBuilder, header};
+use serde_json::json;
+
+/// Items created before the scenarios run.
+const SEEDED_ITEMS: usize = 1000;
+/// Requests sent before measuring, to open connections and warm caches.
+const WARMUP_REQUESTS: usize = 100;
+
+/// What the scenarios get to work with.
+struct Fixture {
+    server: TestServer,
+    ids: Vec<String>,
+}
+
+struct Scenario {
+    name: &'static str,
+    /// The `n`th request of a run.
+    request: fn(&Fixture, usize) -> RequestBuilder,
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "GET /healthz",
+        request: |fixture, _| fixture.server.client.get(fixture.server.url("/healthz")),
+    },
+    Scenario {
+        name: "GET /items/{id}",
+        request: |fixture, n| {
+            let id = &fixture.ids[n % fixture.ids.len()];
+            fixture
+                .server
+                .request(Method::GET, &format!("/api/v1/items/{id}"))
+        },
+    },
+    Scenario {
+        name: "GET /items?limit=50",
+        request: |fixture, _| {
+            fixture
+                .server
+                .request(Method::GET, "/api/v1/items?limit=50")
+        },
+    },
+    Scenario {
+        name: "GET /items?sort=-quantity",
+        request: |fixture, _| {
+            fixture.server.request(
+                Method::GET,
+                "/api/v1/items?sort=-quantity&min_quantity=500&limit=50",
+            )
+        },
+    },
+    Scenario {
+        name: "GET /search?q=item 5*",
+        request: |fixture, _| {
+            fixture
+                .server
+                .request(Method::GET, "/api/v1/search?q=item%205*&limit=50")
+        },
+    },
+    Scenario {
+        name: "POST /items",
+        request: |fixture, n| {
+            fixture
+                .server
+                .request(Method::POST, "/api/v1/items")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(json!({ "name": format!("bench {n}"), "quantity": n }).to_string())
+        },
+    },
+    Scenario {
+        name: "PATCH /items/{id}",
+        request: |fixture, n| {
+            let id = &fixture.ids[n % fixture.ids.len()];
+            fixture
+                .server
+                .request(Method::PATCH, &format!("/api/v1/items/{id}"))
+                .header(header::CONTENT_TYPE, "application/merge-patch+json")
+                .body(json!({ "quantity": n }).to_string())
+        },
+    },
+];
+
+struct Settings {
+    requests: usize,
+    concurrency: usize,
+    filter: Option<String>,
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let number = |name: &str, default: usize| match std::env::var(name) {
+            Ok(value) => value
+                .parse()
+                .unwrap_or_else(|_| panic!("{name} must be a positive number")),
+            Err(_) => default,
+        };
+        Self {
+            requests: number("BENCH_REQUESTS", 2000),
+            concurrency: number("BENCH_CONCURRENCY", 16).max(1),
+            // `cargo bench` passes `--bench`; anything else selects scenarios.
+            filter: std::env::args().skip(1).find(|arg| !arg.starts_with("--")),
+        }
+    }
+}
+
+/// Latencies of one scenario, and how long it took overall.
+struct Measurement {
+    elapsed: Duration,
+    latencies: Vec<Duration>,
+}
+
+impl Measurement {
+    fn throughput(&self) -> f64 {
+        self.latencies.len() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// The latency `percent` of the requests came in under.
+    fn percentile(&self, percent: usize) -> Duration {
+        let rank = (self.latencies.len() * percent).div_ceil(100);
+        self.latencies[rank.saturating_sub(1)]
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let settings = Settings::from_env();
+    let mut report = format!(
+        "{} requests per scenario, {} concurrent clients, {SEEDED_ITEMS} items seeded\n\n",
+        settings.requests, settings.concurrency,
+    );
+    writeln!(
+        report,
+        "{:<8} {:<28} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "backend", "scenario", "req/s", "p50", "p90", "p99", "max"
+    )
+    .unwrap();
+    print!("{report}");
+
+    let dir = tempfile::tempdir().expect("temporary directory");
+    let backends = [
+        ("memory", AppState::new(MemoryStorage::new())),
+        (
+            "sqlite",
+            AppState::new(SqliteStorage::open(dir.path().join("bench.db")).expect("database")),
+        ),
+    ];
+    for (backend, state) in backends {
+        let fixture = Arc::new(seed(TestServer::start(state).await).await);
+        for scenario in SCENARIOS {
+            if settings
+                .filter
+                .as_deref()
+                .is_some_and(|filter| !scenario.name.contains(filter))
+            {
+                continue;
+            }
+            run(&fixture, scenario, WARMUP_REQUESTS, settings.concurrency).await;
+            let measured = run(&fixture, scenario, settings.requests, settings.concurrency).await;
+            let line = format!(
+                "{backend:<8} {:<28} {:>9.0} {:>9} {:>9} {:>9} {:>9}\n",
+                scenario.name,
+                measured.throughput(),
+                millis(measured.percentile(50)),
+                millis(measured.percentile(90)),
+                millis(measured.percentile(99)),
+                millis(*measured.latencies.last().unwrap()),
+            );
+            print!("{line}");
+            report.push_str(&line);
+        }
+        let Ok(fixture) = Arc::try_unwrap(fixture) else {
+            unreachable!("every client has finished");
+        };
+        fixture.server.stop().await;
+    }
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/bench_output.txt");
+    std::fs::write(path, report).expect("bench_output.txt is writable");
+    println!("\nwritten to {path}");
+}
+
+/// Imports [`SEEDED_ITEMS`] items and collects their ids.
+async fn seed(server: TestServer) -> Fixture {
+    let rows: String = (0..SEEDED_ITEMS)
+        .map(|n| {
+            format!(
+                "{}\n",
+                json!({ "name": format!("item {n}"), "quantity": n })
+            )
+        })
+        .collect();
+    let response = server
+        .request(Method::POST, "/api/v1/items/import")
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(rows)
+        .send()
+        .await
+        .expect("import request");
+    assert!(response.status().is_success(), "seeding failed");
+
+    let response = server
+        .request(Method::GET, "/api/v1/items/export")
+        .send()
+        .await
+        .expect("export request");
+    let ids = response
+        .text()
+        .await
+        .expect("export body")
+        .lines()
+        .map(|line| {
+            let item: serde_json::Value = serde_json::from_str(line).expect("exported item");
+            item["id"].as_str().expect("item id").to_owned()
+        })
+        .collect();
+    Fixture { server, ids }
+}
+
+/// Sends `requests` requests from `concurrency` clients at once. Any
+/// unsuccessful response aborts the benchmark: a fast error is not a result.
+async fn run(
+    fixture: &Arc<Fixture>,
+    scenario: &'static Scenario,
+    requests: usize,
+    concurrency: usize,
+) -> Measurement {
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let clients: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let fixture = fixture.clone();
+            let next = next.clone();
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                loop {
+                    let n = next.fetch_add(1, Ordering::Relaxed);
+                    if n >= requests {
+                        return latencies;
+                    }
+                    let request = (scenario.request)(&fixture, n);
+                    let sent = Instant::now();
+                    let response = request.send().await.expect("request is sent");
+                    let status = response.status();
+                    response.bytes().await.expect("response body");
+                    latencies.push(sent.elapsed());
+                    assert!(status.is_success(), "{}: {status}", scenario.name);
+                }
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::with_capacity(requests);
+    for client in clients {
+        latencies.extend(client.await.expect("client finishes"));
+    }
+    let elapsed = started.elapsed();
+    latencies.sort_unstable();
+    Measurement { elapsed, latencies }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
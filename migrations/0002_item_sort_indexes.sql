@@ -0,0 +1,3 @@
+CREATE INDEX items_updated_at ON items (updated_at, id);
+CREATE INDEX items_name ON items (name, id);
+CREATE INDEX items_quantity ON items (quantity, id);
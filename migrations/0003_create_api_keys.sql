@@ -0,0 +1,12 @@
+CREATE TABLE api_keys (
+    id         TEXT PRIMARY KEY NOT NULL,
+    name       TEXT NOT NULL,
+    prefix     TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    -- Space-separated scope names.
+    scopes     TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    revoked_at TEXT
+);
+
+CREATE INDEX api_keys_created_at ON api_keys (created_at, id);
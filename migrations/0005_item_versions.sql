@@ -0,0 +1,2 @@
+-- Bumped on every update; exposed as the item's ETag.
+ALTER TABLE items ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
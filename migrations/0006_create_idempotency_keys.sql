@@ -0,0 +1,14 @@
+CREATE TABLE idempotency_keys (
+    scope       TEXT NOT NULL,
+    key         TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    -- NULL until the first request completes.
+    status      INTEGER,
+    -- JSON array of [name, value] pairs.
+    headers     TEXT,
+    body        BLOB,
+    expires_at  TEXT NOT NULL,
+    PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX idempotency_keys_expires_at ON idempotency_keys (expires_at);
@@ -0,0 +1,27 @@
+CREATE TABLE audit_log (
+    id            TEXT PRIMARY KEY NOT NULL,
+    occurred_at   TEXT NOT NULL,
+    actor         TEXT NOT NULL,
+    action        TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    -- NULL for changes to many resources at once.
+    resource_id   TEXT,
+    -- JSON object of {"before": ..., "after": ...} per changed field.
+    changes       TEXT NOT NULL,
+    request_id    TEXT
+);
+
+CREATE INDEX audit_log_occurred_at ON audit_log (occurred_at, id);
+CREATE INDEX audit_log_actor ON audit_log (actor, occurred_at, id);
+CREATE INDEX audit_log_resource ON audit_log (resource_id, occurred_at, id);
+
+-- The log is append-only.
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
@@ -0,0 +1,60 @@
+-- Full-text index over item names and descriptions. The text stays in
+-- `items`; the triggers below keep the index in step with it.
+--
+-- The index is keyed by an explicit INTEGER PRIMARY KEY on `items`, since
+-- VACUUM may renumber an implicit rowid. Adding one means rebuilding the
+-- table.
+CREATE TABLE items_new (
+    seq         INTEGER PRIMARY KEY,
+    id          TEXT NOT NULL UNIQUE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    quantity    INTEGER NOT NULL DEFAULT 0,
+    created_at  TEXT NOT NULL,
+    updated_at  TEXT NOT NULL,
+    version     INTEGER NOT NULL DEFAULT 1
+);
+
+INSERT INTO items_new (id, name, description, quantity, created_at, updated_at, version)
+SELECT id, name, description, quantity, created_at, updated_at, version
+FROM items;
+
+DROP TABLE items;
+ALTER TABLE items_new RENAME TO items;
+
+CREATE INDEX items_created_at ON items (created_at, id);
+CREATE INDEX items_updated_at ON items (updated_at, id);
+CREATE INDEX items_name ON items (name, id);
+CREATE INDEX items_quantity ON items (quantity, id);
+
+CREATE VIRTUAL TABLE items_fts USING fts5 (
+    name,
+    description,
+    content = 'items',
+    content_rowid = 'seq',
+    tokenize = 'unicode61 remove_diacritics 0',
+    -- Speeds up short prefix queries such as `wi*`.
+    prefix = '2 3'
+);
+
+INSERT INTO items_fts (items_fts) VALUES ('rebuild');
+
+CREATE TRIGGER items_fts_insert AFTER INSERT ON items
+BEGIN
+    INSERT INTO items_fts (rowid, name, description)
+    VALUES (new.seq, new.name, new.description);
+END;
+
+CREATE TRIGGER items_fts_update AFTER UPDATE OF name, description ON items
+BEGIN
+    INSERT INTO items_fts (items_fts, rowid, name, description)
+    VALUES ('delete', old.seq, old.name, old.description);
+    INSERT INTO items_fts (rowid, name, description)
+    VALUES (new.seq, new.name, new.description);
+END;
+
+CREATE TRIGGER items_fts_delete AFTER DELETE ON items
+BEGIN
+    INSERT INTO items_fts (items_fts, rowid, name, description)
+    VALUES ('delete', old.seq, old.name, old.description);
+END;
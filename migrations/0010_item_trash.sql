@@ -0,0 +1,4 @@
+-- Deleted items stay in the table until they are restored or purged.
+ALTER TABLE items ADD COLUMN deleted_at TEXT;
+
+CREATE INDEX items_deleted_at ON items (deleted_at) WHERE deleted_at IS NOT NULL;
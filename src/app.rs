@@ -0,0 +1,276 @@
+//! Router construction and shared application state.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, Router, body::Bytes, extract::FromRef, http::header, middleware, routing::get};
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use uuid::Uuid;
+
+use crate::{
+    auth::{self, jwt::Jwt},
+    bulk, conditional,
+    error::ApiError,
+    events::{Action, ChangeEvent, ChangeFeed, ResourceType, Watched},
+    health::{self, HEALTH_PATH, READY_PATH, Readiness},
+    idempotency, logging,
+    metrics::{self, METRICS_PATH, Metrics},
+    negotiate,
+    openapi::{self, ApiDoc, OPENAPI_PATH},
+    rate_limit::{self, RateLimits, RouteGroup},
+    routes,
+    storage::{DynStorage, InstrumentedStorage, Storage},
+    trash::TrashSettings,
+    webhooks::{self, WebhookSettings, Webhooks},
+};
+
+/// Prefix every versioned endpoint is mounted under.
+pub const API_V1: &str = "/api/v1";
+
+/// State shared by every handler.
+#[derive(Clone)]
+pub struct AppState {
+    /// The configured backend, timed by [`InstrumentedStorage`].
+    pub storage: DynStorage,
+    /// Present when JWT sessions are enabled.
+    pub jwt: Option<Arc<Jwt>>,
+    pub rate_limits: RateLimits,
+    pub metrics: Arc<Metrics>,
+    /// Flipped when graceful shutdown begins.
+    pub readiness: Readiness,
+    /// Whether writes to versioned resources must carry `If-Match`.
+    pub require_if_match: bool,
+    /// How long responses are replayed for a repeated `Idempotency-Key`.
+    pub idempotency_ttl: Duration,
+    /// Smallest response, in bytes, that is compressed.
+    pub compression_min_size: u16,
+    /// Largest upload, in bytes, accepted by `/items/import`.
+    pub max_import_size: u64,
+    /// Changes made through this instance, for the `/events` feed.
+    pub events: Arc<ChangeFeed>,
+    pub webhooks: Arc<Webhooks>,
+    pub trash: TrashSettings,
+}
+
+impl AppState {
+    pub fn new(storage: impl Storage + 'static) -> Self {
+        let metrics = Arc::new(Metrics::new());
+        Self {
+            storage: Arc::new(InstrumentedStorage::new(Arc::new(storage), metrics.clone())),
+            jwt: None,
+            rate_limits: RateLimits::default(),
+            metrics,
+            readiness: Readiness::default(),
+            require_if_match: false,
+            idempotency_ttl: idempotency::DEFAULT_TTL,
+            compression_min_size: negotiate::DEFAULT_COMPRESSION_MIN_SIZE,
+            max_import_size: bulk::DEFAULT_MAX_IMPORT_SIZE,
+            events: Arc::new(ChangeFeed::new()),
+            webhooks: Arc::new(Webhooks::default()),
+            trash: TrashSettings::default(),
+        }
+    }
+
+    /// Sets how long deleted items are kept and how often they are purged.
+    pub fn with_trash_settings(mut self, settings: TrashSettings) -> Self {
+        self.trash = settings;
+        self
+    }
+
+    /// Sets how webhook deliveries are attempted and retried.
+    pub fn with_webhook_settings(mut self, settings: WebhookSettings) -> Self {
+        self.webhooks = Arc::new(Webhooks::new(settings));
+        self
+    }
+
+    /// Sets how long responses are replayed for a repeated
+    /// `Idempotency-Key`.
+    pub fn with_idempotency_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency_ttl = ttl;
+        self
+    }
+
+    /// Sets the smallest response, in bytes, that is compressed.
+    pub fn with_compression_min_size(mut self, bytes: u16) -> Self {
+        self.compression_min_size = bytes;
+        self
+    }
+
+    /// Sets the largest upload, in bytes, accepted by `/items/import`.
+    pub fn with_max_import_size(mut self, bytes: u64) -> Self {
+        self.max_import_size = bytes;
+        self
+    }
+
+    /// Rejects writes to versioned resources without `If-Match` (428).
+    pub fn with_required_if_match(mut self, required: bool) -> Self {
+        self.require_if_match = required;
+        self
+    }
+
+    /// Applies per-route-group rate limits.
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Enables JWT sessions alongside API keys.
+    pub fn with_jwt(mut self, jwt: Jwt) -> Self {
+        self.jwt = Some(Arc::new(jwt));
+        self
+    }
+}
+
+impl AppState {
+    /// Publishes the creation, update or restoration of `resource` to the
+    /// `/events` feed and queues it for webhooks.
+    pub async fn changed<R: Watched>(&self, action: Action, resource: &R) {
+        let event = self.events.changed(action, resource);
+        self.notify_webhooks(&event).await;
+    }
+
+    /// Publishes the deletion of a resource, like [`AppState::changed`].
+    pub async fn deleted(&self, resource: ResourceType, id: Uuid) {
+        let event = self.events.deleted(resource, id);
+        self.notify_webhooks(&event).await;
+    }
+
+    async fn notify_webhooks(&self, event: &ChangeEvent) {
+        if let Err(err) = webhooks::enqueue(self, event).await {
+            // The write itself went through; only its webhooks are lost.
+            tracing::error!(%err, event = event.id, "cannot queue webhook deliveries");
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<Metrics> {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
+
+/// Builds the full application router. Besides the API it serves its own
+/// OpenAPI document, Prometheus metrics and the health probes.
+pub fn router(state: AppState) -> Router {
+    let metrics = state.metrics.clone();
+    let compression = negotiate::compression(state.compression_min_size);
+    let (router, mut spec) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .nest(
+            API_V1,
+            v1(&state).route_layer(middleware::from_fn(negotiate::respond)),
+        )
+        .split_for_parts();
+    openapi::complete(&mut spec);
+    let spec = Bytes::from(spec.to_pretty_json().expect("OpenAPI document serialises"));
+
+    let router = router
+        .route(
+            OPENAPI_PATH,
+            get(move || async move { ([(header::CONTENT_TYPE, "application/json")], spec) }),
+        )
+        .route(METRICS_PATH, get(metrics::serve))
+        .route(HEALTH_PATH, get(health::healthz))
+        .route(READY_PATH, get(health::readyz))
+        .fallback(not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .with_state(state)
+        .layer(compression)
+        // Outside compression, so tags and 304s match the body as sent, and
+        // inside metrics, so those count revalidations as 304s.
+        .layer(middleware::from_fn(conditional::revalidate))
+        .layer(middleware::from_fn_with_state(metrics, metrics::track));
+    logging::layer(router)
+}
+
+fn v1(state: &AppState) -> OpenApiRouter<AppState> {
+    // Idempotency runs innermost, so rate-limited or unauthenticated
+    // attempts never consume a key.
+    let items = routes::items::router().route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        idempotency::enforce,
+    ));
+    // Imports stream their body, which idempotency would have to buffer.
+    let api = limited(
+        state,
+        RouteGroup::Api,
+        items
+            .merge(routes::bulk::router())
+            .merge(routes::events::router())
+            .merge(routes::search::router()),
+    );
+    let admin = routes::api_keys::router()
+        .merge(routes::audit::router())
+        .merge(routes::users::router())
+        .merge(routes::webhooks::router())
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            idempotency::enforce,
+        ));
+    let admin = limited(state, RouteGroup::Admin, admin);
+    // Layers run outside-in, so authentication happens before rate limiting
+    // and limits can be keyed by the authenticated principal.
+    let protected = api.merge(admin).route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        auth::authenticate,
+    ));
+    // Rejected credentials never reach the group limiters above, so they
+    // are charged to the auth quota by IP from outside authentication.
+    let protected = match state.rate_limits.get(RouteGroup::Auth) {
+        Some(limiter) => protected.route_layer(middleware::from_fn_with_state(
+            limiter,
+            rate_limit::enforce_failed_auth,
+        )),
+        None => protected,
+    };
+
+    let mut v1 = OpenApiRouter::new().routes(routes!(index)).merge(protected);
+    if state.jwt.is_some() {
+        v1 = v1.merge(limited(state, RouteGroup::Auth, routes::session::router()));
+    }
+    v1
+}
+
+/// Wraps `routes` in the rate limiter for `group`, if one is configured.
+fn limited(
+    state: &AppState,
+    group: RouteGroup,
+    routes: OpenApiRouter<AppState>,
+) -> OpenApiRouter<AppState> {
+    match state.rate_limits.get(group) {
+        Some(limiter) => {
+            routes.route_layer(middleware::from_fn_with_state(limiter, rate_limit::enforce))
+        }
+        None => routes,
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct ApiInfo {
+    name: &'static str,
+    version: &'static str,
+    api: &'static str,
+}
+
+/// Service name and version
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "meta",
+    responses((status = 200, description = "Service name and version", body = ApiInfo)),
+)]
+async fn index() -> Json<ApiInfo> {
+    Json(ApiInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        api: "v1",
+    })
+}
+
+async fn not_found() -> ApiError {
+    ApiError::NotFound("route")
+}
+
+async fn method_not_allowed() -> ApiError {
+    ApiError::MethodNotAllowed
+}
@@ -0,0 +1,195 @@
+//! Append-only audit log of changes made through the API.
+//!
+//! Handlers that write take an [`Audit`] alongside their [`Principal`] and
+//! record each successful change with it. Entries name the actor, the
+//! action, the resource and the request id, and carry a field-by-field diff
+//! of the resource. They are appended after the write itself, so a failure
+//! to record one is logged rather than failing a request whose change
+//! already happened. Background work such as purging the trash records as
+//! [`Audit::system`].
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use tower_http::request_id::RequestId;
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    auth::Principal,
+    error::ApiError,
+    model::{ApiKey, AuditEntry, Item, User, Webhook},
+};
+
+/// A resource whose changes are audited.
+pub trait Audited: Serialize {
+    /// `resource_type` of its entries.
+    const RESOURCE: &'static str;
+
+    fn id(&self) -> Uuid;
+}
+
+impl Audited for Item {
+    const RESOURCE: &'static str = "item";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for ApiKey {
+    const RESOURCE: &'static str = "api_key";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for User {
+    const RESOURCE: &'static str = "user";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Audited for Webhook {
+    const RESOURCE: &'static str = "webhook";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// Who is making the current request, for the entries it writes.
+#[derive(Debug, Clone)]
+pub struct Audit {
+    actor: String,
+    request_id: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Audit {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let principal = Principal::from_request_parts(parts, state).await?;
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(str::to_owned);
+        Ok(Self {
+            actor: principal.subject,
+            request_id,
+        })
+    }
+}
+
+impl Audit {
+    /// The server itself, for changes no request asked for.
+    pub fn system() -> Self {
+        Self {
+            actor: "system".into(),
+            request_id: None,
+        }
+    }
+
+    pub async fn created<R: Audited>(&self, state: &AppState, resource: &R) {
+        let changes = diff(None, Some(resource));
+        self.record(
+            state,
+            R::RESOURCE,
+            "created",
+            Some(R::id(resource)),
+            changes,
+        )
+        .await;
+    }
+
+    /// Records nothing if the write left the resource as it was.
+    pub async fn updated<R: Audited>(&self, state: &AppState, before: &R, after: &R) {
+        let changes = diff(Some(before), Some(after));
+        if !changes.is_empty() {
+            self.record(state, R::RESOURCE, "updated", Some(R::id(after)), changes)
+                .await;
+        }
+    }
+
+    pub async fn deleted<R: Audited>(&self, state: &AppState, resource: &R) {
+        let changes = diff(Some(resource), None);
+        self.record(
+            state,
+            R::RESOURCE,
+            "deleted",
+            Some(R::id(resource)),
+            changes,
+        )
+        .await;
+    }
+
+    pub async fn restored<R: Audited>(&self, state: &AppState, before: &R, after: &R) {
+        let changes = diff(Some(before), Some(after));
+        self.record(state, R::RESOURCE, "restored", Some(R::id(after)), changes)
+            .await;
+    }
+
+    /// Records a bulk import as one entry with the number of resources
+    /// created.
+    pub async fn imported(&self, state: &AppState, resource_type: &str, count: u64) {
+        let mut changes = Map::new();
+        changes.insert("count".into(), json!({ "before": null, "after": count }));
+        self.record(state, resource_type, "imported", None, changes)
+            .await;
+    }
+
+    /// Records a purge as one entry with the number of resources removed.
+    pub async fn purged(&self, state: &AppState, resource_type: &str, count: u64) {
+        let mut changes = Map::new();
+        changes.insert("count".into(), json!({ "before": count, "after": null }));
+        self.record(state, resource_type, "purged", None, changes)
+            .await;
+    }
+
+    async fn record(
+        &self,
+        state: &AppState,
+        resource_type: &str,
+        action: &str,
+        resource_id: Option<Uuid>,
+        changes: Map<String, Value>,
+    ) {
+        let entry = AuditEntry {
+            id: Uuid::now_v7(),
+            occurred_at: Utc::now(),
+            actor: self.actor.clone(),
+            action: format!("{resource_type}.{action}"),
+            resource_type: resource_type.to_owned(),
+            resource_id,
+            changes: Value::Object(changes),
+            request_id: self.request_id.clone(),
+        };
+        if let Err(err) = state.storage.append_audit_entry(&entry).await {
+            tracing::error!(%err, action = %entry.action, ?resource_id, "cannot write audit entry");
+        }
+    }
+}
+
+/// Every field that differs between two states of a resource, as
+/// `{"before": ..., "after": ...}`; a missing state counts as all `null`.
+fn diff<R: Serialize>(before: Option<&R>, after: Option<&R>) -> Map<String, Value> {
+    let fields = |state: Option<&R>| match state.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => Map::new(),
+    };
+    let (before, after) = (fields(before), fields(after));
+    let mut changes = Map::new();
+    for name in after.keys().chain(before.keys()) {
+        let old = before.get(name).unwrap_or(&Value::Null);
+        let new = after.get(name).unwrap_or(&Value::Null);
+        if old != new && !changes.contains_key(name) {
+            changes.insert(name.clone(), json!({ "before": old, "after": new }));
+        }
+    }
+    changes
+}
@@ -0,0 +1,248 @@
+//! Signed JWT access tokens with server-side refresh tokens.
+//!
+//! Access tokens are short-lived and verified statelessly. Refresh tokens
+//! are opaque random strings stored (hashed) by the backend; every refresh
+//! rotates them, and presenting an already used one revokes all of the
+//! user's sessions, since it means the token was copied.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+use super::{Credential, Principal, Scope};
+use crate::model::User;
+
+/// Signing algorithms we accept in configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningAlgorithm {
+    Hs256,
+    Hs384,
+    Hs512,
+    Rs256,
+    Rs384,
+    Rs512,
+    EdDsa,
+}
+
+impl SigningAlgorithm {
+    fn jwt(self) -> Algorithm {
+        match self {
+            SigningAlgorithm::Hs256 => Algorithm::HS256,
+            SigningAlgorithm::Hs384 => Algorithm::HS384,
+            SigningAlgorithm::Hs512 => Algorithm::HS512,
+            SigningAlgorithm::Rs256 => Algorithm::RS256,
+            SigningAlgorithm::Rs384 => Algorithm::RS384,
+            SigningAlgorithm::Rs512 => Algorithm::RS512,
+            SigningAlgorithm::EdDsa => Algorithm::EdDSA,
+        }
+    }
+
+    pub fn is_hmac(self) -> bool {
+        matches!(
+            self,
+            SigningAlgorithm::Hs256 | SigningAlgorithm::Hs384 | SigningAlgorithm::Hs512
+        )
+    }
+}
+
+impl FromStr for SigningAlgorithm {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(match raw.to_ascii_uppercase().as_str() {
+            "HS256" => SigningAlgorithm::Hs256,
+            "HS384" => SigningAlgorithm::Hs384,
+            "HS512" => SigningAlgorithm::Hs512,
+            "RS256" => SigningAlgorithm::Rs256,
+            "RS384" => SigningAlgorithm::Rs384,
+            "RS512" => SigningAlgorithm::Rs512,
+            "EDDSA" => SigningAlgorithm::EdDsa,
+            _ => return Err(format!("unsupported JWT algorithm `{raw}`")),
+        })
+    }
+}
+
+impl fmt::Display for SigningAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SigningAlgorithm::Hs256 => "HS256",
+            SigningAlgorithm::Hs384 => "HS384",
+            SigningAlgorithm::Hs512 => "HS512",
+            SigningAlgorithm::Rs256 => "RS256",
+            SigningAlgorithm::Rs384 => "RS384",
+            SigningAlgorithm::Rs512 => "RS512",
+            SigningAlgorithm::EdDsa => "EdDSA",
+        })
+    }
+}
+
+impl Serialize for SigningAlgorithm {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SigningAlgorithm {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Key material for signing and verifying tokens.
+pub enum KeyMaterial {
+    /// Shared secret for the HMAC algorithms.
+    Secret(Vec<u8>),
+    /// PEM-encoded key pair for RSA or EdDSA.
+    Pem { private: Vec<u8>, public: Vec<u8> },
+}
+
+/// Lifetimes and tolerances of issued tokens.
+#[derive(Debug, Clone)]
+pub struct JwtSettings {
+    pub issuer: String,
+    pub access_ttl: Duration,
+    pub refresh_ttl: Duration,
+    /// Allowed clock skew when checking `exp` and `nbf`.
+    pub leeway: Duration,
+}
+
+impl Default for JwtSettings {
+    fn default() -> Self {
+        Self {
+            issuer: env!("CARGO_PKG_NAME").to_owned(),
+            access_ttl: Duration::from_secs(15 * 60),
+            refresh_ttl: Duration::from_secs(14 * 24 * 60 * 60),
+            leeway: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwtError {
+    #[error("{0} requires a shared secret")]
+    SecretRequired(SigningAlgorithm),
+    #[error("{0} requires a PEM key pair")]
+    KeyPairRequired(SigningAlgorithm),
+    #[error("invalid signing key: {0}")]
+    InvalidKey(#[from] jsonwebtoken::errors::Error),
+}
+
+/// Claims carried by an access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: Uuid,
+    pub name: String,
+    /// Space-separated scope names, as in RFC 8693.
+    pub scope: String,
+    pub iss: String,
+    pub iat: i64,
+    pub nbf: i64,
+    pub exp: i64,
+    pub jti: Uuid,
+}
+
+/// Signs and verifies access tokens.
+pub struct Jwt {
+    algorithm: SigningAlgorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    validation: Validation,
+    settings: JwtSettings,
+}
+
+impl fmt::Debug for Jwt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwt")
+            .field("algorithm", &self.algorithm)
+            .field("settings", &self.settings)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Jwt {
+    pub fn new(
+        algorithm: SigningAlgorithm,
+        keys: KeyMaterial,
+        settings: JwtSettings,
+    ) -> Result<Self, JwtError> {
+        let (encoding, decoding) = match (algorithm, keys) {
+            (alg, KeyMaterial::Secret(secret)) if alg.is_hmac() => (
+                EncodingKey::from_secret(&secret),
+                DecodingKey::from_secret(&secret),
+            ),
+            (alg, _) if alg.is_hmac() => return Err(JwtError::SecretRequired(alg)),
+            (SigningAlgorithm::EdDsa, KeyMaterial::Pem { private, public }) => (
+                EncodingKey::from_ed_pem(&private)?,
+                DecodingKey::from_ed_pem(&public)?,
+            ),
+            (_, KeyMaterial::Pem { private, public }) => (
+                EncodingKey::from_rsa_pem(&private)?,
+                DecodingKey::from_rsa_pem(&public)?,
+            ),
+            (alg, KeyMaterial::Secret(_)) => return Err(JwtError::KeyPairRequired(alg)),
+        };
+
+        let mut validation = Validation::new(algorithm.jwt());
+        validation.leeway = settings.leeway.as_secs();
+        validation.validate_nbf = true;
+        validation.set_issuer(&[&settings.issuer]);
+        validation.set_required_spec_claims(&["exp", "nbf", "iss", "sub"]);
+
+        Ok(Self {
+            algorithm,
+            encoding,
+            decoding,
+            validation,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &JwtSettings {
+        &self.settings
+    }
+
+    /// Issues an access token for `user`.
+    pub fn issue(&self, user: &User) -> String {
+        let now = Utc::now().timestamp();
+        let scope: Vec<&str> = user.scopes.iter().map(|s| s.as_str()).collect();
+        let claims = Claims {
+            sub: user.id,
+            name: user.username.clone(),
+            scope: scope.join(" "),
+            iss: self.settings.issuer.clone(),
+            iat: now,
+            nbf: now,
+            exp: now + self.settings.access_ttl.as_secs() as i64,
+            jti: Uuid::now_v7(),
+        };
+        jsonwebtoken::encode(&Header::new(self.algorithm.jwt()), &claims, &self.encoding)
+            .expect("claims serialise and the key was validated at startup")
+    }
+
+    /// Verifies signature, issuer and expiry, returning the claims.
+    pub fn verify(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        jsonwebtoken::decode::<Claims>(token, &self.decoding, &self.validation)
+            .map(|data| data.claims)
+    }
+}
+
+impl Principal {
+    pub fn from_claims(claims: &Claims) -> Self {
+        Self {
+            subject: format!("user:{}", claims.sub),
+            scopes: claims
+                .scope
+                .split_whitespace()
+                .filter_map(Scope::parse)
+                .collect(),
+            credential: Credential::AccessToken {
+                expires_at: claims.exp,
+            },
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Bearer-token authentication and scope checks.
+//!
+//! [`authenticate`] runs in front of every protected route: it resolves the
+//! `Authorization: Bearer <token>` header to a [`Principal`] and stores it in
+//! the request extensions. The token is either an API key (recognisable by
+//! its `ak_` prefix) or, when configured, a JWT access token from [`jwt`].
+//! Handlers take the [`Principal`] as an extractor and call
+//! [`Principal::require`] with the scope they need.
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub mod jwt;
+pub mod password;
+
+use crate::{app::AppState, error::ApiError, model::ApiKey, storage::StorageError};
+
+/// Prefix of every API key token, to make leaked keys easy to grep for.
+pub const TOKEN_PREFIX: &str = "ak_";
+
+/// Prefix of refresh tokens handed out with JWT sessions.
+pub const REFRESH_TOKEN_PREFIX: &str = "rt_";
+
+/// Permission carried by a credential. `admin` implies every other scope.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "read" => Some(Scope::Read),
+            "write" => Some(Scope::Write),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// The authenticated caller of a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    /// Stable identifier of the caller, e.g. `key:<uuid>`.
+    pub subject: String,
+    pub scopes: Vec<Scope>,
+    pub credential: Credential,
+}
+
+/// What a [`Principal`] authenticated with, so long-lived connections can
+/// tell when it stops being accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// An API key, by token hash.
+    ApiKey { token_hash: String },
+    /// A JWT access token expiring at `expires_at` (Unix seconds).
+    AccessToken { expires_at: i64 },
+}
+
+impl Principal {
+    pub fn from_api_key(key: &ApiKey, token_hash: String) -> Self {
+        Self {
+            subject: format!("key:{}", key.id),
+            scopes: key.scopes.clone(),
+            credential: Credential::ApiKey { token_hash },
+        }
+    }
+
+    /// Whether the credential would still be accepted: the access token has
+    /// not expired, or the API key has not been revoked.
+    pub async fn still_valid(&self, state: &AppState) -> Result<bool, StorageError> {
+        match &self.credential {
+            Credential::ApiKey { token_hash } => Ok(state
+                .storage
+                .find_api_key(token_hash)
+                .await?
+                .is_some_and(|key| key.revoked_at.is_none())),
+            Credential::AccessToken { expires_at } => Ok(Utc::now().timestamp() < *expires_at),
+        }
+    }
+
+    pub fn has(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+
+    /// Fails with 403 unless the caller holds `scope`.
+    pub fn require(&self, scope: Scope) -> Result<(), ApiError> {
+        if self.has(scope) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(scope))
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or(ApiError::Unauthorized("missing bearer token"))
+    }
+}
+
+/// Middleware resolving the bearer token into a [`Principal`].
+pub async fn authenticate(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let token = bearer_token(&request)?;
+    let principal = if token.starts_with(TOKEN_PREFIX) {
+        let token_hash = hash_token(token);
+        let key = state
+            .storage
+            .find_api_key(&token_hash)
+            .await?
+            .filter(|key| key.revoked_at.is_none())
+            .ok_or(ApiError::Unauthorized("invalid or revoked token"))?;
+        Principal::from_api_key(&key, token_hash)
+    } else if let Some(jwt) = &state.jwt {
+        let claims = jwt.verify(token).map_err(|err| {
+            tracing::debug!(%err, "rejected access token");
+            ApiError::Unauthorized("invalid or expired access token")
+        })?;
+        Principal::from_claims(&claims)
+    } else {
+        return Err(ApiError::Unauthorized("invalid or revoked token"));
+    };
+
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+fn bearer_token(request: &Request) -> Result<&str, ApiError> {
+    let header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .ok_or(ApiError::Unauthorized("missing bearer token"))?;
+    header
+        .to_str()
+        .ok()
+        .and_then(|value| {
+            let (scheme, token) = value.split_once(' ')?;
+            scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+        })
+        .filter(|token| !token.is_empty())
+        .ok_or(ApiError::Unauthorized("malformed authorization header"))
+}
+
+/// Generates a fresh, random token with the given prefix.
+pub fn generate_token(prefix: &str) -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    format!("{prefix}{}", URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Hash under which a token is stored. Tokens carry 256 bits of entropy,
+/// so a plain SHA-256 is sufficient; no salt or key stretching needed.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Issues a new API key, returning the stored record and the one-time token.
+pub async fn issue_key(
+    state: &AppState,
+    name: String,
+    scopes: Vec<Scope>,
+) -> Result<(ApiKey, String), StorageError> {
+    let token = generate_token(TOKEN_PREFIX);
+    let key = new_key(name, scopes, &token);
+    state
+        .storage
+        .create_api_key(&key, &hash_token(&token))
+        .await?;
+    Ok((key, token))
+}
+
+/// Makes sure an admin key with the given token exists, so a fresh
+/// deployment has a way in. Does nothing if the token is already known.
+pub async fn bootstrap_admin(state: &AppState, token: &str) -> Result<(), StorageError> {
+    let hash = hash_token(token);
+    if state.storage.find_api_key(&hash).await?.is_some() {
+        return Ok(());
+    }
+    let key = new_key("bootstrap".to_owned(), vec![Scope::Admin], token);
+    state.storage.create_api_key(&key, &hash).await?;
+    tracing::info!(id = %key.id, "created bootstrap admin key");
+    Ok(())
+}
+
+fn new_key(name: String, mut scopes: Vec<Scope>, token: &str) -> ApiKey {
+    scopes.sort();
+    scopes.dedup();
+    ApiKey {
+        id: Uuid::now_v7(),
+        name,
+        prefix: token.chars().take(TOKEN_PREFIX.len() + 6).collect(),
+        scopes,
+        created_at: Utc::now(),
+        revoked_at: None,
+    }
+}
@@ -0,0 +1,344 @@
+//! Bulk export and import of items as JSON Lines or CSV.
+//!
+//! Exports are written one page at a time. Imports are spooled to a
+//! temporary file first and then read twice: once to validate every row,
+//! once to store the valid ones. Neither direction holds a whole file in
+//! memory.
+//!
+//! Both formats use the fields of [`Item`]. On import, `id`, `version` and
+//! the timestamps are ignored, so an export can be imported as is; imported
+//! items always get fresh ones.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::{
+    model::{Item, ItemInput},
+    validation::{self, FieldError},
+};
+
+pub const NDJSON: &str = "application/x-ndjson";
+pub const CSV: &str = "text/csv";
+
+/// Columns of a CSV export, in order.
+const CSV_COLUMNS: [&str; 8] = [
+    "id",
+    "name",
+    "description",
+    "quantity",
+    "version",
+    "created_at",
+    "updated_at",
+    "deleted_at",
+];
+
+/// CSV columns holding integers; all other cells are read as text.
+const INTEGER_COLUMNS: [&str; 2] = ["quantity", "version"];
+
+/// Exported fields that are not part of [`ItemInput`].
+const IGNORED_FIELDS: [&str; 5] = ["id", "version", "created_at", "updated_at", "deleted_at"];
+
+/// Failures listed in an [`ImportReport`]; further ones are only counted.
+pub const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Largest upload accepted for import unless configured otherwise.
+pub const DEFAULT_MAX_IMPORT_SIZE: u64 = 100 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    Ndjson,
+    /// A header row naming the columns, then one item per row.
+    Csv,
+}
+
+impl Format {
+    /// The format of a `Content-Type` or `Accept` media type.
+    pub fn from_media_type(essence: &str) -> Option<Self> {
+        match essence {
+            NDJSON | "application/jsonl" => Some(Format::Ndjson),
+            CSV => Some(Format::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Ndjson => NDJSON,
+            Format::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+        }
+    }
+
+    /// What precedes the first row: the header row for CSV.
+    pub fn preamble(self) -> Option<Vec<u8>> {
+        match self {
+            Format::Ndjson => None,
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                writer
+                    .write_record(CSV_COLUMNS)
+                    .expect("writing to memory cannot fail");
+                Some(writer.into_inner().expect("writing to memory cannot fail"))
+            }
+        }
+    }
+
+    /// Encodes `items` as consecutive rows.
+    pub fn encode(self, items: &[Item]) -> Vec<u8> {
+        match self {
+            Format::Ndjson => {
+                let mut out = Vec::new();
+                for item in items {
+                    serde_json::to_writer(&mut out, item).expect("items serialise");
+                    out.push(b'\n');
+                }
+                out
+            }
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                for item in items {
+                    writer.serialize(item).expect("items serialise");
+                }
+                writer.into_inner().expect("writing to memory cannot fail")
+            }
+        }
+    }
+}
+
+/// Outcome of an import.
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    /// Items stored.
+    pub imported: u64,
+    /// Rows that failed to decode or validate.
+    pub failed: u64,
+    /// The first 100 failures, in file order.
+    pub errors: Vec<RowError>,
+}
+
+/// Why one row could not be imported.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RowError {
+    /// Line of the file the row starts on, counting from 1.
+    pub line: u64,
+    pub errors: Vec<FieldError>,
+}
+
+/// One decoded row of an upload.
+#[derive(Debug)]
+pub struct Row {
+    pub line: u64,
+    pub input: Result<ItemInput, Vec<FieldError>>,
+}
+
+/// Reads the rows of an upload. Only I/O failures end the iteration; rows
+/// that do not decode are reported in [`Row::input`].
+pub struct Rows(Reader);
+
+enum Reader {
+    Ndjson {
+        reader: BufReader<File>,
+        line: u64,
+        buffer: Vec<u8>,
+    },
+    Csv {
+        reader: csv::Reader<File>,
+        headers: Option<Vec<String>>,
+        record: csv::ByteRecord,
+    },
+}
+
+impl Rows {
+    pub fn new(format: Format, file: File) -> Self {
+        Rows(match format {
+            Format::Ndjson => Reader::Ndjson {
+                reader: BufReader::new(file),
+                line: 0,
+                buffer: Vec::new(),
+            },
+            Format::Csv => Reader::Csv {
+                reader: csv::ReaderBuilder::new()
+                    .trim(csv::Trim::Headers)
+                    .from_reader(file),
+                headers: None,
+                record: csv::ByteRecord::new(),
+            },
+        })
+    }
+
+    /// Reads every row, counting and collecting failures.
+    pub fn check(self) -> io::Result<ImportReport> {
+        let mut report = ImportReport::default();
+        for row in self {
+            let row = row?;
+            if let Err(errors) = row.input {
+                report.record_failure(row.line, errors);
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl ImportReport {
+    fn record_failure(&mut self, line: u64, errors: Vec<FieldError>) {
+        self.failed += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(RowError { line, errors });
+        }
+    }
+}
+
+impl Iterator for Rows {
+    type Item = io::Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Reader::Ndjson {
+                reader,
+                line,
+                buffer,
+            } => loop {
+                buffer.clear();
+                match reader.read_until(b'\n', buffer) {
+                    Ok(0) => return None,
+                    Ok(_) => *line += 1,
+                    Err(err) => return Some(Err(err)),
+                }
+                if buffer.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return Some(Ok(Row {
+                    line: *line,
+                    input: decode_json_line(buffer),
+                }));
+            },
+            Reader::Csv {
+                reader,
+                headers,
+                record,
+            } => {
+                if headers.is_none() {
+                    match reader.byte_headers() {
+                        Ok(names) => {
+                            *headers = Some(
+                                names
+                                    .iter()
+                                    .map(|name| String::from_utf8_lossy(name).into_owned())
+                                    .collect(),
+                            )
+                        }
+                        Err(err) => return Some(Err(csv_io_error(err))),
+                    }
+                }
+                let headers = headers.as_deref().unwrap_or_default();
+                match reader.read_byte_record(record) {
+                    Ok(false) => None,
+                    Ok(true) => Some(Ok(Row {
+                        line: record.position().map_or(0, csv::Position::line),
+                        input: decode_csv_record(headers, record),
+                    })),
+                    Err(err) => {
+                        let line = err.position().map_or(0, csv::Position::line);
+                        match err.kind() {
+                            csv::ErrorKind::UnequalLengths {
+                                expected_len, len, ..
+                            } => Some(Ok(Row {
+                                line,
+                                input: Err(vec![FieldError::new(
+                                    "",
+                                    "malformed",
+                                    format!("has {len} columns, expected {expected_len}"),
+                                )]),
+                            })),
+                            _ => Some(Err(csv_io_error(err))),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn csv_io_error(err: csv::Error) -> io::Error {
+    match err.into_kind() {
+        csv::ErrorKind::Io(err) => err,
+        other => io::Error::new(io::ErrorKind::InvalidData, format!("{other:?}")),
+    }
+}
+
+fn decode_json_line(line: &[u8]) -> Result<ItemInput, Vec<FieldError>> {
+    let value: Value = serde_json::from_slice(line).map_err(|err| {
+        let message = err.to_string();
+        let reason = message
+            .rsplit_once(" at line ")
+            .map_or(message.as_str(), |(head, _)| head);
+        vec![FieldError::new(
+            "",
+            "malformed",
+            format!("malformed JSON at column {}: {reason}", err.column()),
+        )]
+    })?;
+    decode(value)
+}
+
+/// Turns a CSV record into the JSON object it stands for. Empty cells are
+/// left out, as if the field were missing.
+fn decode_csv_record(
+    headers: &[String],
+    record: &csv::ByteRecord,
+) -> Result<ItemInput, Vec<FieldError>> {
+    let mut fields = Map::new();
+    for (name, cell) in headers.iter().zip(record) {
+        if cell.is_empty() {
+            continue;
+        }
+        let Ok(cell) = std::str::from_utf8(cell) else {
+            return Err(vec![FieldError::new(
+                name.as_str(),
+                "invalid_value",
+                "is not valid UTF-8",
+            )]);
+        };
+        let value = match cell.parse::<i64>() {
+            Ok(number) if INTEGER_COLUMNS.contains(&name.as_str()) => Value::from(number),
+            _ => Value::from(cell),
+        };
+        fields.insert(name.clone(), value);
+    }
+    decode(Value::Object(fields))
+}
+
+/// Decodes and validates one row exactly like an item request body.
+fn decode(mut value: Value) -> Result<ItemInput, Vec<FieldError>> {
+    if let Value::Object(fields) = &mut value {
+        for name in IGNORED_FIELDS {
+            fields.remove(name);
+        }
+    }
+    let input: ItemInput = serde_path_to_error::deserialize(value).map_err(|err| {
+        vec![
+            validation::from_serde(&err)
+                .unwrap_or_else(|| FieldError::new("", "malformed", err.inner().to_string())),
+        ]
+    })?;
+    input
+        .validate()
+        .map_err(|errors| validation::from_validator(&errors))?;
+    Ok(input)
+}
@@ -0,0 +1,290 @@
+//! Conditional requests on versioned resources.
+//!
+//! A resource's version is sent as a strong `ETag`, suffixed with the
+//! format and content coding of the body unless that is plain JSON: `"3"`,
+//! `"3-csv"`, `"3-msgpack-gzip"`. [`revalidate`] adds the suffix and answers
+//! `If-None-Match` on `GET` with `304 Not Modified`; handlers that can tell
+//! a match before building the body check [`IfNoneMatch`] first. Writes
+//! turn `If-Match` into a [`VersionGuard`] the storage layer checks
+//! atomically, so a stale client gets `412 Precondition Failed` instead of
+//! overwriting someone else's change; any representation's tag will do
+//! there. Servers can additionally require `If-Match` on every write.
+
+use std::fmt;
+
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, Request},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{bulk, error::ApiError, extract::media_type, negotiate, storage::VersionGuard};
+
+/// The `ETag` of a resource at `version`, before [`revalidate`] makes it
+/// specific to the representation.
+pub fn etag(version: i64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{version}\"")).expect("digits are valid header values")
+}
+
+/// A response for a resource at a given version, carrying its `ETag`.
+pub struct Tagged<T>(pub i64, pub T);
+
+impl<T: IntoResponse> IntoResponse for Tagged<T> {
+    fn into_response(self) -> Response {
+        let Tagged(version, inner) = self;
+        ([(header::ETAG, etag(version))], inner).into_response()
+    }
+}
+
+/// Middleware suffixing `ETag`s with the representation they describe and
+/// answering `If-None-Match`. Must run outside transcoding and compression,
+/// so it sees the body as sent.
+///
+/// It only decides once the handler has built the response and it has been
+/// compressed, so its 304s save bandwidth but not server work. Responses
+/// that are already 304, from an early [`IfNoneMatch`] check, pass through.
+pub async fn revalidate(request: Request, next: Next) -> Response {
+    let conditional = matches!(*request.method(), Method::GET | Method::HEAD);
+    let if_none_match = tag_list(request.headers(), header::IF_NONE_MATCH);
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return response;
+    }
+
+    let Some(mut tag) = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|value| parse_tags(value.to_str().ok()?))
+        .and_then(|tags| tags.into_iter().next())
+    else {
+        return response;
+    };
+    tag.opaque.push_str(&representation(response.headers()));
+    let value = HeaderValue::try_from(tag.to_string()).expect("tags stay valid header values");
+    response.headers_mut().insert(header::ETAG, value);
+
+    if !(conditional && response.status().is_success()) {
+        return response;
+    }
+    match if_none_match {
+        Err(err) => err.into_response(),
+        // `If-None-Match` uses weak comparison.
+        Ok(Some(TagList::Any)) => not_modified(response),
+        Ok(Some(TagList::Tags(tags))) if tags.iter().any(|t| t.opaque == tag.opaque) => {
+            not_modified(response)
+        }
+        Ok(_) => response,
+    }
+}
+
+/// The `ETag` suffix for a response's body: its format unless JSON, then
+/// its content coding, if any.
+fn representation(headers: &HeaderMap) -> String {
+    let mut suffix = format_suffix(media_type(headers).as_deref().unwrap_or_default()).to_owned();
+    if let Some(coding) = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    {
+        suffix.push('-');
+        suffix.push_str(coding);
+    }
+    suffix
+}
+
+/// The `ETag` suffix for a body of `media_type`.
+fn format_suffix(media_type: &str) -> &'static str {
+    match media_type {
+        negotiate::MSGPACK => "-msgpack",
+        bulk::CSV => "-csv",
+        _ => "",
+    }
+}
+
+/// The `If-None-Match` of a read, for handlers to answer before building the
+/// body. [`revalidate`] covers whatever they leave to it.
+#[derive(Debug, Clone, Default)]
+pub struct IfNoneMatch {
+    tags: Option<TagList>,
+    /// Content codings the client accepts besides `identity`.
+    codings: Vec<String>,
+}
+
+impl IfNoneMatch {
+    /// `304 Not Modified` if the client already holds version `version` of
+    /// the resource as `media_type`, in a content coding it still accepts.
+    /// The response carries the (strong) tag that matched.
+    pub fn not_modified(&self, version: i64, media_type: &str) -> Option<Response> {
+        let format = format_suffix(media_type);
+        let tag = match self.tags.as_ref()? {
+            TagList::Any => EntityTag {
+                weak: false,
+                opaque: format!("{version}{format}"),
+            },
+            // `If-None-Match` uses weak comparison.
+            TagList::Tags(tags) => EntityTag {
+                weak: false,
+                opaque: tags
+                    .iter()
+                    .find(|tag| {
+                        let Some(rest) = tag.opaque.strip_prefix(&version.to_string()) else {
+                            return false;
+                        };
+                        match rest.strip_prefix(format) {
+                            Some("") => true,
+                            Some(coding) => coding.strip_prefix('-').is_some_and(|coding| {
+                                !matches!(coding, "msgpack" | "csv")
+                                    && self.codings.iter().any(|c| c == coding || c == "*")
+                            }),
+                            None => false,
+                        }
+                    })?
+                    .opaque
+                    .clone(),
+            },
+        };
+        let value = HeaderValue::try_from(tag.to_string()).expect("tags stay valid header values");
+        Some((StatusCode::NOT_MODIFIED, [(header::ETAG, value)]).into_response())
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for IfNoneMatch {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let codings = parts
+            .headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|coding| {
+                let mut params = coding.split(';');
+                let name = params.next()?.trim().to_ascii_lowercase();
+                let refused = params.any(|param| {
+                    param.split_once('=').is_some_and(|(key, q)| {
+                        key.trim().eq_ignore_ascii_case("q") && q.trim().parse() == Ok(0.0)
+                    })
+                });
+                (!refused && !name.is_empty()).then_some(name)
+            })
+            .collect();
+        Ok(Self {
+            tags: tag_list(&parts.headers, header::IF_NONE_MATCH)?,
+            codings,
+        })
+    }
+}
+
+/// Turns a response into `304 Not Modified`, keeping its headers.
+fn not_modified(response: Response) -> Response {
+    let (mut parts, _) = response.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::empty())
+}
+
+/// The version an entity tag names, whatever representation it is for.
+fn version(opaque: &str) -> Option<i64> {
+    opaque.split('-').next()?.parse().ok()
+}
+
+/// One entity tag from a conditional header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EntityTag {
+    weak: bool,
+    opaque: String,
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+        write!(f, "\"{}\"", self.opaque)
+    }
+}
+
+/// Contents of an `If-Match` or `If-None-Match` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TagList {
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+/// The conditional headers of a write. `If-None-Match` is handled by
+/// [`revalidate`].
+#[derive(Debug, Clone, Default)]
+pub struct Preconditions {
+    if_match: Option<TagList>,
+}
+
+impl Preconditions {
+    /// The guard a write must pass. Fails with 428 if `required` and the
+    /// request has no `If-Match`.
+    pub fn write_guard(&self, required: bool) -> Result<VersionGuard, ApiError> {
+        match &self.if_match {
+            None if required => Err(ApiError::PreconditionRequired),
+            None | Some(TagList::Any) => Ok(VersionGuard::Any),
+            // `If-Match` uses strong comparison, so weak tags never match.
+            Some(TagList::Tags(tags)) => Ok(VersionGuard::OneOf(
+                tags.iter()
+                    .filter(|tag| !tag.weak)
+                    .filter_map(|tag| version(&tag.opaque))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Preconditions {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            if_match: tag_list(&parts.headers, header::IF_MATCH)?,
+        })
+    }
+}
+
+/// Parses every occurrence of `name` into one list.
+fn tag_list(headers: &HeaderMap, name: HeaderName) -> Result<Option<TagList>, ApiError> {
+    let malformed = || ApiError::BadRequest(format!("malformed {name} header"));
+    let mut tags = Vec::new();
+    let mut seen = false;
+    for value in headers.get_all(&name) {
+        seen = true;
+        let value = value.to_str().map_err(|_| malformed())?.trim();
+        if value == "*" {
+            return Ok(Some(TagList::Any));
+        }
+        tags.extend(parse_tags(value).ok_or_else(malformed)?);
+    }
+    Ok(seen.then_some(TagList::Tags(tags)))
+}
+
+/// Parses a comma-separated list of `"opaque"` or `W/"opaque"` tags.
+fn parse_tags(mut raw: &str) -> Option<Vec<EntityTag>> {
+    let mut tags = Vec::new();
+    loop {
+        raw = raw.trim_start_matches([' ', '\t', ',']);
+        if raw.is_empty() {
+            return Some(tags);
+        }
+        let weak = raw.starts_with("W/");
+        if weak {
+            raw = &raw[2..];
+        }
+        let rest = raw.strip_prefix('"')?;
+        let end = rest.find('"')?;
+        tags.push(EntityTag {
+            weak,
+            opaque: rest[..end].to_owned(),
+        });
+        raw = &rest[end + 1..];
+        if !(raw.is_empty() || raw.starts_with([' ', '\t', ','])) {
+            return None;
+        }
+    }
+}
@@ -0,0 +1,802 @@
+//! Service configuration.
+//!
+//! Settings are layered, later layers overriding earlier ones:
+//!
+//! 1. built-in defaults,
+//! 2. a TOML file given with `--config` (or `API_APP_CONFIG`),
+//! 3. `API_APP_*` environment variables,
+//! 4. command-line flags.
+//!
+//! Each layer is turned into a partial TOML table; the tables are merged and
+//! the result deserialised into [`Config`], which is then validated as a
+//! whole so every problem is reported at once.
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Parser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use toml::{Table, Value};
+
+use crate::{
+    auth::{
+        TOKEN_PREFIX,
+        jwt::{Jwt, JwtError, JwtSettings, KeyMaterial, SigningAlgorithm},
+    },
+    bulk::DEFAULT_MAX_IMPORT_SIZE,
+    logging::LogFormat,
+    negotiate::DEFAULT_COMPRESSION_MIN_SIZE,
+    rate_limit::{Quota, RateLimitConfig},
+    trash::TrashSettings,
+    webhooks::WebhookSettings,
+};
+
+/// Effective configuration of the service.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub api: ApiConfig,
+    pub log: LogConfig,
+    pub auth: AuthConfig,
+    pub rate_limits: RateLimitsConfig,
+    pub webhooks: WebhooksConfig,
+    pub trash: TrashConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Seconds between a shutdown signal and closing the listener, during
+    /// which `/readyz` fails but requests are still served.
+    pub shutdown_delay: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+            shutdown_delay: 5,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    pub fn shutdown_delay(&self) -> Duration {
+        Duration::from_secs(self.shutdown_delay)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// SQLite file; data is kept in memory when unset.
+    pub path: Option<PathBuf>,
+}
+
+/// Behaviour of the HTTP API itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Reject `PUT`/`PATCH`/`DELETE` on versioned resources without `If-Match`.
+    pub require_if_match: bool,
+    /// Seconds a response is replayed for repeats of its `Idempotency-Key`.
+    pub idempotency_ttl: u64,
+    /// Smallest response, in bytes, that is compressed.
+    pub compression_min_size: u16,
+    /// Largest upload, in bytes, accepted by `/items/import`.
+    pub max_import_size: u64,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            require_if_match: false,
+            idempotency_ttl: 24 * 60 * 60,
+            compression_min_size: DEFAULT_COMPRESSION_MIN_SIZE,
+            max_import_size: DEFAULT_MAX_IMPORT_SIZE,
+        }
+    }
+}
+
+impl ApiConfig {
+    pub fn idempotency_ttl(&self) -> Duration {
+        Duration::from_secs(self.idempotency_ttl)
+    }
+}
+
+/// Delivery of outbound webhooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    /// Attempts before a delivery is given up.
+    pub max_attempts: u32,
+    /// Seconds before the first retry; doubled after each further failure.
+    pub retry_backoff: u64,
+    /// Seconds to wait for a receiver to answer.
+    pub timeout: u64,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        let settings = WebhookSettings::default();
+        Self {
+            max_attempts: settings.max_attempts,
+            retry_backoff: settings.retry_backoff.as_secs(),
+            timeout: settings.timeout.as_secs(),
+        }
+    }
+}
+
+impl WebhooksConfig {
+    pub fn settings(&self) -> WebhookSettings {
+        WebhookSettings {
+            max_attempts: self.max_attempts,
+            retry_backoff: Duration::from_secs(self.retry_backoff),
+            timeout: Duration::from_secs(self.timeout),
+            ..WebhookSettings::default()
+        }
+    }
+}
+
+/// Retention of deleted items.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    /// Seconds a deleted item can be restored before it is purged.
+    pub retention: u64,
+    /// Seconds between two purges.
+    pub purge_interval: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        let settings = TrashSettings::default();
+        Self {
+            retention: settings.retention.as_secs(),
+            purge_interval: settings.purge_interval.as_secs(),
+        }
+    }
+}
+
+impl TrashConfig {
+    pub fn settings(&self) -> TrashSettings {
+        TrashSettings {
+            retention: Duration::from_secs(self.retention),
+            purge_interval: Duration::from_secs(self.purge_interval),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A `tracing` filter directive such as `info` or `api_app=debug,info`.
+    pub level: String,
+    /// `pretty` or `json`.
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_owned(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Token of an admin API key created at startup if missing.
+    pub bootstrap_key: Option<Secret>,
+    pub jwt: JwtConfig,
+}
+
+/// JWT sessions are enabled when a secret or a key pair is configured.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    pub algorithm: SigningAlgorithm,
+    /// Shared secret for the HMAC algorithms.
+    pub secret: Option<Secret>,
+    /// PEM private key for RSA or EdDSA.
+    pub private_key: Option<PathBuf>,
+    pub public_key: Option<PathBuf>,
+    /// Access token lifetime in seconds.
+    pub access_ttl: u64,
+    /// Refresh token lifetime in seconds.
+    pub refresh_ttl: u64,
+    /// Tolerated clock skew in seconds.
+    pub leeway: u64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        let settings = JwtSettings::default();
+        Self {
+            algorithm: SigningAlgorithm::Hs256,
+            secret: None,
+            private_key: None,
+            public_key: None,
+            access_ttl: settings.access_ttl.as_secs(),
+            refresh_ttl: settings.refresh_ttl.as_secs(),
+            leeway: settings.leeway.as_secs(),
+        }
+    }
+}
+
+impl JwtConfig {
+    pub fn enabled(&self) -> bool {
+        self.secret.is_some() || self.private_key.is_some()
+    }
+
+    /// Loads the keys and builds the signer, or `None` if disabled.
+    pub fn build(&self) -> Result<Option<Jwt>, ConfigError> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        let keys = match (&self.secret, &self.private_key, &self.public_key) {
+            (Some(secret), _, _) => KeyMaterial::Secret(secret.expose().as_bytes().to_vec()),
+            (None, Some(private), Some(public)) => KeyMaterial::Pem {
+                private: read(private)?,
+                public: read(public)?,
+            },
+            _ => {
+                return Err(ConfigError::Invalid(vec![
+                    "auth.jwt.public_key is required".into(),
+                ]));
+            }
+        };
+        let settings = JwtSettings {
+            access_ttl: Duration::from_secs(self.access_ttl),
+            refresh_ttl: Duration::from_secs(self.refresh_ttl),
+            leeway: Duration::from_secs(self.leeway),
+            ..JwtSettings::default()
+        };
+        Ok(Some(Jwt::new(self.algorithm, keys, settings)?))
+    }
+}
+
+/// Quota per route group, written as `<requests>/<period>` or `off`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitsConfig {
+    #[serde(with = "limit")]
+    pub api: Option<Quota>,
+    #[serde(with = "limit")]
+    pub admin: Option<Quota>,
+    #[serde(with = "limit")]
+    pub auth: Option<Quota>,
+    /// Reverse proxies in front of the server; anonymous clients are told
+    /// apart by the `X-Forwarded-For` entries they add.
+    pub trusted_proxies: usize,
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        Self {
+            api: Some(Quota::per_minute(600)),
+            admin: Some(Quota::per_minute(60)),
+            auth: Some(Quota::per_minute(10)),
+            trusted_proxies: 0,
+        }
+    }
+}
+
+impl From<&RateLimitsConfig> for RateLimitConfig {
+    fn from(config: &RateLimitsConfig) -> Self {
+        Self {
+            api: config.api,
+            admin: config.admin,
+            auth: config.auth,
+            trusted_proxies: config.trusted_proxies,
+        }
+    }
+}
+
+mod limit {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(quota: &Option<Quota>, s: S) -> Result<S::Ok, S::Error> {
+        match quota {
+            Some(quota) => s.collect_str(quota),
+            None => s.serialize_str("off"),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Quota>, D::Error> {
+        match String::deserialize(d)?.as_str() {
+            "off" => Ok(None),
+            raw => raw.parse().map(Some).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// A configuration value that must never be printed or logged.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Secrets serialise redacted, which is what `--print-config` relies on.
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str("<redacted>")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid configuration: {0}")]
+    Parse(String),
+    #[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+    #[error(transparent)]
+    Jwt(#[from] JwtError),
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, ConfigError> {
+    std::fs::read(path).map_err(|source| ConfigError::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// Command-line interface. Every setting flag is optional; unset flags
+/// leave lower layers in charge.
+#[derive(Debug, Default, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// TOML configuration file.
+    #[arg(long, env = "API_APP_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration, secrets redacted, and exit.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Address to bind to.
+    #[arg(long)]
+    pub bind: Option<IpAddr>,
+
+    /// Port to listen on.
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Seconds to keep serving, reported unready, after a shutdown signal.
+    #[arg(long)]
+    pub shutdown_delay: Option<u64>,
+
+    /// SQLite database file. Data is kept in memory when not configured.
+    #[arg(long)]
+    pub database: Option<PathBuf>,
+
+    /// Require `If-Match` on writes to versioned resources.
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub require_if_match: Option<bool>,
+
+    /// Seconds to replay responses for a repeated `Idempotency-Key`.
+    #[arg(long)]
+    pub idempotency_ttl: Option<u64>,
+
+    /// Smallest response, in bytes, to compress for clients that accept it.
+    #[arg(long)]
+    pub compression_min_size: Option<u16>,
+
+    /// Largest upload, in bytes, accepted by `/items/import`.
+    #[arg(long)]
+    pub max_import_size: Option<u64>,
+
+    /// Log filter, e.g. `info` or `api_app=debug,info`.
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Log output: `pretty` or `json`.
+    #[arg(long)]
+    pub log_format: Option<String>,
+
+    /// Token of an admin API key to create at startup if it does not exist
+    /// yet. Use it to issue regular keys, then drop it.
+    #[arg(long)]
+    pub bootstrap_key: Option<String>,
+
+    /// JWT signing algorithm: HS256/384/512, RS256/384/512 or EdDSA.
+    #[arg(long)]
+    pub jwt_algorithm: Option<String>,
+
+    /// Shared secret for the HMAC algorithms.
+    #[arg(long)]
+    pub jwt_secret: Option<String>,
+
+    /// PEM private key for RSA or EdDSA.
+    #[arg(long)]
+    pub jwt_private_key: Option<PathBuf>,
+
+    /// PEM public key matching `--jwt-private-key`.
+    #[arg(long)]
+    pub jwt_public_key: Option<PathBuf>,
+
+    /// Access token lifetime in seconds.
+    #[arg(long)]
+    pub jwt_access_ttl: Option<u64>,
+
+    /// Refresh token lifetime in seconds.
+    #[arg(long)]
+    pub jwt_refresh_ttl: Option<u64>,
+
+    /// Tolerated clock skew in seconds when checking token expiry.
+    #[arg(long)]
+    pub jwt_leeway: Option<u64>,
+
+    /// Quota per client for resource endpoints, e.g. `600/min`, or `off`.
+    #[arg(long)]
+    pub rate_limit_api: Option<String>,
+
+    /// Quota per client for `/admin` endpoints.
+    #[arg(long)]
+    pub rate_limit_admin: Option<String>,
+
+    /// Quota per client for `/auth` endpoints.
+    #[arg(long)]
+    pub rate_limit_auth: Option<String>,
+
+    /// Reverse proxies in front of the server whose `X-Forwarded-For`
+    /// entries identify clients.
+    #[arg(long)]
+    pub trusted_proxies: Option<u64>,
+
+    /// Attempts per webhook delivery before giving up.
+    #[arg(long)]
+    pub webhook_max_attempts: Option<u32>,
+
+    /// Seconds before the first webhook retry; doubled after each failure.
+    #[arg(long)]
+    pub webhook_retry_backoff: Option<u64>,
+
+    /// Seconds to wait for a webhook receiver to answer.
+    #[arg(long)]
+    pub webhook_timeout: Option<u64>,
+
+    /// Seconds a deleted item stays restorable before it is purged.
+    #[arg(long)]
+    pub trash_retention: Option<u64>,
+
+    /// Seconds between two purges of deleted items.
+    #[arg(long)]
+    pub trash_purge_interval: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Str,
+    Int,
+    Bool,
+}
+
+/// Environment variables and the settings they override.
+const ENV: &[(&str, &str, Kind)] = &[
+    ("API_APP_BIND", "server.bind", Kind::Str),
+    ("API_APP_PORT", "server.port", Kind::Int),
+    ("API_APP_SHUTDOWN_DELAY", "server.shutdown_delay", Kind::Int),
+    ("API_APP_DATABASE", "database.path", Kind::Str),
+    (
+        "API_APP_REQUIRE_IF_MATCH",
+        "api.require_if_match",
+        Kind::Bool,
+    ),
+    ("API_APP_IDEMPOTENCY_TTL", "api.idempotency_ttl", Kind::Int),
+    (
+        "API_APP_COMPRESSION_MIN_SIZE",
+        "api.compression_min_size",
+        Kind::Int,
+    ),
+    ("API_APP_MAX_IMPORT_SIZE", "api.max_import_size", Kind::Int),
+    ("API_APP_LOG_LEVEL", "log.level", Kind::Str),
+    ("API_APP_LOG_FORMAT", "log.format", Kind::Str),
+    ("API_APP_BOOTSTRAP_KEY", "auth.bootstrap_key", Kind::Str),
+    ("API_APP_JWT_ALGORITHM", "auth.jwt.algorithm", Kind::Str),
+    ("API_APP_JWT_SECRET", "auth.jwt.secret", Kind::Str),
+    ("API_APP_JWT_PRIVATE_KEY", "auth.jwt.private_key", Kind::Str),
+    ("API_APP_JWT_PUBLIC_KEY", "auth.jwt.public_key", Kind::Str),
+    ("API_APP_JWT_ACCESS_TTL", "auth.jwt.access_ttl", Kind::Int),
+    ("API_APP_JWT_REFRESH_TTL", "auth.jwt.refresh_ttl", Kind::Int),
+    ("API_APP_JWT_LEEWAY", "auth.jwt.leeway", Kind::Int),
+    ("API_APP_RATE_LIMIT_API", "rate_limits.api", Kind::Str),
+    ("API_APP_RATE_LIMIT_ADMIN", "rate_limits.admin", Kind::Str),
+    ("API_APP_RATE_LIMIT_AUTH", "rate_limits.auth", Kind::Str),
+    (
+        "API_APP_TRUSTED_PROXIES",
+        "rate_limits.trusted_proxies",
+        Kind::Int,
+    ),
+    (
+        "API_APP_WEBHOOK_MAX_ATTEMPTS",
+        "webhooks.max_attempts",
+        Kind::Int,
+    ),
+    (
+        "API_APP_WEBHOOK_RETRY_BACKOFF",
+        "webhooks.retry_backoff",
+        Kind::Int,
+    ),
+    ("API_APP_WEBHOOK_TIMEOUT", "webhooks.timeout", Kind::Int),
+    ("API_APP_TRASH_RETENTION", "trash.retention", Kind::Int),
+    (
+        "API_APP_TRASH_PURGE_INTERVAL",
+        "trash.purge_interval",
+        Kind::Int,
+    ),
+];
+
+impl Cli {
+    /// Settings given on the command line, as a partial config table.
+    fn overrides(&self) -> Table {
+        let mut table = Table::new();
+        let mut set = |path: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                insert(&mut table, path, value);
+            }
+        };
+        let string = |v: &Option<String>| v.clone().map(Value::String);
+        let path = |v: &Option<PathBuf>| v.as_ref().map(|p| Value::String(p.display().to_string()));
+        let int = |v: Option<u64>| v.map(|n| Value::Integer(n as i64));
+
+        set(
+            "server.bind",
+            self.bind.map(|ip| Value::String(ip.to_string())),
+        );
+        set("server.port", self.port.map(|p| Value::Integer(p.into())));
+        set("server.shutdown_delay", int(self.shutdown_delay));
+        set("database.path", path(&self.database));
+        set(
+            "api.require_if_match",
+            self.require_if_match.map(Value::Boolean),
+        );
+        set("api.idempotency_ttl", int(self.idempotency_ttl));
+        set(
+            "api.compression_min_size",
+            int(self.compression_min_size.map(u64::from)),
+        );
+        set("api.max_import_size", int(self.max_import_size));
+        set("log.level", string(&self.log_level));
+        set("log.format", string(&self.log_format));
+        set("auth.bootstrap_key", string(&self.bootstrap_key));
+        set("auth.jwt.algorithm", string(&self.jwt_algorithm));
+        set("auth.jwt.secret", string(&self.jwt_secret));
+        set("auth.jwt.private_key", path(&self.jwt_private_key));
+        set("auth.jwt.public_key", path(&self.jwt_public_key));
+        set("auth.jwt.access_ttl", int(self.jwt_access_ttl));
+        set("auth.jwt.refresh_ttl", int(self.jwt_refresh_ttl));
+        set("auth.jwt.leeway", int(self.jwt_leeway));
+        set("rate_limits.api", string(&self.rate_limit_api));
+        set("rate_limits.admin", string(&self.rate_limit_admin));
+        set("rate_limits.auth", string(&self.rate_limit_auth));
+        set("rate_limits.trusted_proxies", int(self.trusted_proxies));
+        set(
+            "webhooks.max_attempts",
+            self.webhook_max_attempts.map(|n| Value::Integer(n.into())),
+        );
+        set("webhooks.retry_backoff", int(self.webhook_retry_backoff));
+        set("webhooks.timeout", int(self.webhook_timeout));
+        set("trash.retention", int(self.trash_retention));
+        set("trash.purge_interval", int(self.trash_purge_interval));
+        table
+    }
+}
+
+impl Config {
+    /// Loads and validates the configuration from all layers.
+    pub fn load(
+        cli: &Cli,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut table = match &cli.config {
+            Some(path) => {
+                let raw = read(path)?;
+                let raw =
+                    String::from_utf8(raw).map_err(|err| ConfigError::Parse(err.to_string()))?;
+                toml::from_str(&raw).map_err(|err| {
+                    ConfigError::Parse(format!("{}: {}", path.display(), err.message()))
+                })?
+            }
+            None => Table::new(),
+        };
+        merge(&mut table, env_overrides(env)?);
+        merge(&mut table, cli.overrides());
+
+        let config: Config = Config::deserialize(table)
+            .map_err(|err| ConfigError::Parse(err.message().to_owned()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks settings that depend on each other or on the environment.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = Vec::new();
+
+        if tracing_subscriber::EnvFilter::try_new(&self.log.level).is_err() {
+            errors.push(format!(
+                "log.level `{}` is not a valid filter",
+                self.log.level
+            ));
+        }
+        if let Some(dir) = self.database.path.as_deref().and_then(Path::parent)
+            && !dir.as_os_str().is_empty()
+            && !dir.is_dir()
+        {
+            errors.push(format!(
+                "database.path: directory {} does not exist",
+                dir.display()
+            ));
+        }
+
+        let jwt = &self.auth.jwt;
+        if jwt.enabled() {
+            let has_pair = jwt.private_key.is_some() || jwt.public_key.is_some();
+            if jwt.algorithm.is_hmac() {
+                if jwt.secret.is_none() {
+                    errors.push(format!("auth.jwt.secret is required for {}", jwt.algorithm));
+                }
+                if has_pair {
+                    errors.push(format!(
+                        "auth.jwt key files are not used with {}",
+                        jwt.algorithm
+                    ));
+                }
+            } else {
+                if jwt.secret.is_some() {
+                    errors.push(format!(
+                        "auth.jwt.secret is not used with {}",
+                        jwt.algorithm
+                    ));
+                }
+                for (name, path) in [
+                    ("private_key", &jwt.private_key),
+                    ("public_key", &jwt.public_key),
+                ] {
+                    match path {
+                        None => errors
+                            .push(format!("auth.jwt.{name} is required for {}", jwt.algorithm)),
+                        Some(path) if !path.is_file() => errors
+                            .push(format!("auth.jwt.{name}: {} is not a file", path.display())),
+                        Some(_) => {}
+                    }
+                }
+            }
+            if jwt.access_ttl == 0 || jwt.refresh_ttl == 0 {
+                errors.push("auth.jwt token lifetimes must be positive".to_owned());
+            }
+            if jwt.access_ttl >= jwt.refresh_ttl {
+                errors.push("auth.jwt.access_ttl must be shorter than refresh_ttl".to_owned());
+            }
+        } else if jwt.public_key.is_some() {
+            errors.push("auth.jwt.public_key is set without a private key".to_owned());
+        }
+        if self.api.idempotency_ttl == 0 {
+            errors.push("api.idempotency_ttl must be positive".to_owned());
+        }
+        if self.api.max_import_size == 0 {
+            errors.push("api.max_import_size must be positive".to_owned());
+        }
+        let webhooks = &self.webhooks;
+        if webhooks.max_attempts == 0 || webhooks.retry_backoff == 0 || webhooks.timeout == 0 {
+            errors.push("webhooks settings must be positive".to_owned());
+        }
+        if self.trash.purge_interval == 0 {
+            errors.push("trash.purge_interval must be positive".to_owned());
+        }
+        if let Some(key) = &self.auth.bootstrap_key
+            && !key.expose().starts_with(TOKEN_PREFIX)
+        {
+            errors.push(format!(
+                "auth.bootstrap_key must start with `{TOKEN_PREFIX}`"
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    /// The configuration as TOML, with secrets redacted.
+    pub fn to_redacted_toml(&self) -> String {
+        toml::to_string(self).expect("config serialises to TOML")
+    }
+}
+
+fn env_overrides(env: impl IntoIterator<Item = (String, String)>) -> Result<Table, ConfigError> {
+    let mut table = Table::new();
+    let mut errors = Vec::new();
+    for (name, raw) in env {
+        let Some((_, path, kind)) = ENV.iter().find(|(var, _, _)| *var == name) else {
+            continue;
+        };
+        let value = match kind {
+            Kind::Str => Value::String(raw),
+            Kind::Int => match raw.trim().parse() {
+                Ok(n) => Value::Integer(n),
+                Err(_) => {
+                    errors.push(format!("{name}: `{raw}` is not an integer"));
+                    continue;
+                }
+            },
+            Kind::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" => Value::Boolean(true),
+                "false" | "0" | "no" => Value::Boolean(false),
+                _ => {
+                    errors.push(format!("{name}: `{raw}` is not a boolean"));
+                    continue;
+                }
+            },
+        };
+        insert(&mut table, path, value);
+    }
+    if errors.is_empty() {
+        Ok(table)
+    } else {
+        Err(ConfigError::Invalid(errors))
+    }
+}
+
+/// Sets a dotted `path` in `table`, creating intermediate tables.
+fn insert(table: &mut Table, path: &str, value: Value) {
+    let mut segments: Vec<&str> = path.split('.').collect();
+    let last = segments.pop().expect("paths are non-empty");
+    let mut current = table;
+    for segment in segments {
+        current = current
+            .entry(segment)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .expect("intermediate settings are tables");
+    }
+    current.insert(last.to_owned(), value);
+}
+
+/// Deep-merges `overlay` into `base`; overlay values win.
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(nested)) => merge(existing, nested),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
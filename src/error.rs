@@ -0,0 +1,222 @@
+//! The single error type returned by every handler.
+//!
+//! All failures are rendered as
+//!
+//! ```json
+//! { "error": { "code": "not_found", "message": "item not found" } }
+//! ```
+//!
+//! where `code` is a stable, machine-readable identifier and `message` is
+//! meant for humans. Validation failures add a `details` array with one
+//! entry per offending field. The mapping from error to HTTP status lives in
+//! [`ApiError::status`] and nowhere else.
+
+use axum::{
+    Json,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{auth::Scope, storage::StorageError, validation::FieldError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// The request is malformed in a way not tied to a specific field.
+    #[error("{0}")]
+    BadRequest(String),
+
+    /// One or more fields failed validation.
+    #[error("request validation failed")]
+    Validation(Vec<FieldError>),
+
+    /// No valid credentials were presented.
+    #[error("{0}")]
+    Unauthorized(&'static str),
+
+    /// The credentials lack the scope the operation needs.
+    #[error("this operation requires the `{}` scope", .0.as_str())]
+    Forbidden(Scope),
+
+    /// The client exceeded its rate limit.
+    #[error("rate limit exceeded, retry in {retry_after} seconds")]
+    RateLimited { retry_after: u64 },
+
+    #[error("{0} not found")]
+    NotFound(&'static str),
+
+    #[error("method not allowed")]
+    MethodNotAllowed,
+
+    /// The request conflicts with the current state of the resource.
+    #[error("{0}")]
+    Conflict(String),
+
+    /// An `If-Match` condition did not hold: the resource changed since the
+    /// client last saw it.
+    #[error("the resource has been modified; fetch it again and retry")]
+    PreconditionFailed,
+
+    /// The server requires conditional writes and no `If-Match` was sent.
+    #[error("this request requires an If-Match header")]
+    PreconditionRequired,
+
+    /// An `Idempotency-Key` was reused for a different request.
+    #[error("this idempotency key was already used for a different request")]
+    IdempotencyKeyReused,
+
+    /// The request body is larger than the server accepts.
+    #[error("{0}")]
+    PayloadTooLarge(String),
+
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+
+    /// `Accept` rules out every representation the resource has.
+    #[error("{0}")]
+    NotAcceptable(String),
+
+    /// Anything the client cannot fix. The message is logged, not returned.
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+/// Wire format of an error response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// Stable, machine-readable error code, e.g. `not_found`.
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<FieldError>>,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ApiError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            ApiError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable identifier clients can match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::MethodNotAllowed => "method_not_allowed",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PreconditionFailed => "precondition_failed",
+            ApiError::PreconditionRequired => "precondition_required",
+            ApiError::IdempotencyKeyReused => "idempotency_key_reused",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::NotAcceptable(_) => "not_acceptable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    fn body(&self) -> ErrorBody {
+        let message = match self {
+            ApiError::Internal(_) => "internal server error".to_owned(),
+            other => other.to_string(),
+        };
+        let details = match self {
+            ApiError::Validation(fields) => Some(fields.clone()),
+            _ => None,
+        };
+        ErrorBody {
+            error: ErrorDetail {
+                code: self.code(),
+                message,
+                details,
+            },
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let ApiError::Internal(detail) = &self {
+            tracing::error!(%detail, "internal error");
+        }
+        let mut response = (self.status(), Json(self.body())).into_response();
+        let headers = response.headers_mut();
+        match self {
+            ApiError::Unauthorized(_) => {
+                headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            }
+            ApiError::RateLimited { retry_after } => {
+                headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+            }
+            _ => {}
+        }
+        response
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(err: StorageError) -> Self {
+        match err {
+            StorageError::Conflict(message) => ApiError::Conflict(message),
+            StorageError::VersionMismatch => ApiError::PreconditionFailed,
+            StorageError::Backend(message) => ApiError::Internal(message),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::MissingJsonContentType(_) => {
+                ApiError::UnsupportedMediaType("expected `Content-Type: application/json`".into())
+            }
+            other => ApiError::BadRequest(other.body_text()),
+        }
+    }
+}
+
+impl From<validator::ValidationErrors> for ApiError {
+    fn from(errors: validator::ValidationErrors) -> Self {
+        ApiError::Validation(crate::validation::from_validator(&errors))
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+/// Result alias used by handlers.
+pub type ApiResult<T> = Result<T, ApiError>;
@@ -0,0 +1,318 @@
+//! In-process feed of resource changes.
+//!
+//! Handlers publish an event after every successful write (see
+//! [`AppState::changed`](crate::app::AppState::changed), which also queues
+//! [webhooks](crate::webhooks)); the SSE and WebSocket endpoints in
+//! [`routes::events`](crate::routes::events) relay them to subscribers.
+//! The most recent events are kept so a client that reconnects with the
+//! last id it saw misses nothing. When that is no longer possible (it was
+//! away too long, or too slow to keep up) it gets a
+//! [`FeedMessage::Resync`] and should refetch what it displays. Changes too
+//! large to publish one by one, like bulk imports, send every subscriber a
+//! resync instead (see [`ChangeFeed::invalidate`]).
+//!
+//! Event ids start at the process start time in microseconds, so they keep
+//! increasing across restarts and an id from before a restart is simply too
+//! old to resume from.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    auth::Scope,
+    model::{ApiKey, Item},
+};
+
+/// Events kept for resumption.
+const HISTORY: usize = 1024;
+
+/// Events a subscriber may fall behind by before it has to resync.
+const BUFFER: usize = 256;
+
+/// Kinds of resources that report changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceType {
+    Item,
+    ApiKey,
+}
+
+impl ResourceType {
+    pub const ALL: [ResourceType; 2] = [ResourceType::Item, ResourceType::ApiKey];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResourceType::Item => "item",
+            ResourceType::ApiKey => "api_key",
+        }
+    }
+
+    /// Scope needed to watch this resource type.
+    pub fn scope(self) -> Scope {
+        match self {
+            ResourceType::Item => Scope::Read,
+            ResourceType::ApiKey => Scope::Admin,
+        }
+    }
+}
+
+impl fmt::Display for ResourceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ResourceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| format!("unknown resource type `{s}`"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Created,
+    Updated,
+    Deleted,
+    /// Taken back out of the trash.
+    Restored,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [
+        Action::Created,
+        Action::Updated,
+        Action::Deleted,
+        Action::Restored,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::Created => "created",
+            Action::Updated => "updated",
+            Action::Deleted => "deleted",
+            Action::Restored => "restored",
+        }
+    }
+}
+
+/// A resource whose changes go through the feed.
+pub trait Watched: Serialize {
+    const TYPE: ResourceType;
+
+    fn id(&self) -> Uuid;
+}
+
+impl Watched for Item {
+    const TYPE: ResourceType = ResourceType::Item;
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+impl Watched for ApiKey {
+    const TYPE: ResourceType = ResourceType::ApiKey;
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+}
+
+/// One change to one resource.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ChangeEvent {
+    /// Increasing; pass the last one seen to resume after a reconnect.
+    pub id: u64,
+    pub resource: ResourceType,
+    pub action: Action,
+    pub resource_id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    /// The resource after the change; `null` once deleted.
+    #[schema(value_type = Option<Object>)]
+    pub data: Option<Value>,
+}
+
+impl ChangeEvent {
+    /// `<resource>.<action>`, e.g. `item.created`.
+    pub fn event_type(&self) -> String {
+        format!("{}.{}", self.resource, self.action.as_str())
+    }
+}
+
+/// Every possible [`ChangeEvent::event_type`].
+pub fn event_types() -> impl Iterator<Item = String> {
+    ResourceType::ALL.into_iter().flat_map(|resource| {
+        Action::ALL
+            .into_iter()
+            .map(move |action| format!("{resource}.{}", action.as_str()))
+    })
+}
+
+/// What a subscriber receives.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedMessage {
+    Change(ChangeEvent),
+    /// Some events could not be delivered; refetch current state.
+    Resync,
+}
+
+/// Publishes changes to every current subscriber.
+pub struct ChangeFeed {
+    state: Mutex<FeedState>,
+    /// `None` asks subscribers to resync.
+    sender: broadcast::Sender<Option<Arc<ChangeEvent>>>,
+}
+
+struct FeedState {
+    last_id: u64,
+    recent: VecDeque<Arc<ChangeEvent>>,
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        let started = Utc::now().timestamp_micros().max(0) as u64;
+        Self {
+            state: Mutex::new(FeedState {
+                last_id: started,
+                recent: VecDeque::with_capacity(HISTORY),
+            }),
+            sender: broadcast::Sender::new(BUFFER),
+        }
+    }
+
+    /// Publishes the creation, update or restoration of `resource`.
+    pub fn changed<R: Watched>(&self, action: Action, resource: &R) -> Arc<ChangeEvent> {
+        let data = serde_json::to_value(resource).ok();
+        self.publish(R::TYPE, action, resource.id(), data)
+    }
+
+    /// Publishes the deletion of a resource.
+    pub fn deleted(&self, resource: ResourceType, resource_id: Uuid) -> Arc<ChangeEvent> {
+        self.publish(resource, Action::Deleted, resource_id, None)
+    }
+
+    fn publish(
+        &self,
+        resource: ResourceType,
+        action: Action,
+        resource_id: Uuid,
+        data: Option<Value>,
+    ) -> Arc<ChangeEvent> {
+        let mut state = self.state.lock().unwrap();
+        state.last_id += 1;
+        let event = Arc::new(ChangeEvent {
+            id: state.last_id,
+            resource,
+            action,
+            resource_id,
+            occurred_at: Utc::now(),
+            data,
+        });
+        if state.recent.len() == HISTORY {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(event.clone());
+        // Sending under the lock keeps history and live events in one order.
+        // Having no subscribers is fine.
+        let _ = self.sender.send(Some(event.clone()));
+        event
+    }
+
+    /// Tells every subscriber to resync, for changes that are not published
+    /// one by one. Clients resuming from before this point resync too.
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        // Consuming an id makes every earlier one too old to resume from.
+        state.last_id += 1;
+        state.recent.clear();
+        let _ = self.sender.send(None);
+    }
+
+    /// Subscribes to changes of the given types after `last_seen`, or to
+    /// new changes only if `None`.
+    pub fn subscribe(&self, last_seen: Option<u64>, types: Vec<ResourceType>) -> Subscription {
+        let state = self.state.lock().unwrap();
+        let receiver = self.sender.subscribe();
+        let (backlog, missed) = match last_seen {
+            None => (VecDeque::new(), false),
+            Some(last_seen) => {
+                let oldest = state
+                    .recent
+                    .front()
+                    .map_or(state.last_id + 1, |event| event.id);
+                if last_seen + 1 < oldest || last_seen > state.last_id {
+                    // The refetch that follows a resync covers the history.
+                    (VecDeque::new(), true)
+                } else {
+                    let backlog = state
+                        .recent
+                        .iter()
+                        .filter(|event| event.id > last_seen)
+                        .cloned()
+                        .collect();
+                    (backlog, false)
+                }
+            }
+        };
+        Subscription {
+            backlog,
+            receiver,
+            missed,
+            types,
+        }
+    }
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A subscriber's view of the feed.
+pub struct Subscription {
+    backlog: VecDeque<Arc<ChangeEvent>>,
+    receiver: broadcast::Receiver<Option<Arc<ChangeEvent>>>,
+    missed: bool,
+    types: Vec<ResourceType>,
+}
+
+impl Subscription {
+    /// The next message for this subscriber; `None` once the feed is gone.
+    pub async fn next(&mut self) -> Option<FeedMessage> {
+        if std::mem::take(&mut self.missed) {
+            return Some(FeedMessage::Resync);
+        }
+        loop {
+            let event = match self.backlog.pop_front() {
+                Some(event) => event,
+                None => match self.receiver.recv().await {
+                    Ok(Some(event)) => event,
+                    Ok(None) | Err(RecvError::Lagged(_)) => return Some(FeedMessage::Resync),
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+            if self.types.contains(&event.resource) {
+                return Some(FeedMessage::Change(ChangeEvent::clone(&event)));
+            }
+        }
+    }
+}
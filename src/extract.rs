@@ -0,0 +1,95 @@
+//! Drop-in replacements for axum extractors whose rejections are turned
+//! into [`ApiError`]s, so malformed input gets the standard error body.
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+use crate::{error::ApiError, validation};
+
+/// JSON request body, also usable as a JSON response.
+#[derive(Debug, Clone, Copy, Default, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: serde::Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// JSON request body that is checked against `T`'s validation rules.
+///
+/// Missing fields, type mismatches and rule violations are all reported
+/// as [`ApiError::Validation`]; only unparseable JSON is a plain 400.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidJson<T>(pub T);
+
+impl<S, T> FromRequest<S> for ValidJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Validate,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !has_json_content_type(req.headers()) {
+            return Err(ApiError::UnsupportedMediaType(
+                "expected `Content-Type: application/json`".into(),
+            ));
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+        let value: T = decode_json(&bytes)?;
+        value.validate()?;
+        Ok(ValidJson(value))
+    }
+}
+
+/// Deserialises `bytes`, reporting data errors per field.
+pub fn decode_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiError> {
+    let de = &mut serde_json::Deserializer::from_slice(bytes);
+    serde_path_to_error::deserialize(de).map_err(|err| match validation::from_serde(&err) {
+        Some(field) => ApiError::Validation(vec![field]),
+        None => ApiError::BadRequest(format!("malformed JSON body: {}", err.inner())),
+    })
+}
+
+/// Whether the request declares a JSON body (`application/json` or any
+/// `application/*+json` type).
+pub fn has_json_content_type(headers: &HeaderMap) -> bool {
+    media_type(headers).is_some_and(|essence| {
+        essence == "application/json"
+            || (essence.starts_with("application/") && essence.ends_with("+json"))
+    })
+}
+
+/// The lowercased `type/subtype` of the request's `Content-Type`, without
+/// parameters.
+pub fn media_type(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    Some(
+        content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase(),
+    )
+}
+
+/// Query string parameters.
+#[derive(Debug, Clone, Default, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+/// Path parameters.
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
@@ -0,0 +1,110 @@
+//! Liveness and readiness probes for orchestrators.
+//!
+//! `/healthz` only says the process is up and serving. `/readyz` also
+//! checks the storage backend (reachable, migrations applied) and fails as
+//! soon as shutdown begins, so load balancers stop routing new traffic while
+//! in-flight requests drain.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::app::AppState;
+
+pub const HEALTH_PATH: &str = "/healthz";
+pub const READY_PATH: &str = "/readyz";
+
+/// A storage check taking longer than this counts as failed.
+const STORAGE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether the service should keep receiving traffic. Cloning shares the
+/// flag.
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    shutting_down: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self {
+            shutting_down: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl Readiness {
+    /// Makes `/readyz` fail from now on.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutting_down.borrow()
+    }
+
+    /// Completes once shutdown begins. Long-lived streams end on this so
+    /// clients reconnect elsewhere instead of holding up the drain.
+    pub async fn shutdown_begun(&self) {
+        let mut flag = self.shutting_down.subscribe();
+        // The sender lives in `self`, so this cannot fail.
+        let _ = flag.wait_for(|shutting_down| *shutting_down).await;
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Health {
+    status: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadyReport {
+    status: &'static str,
+    checks: Checks,
+}
+
+#[derive(Debug, Serialize)]
+struct Checks {
+    /// `ok` or `fail`.
+    storage: &'static str,
+    /// `ok` or `shutting_down`.
+    lifecycle: &'static str,
+}
+
+/// `GET /healthz`.
+pub async fn healthz() -> Json<Health> {
+    Json(Health { status: "ok" })
+}
+
+/// `GET /readyz`.
+pub async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<ReadyReport>) {
+    let storage_ok = match tokio::time::timeout(STORAGE_TIMEOUT, state.storage.check_health()).await
+    {
+        Ok(Ok(())) => true,
+        Ok(Err(err)) => {
+            tracing::warn!(%err, "storage not ready");
+            false
+        }
+        Err(_) => {
+            tracing::warn!(timeout = ?STORAGE_TIMEOUT, "storage health check timed out");
+            false
+        }
+    };
+    let shutting_down = state.readiness.is_shutting_down();
+
+    let ready = storage_ok && !shutting_down;
+    let report = ReadyReport {
+        status: if ready { "ready" } else { "unavailable" },
+        checks: Checks {
+            storage: if storage_ok { "ok" } else { "fail" },
+            lifecycle: if shutting_down { "shutting_down" } else { "ok" },
+        },
+    };
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
@@ -0,0 +1,209 @@
+//! Safe retries of writes with `Idempotency-Key`.
+//!
+//! The first mutating request with a given key is handled normally and its
+//! response stored; repeats with the same method, target and body get that
+//! response back (marked with `Idempotent-Replayed: true`) without running
+//! the handler again. Reusing a key for a different request is a 422, and a
+//! repeat that arrives while the first is still running is a 409.
+//!
+//! Keys are scoped to the authenticated principal and expire after
+//! [`AppState::idempotency_ttl`]. Server errors are not stored, so the
+//! request can be retried under the same key. Neither are responses marked
+//! [`OneTimeSecret`]: repeats of those get a 409 rather than the secret.
+
+use std::time::Duration;
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header, response::Parts},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::AppState,
+    auth::Principal,
+    error::ApiError,
+    model::{IdempotencyRecord, StoredResponse},
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long responses are replayed unless configured otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a claim without a response blocks its key, in case the process
+/// dies before the first request completes.
+const PENDING_LEASE: Duration = Duration::from_secs(60);
+
+/// Longest accepted key.
+const MAX_KEY_LEN: usize = 255;
+
+/// Bodies are buffered to fingerprint them; this matches axum's default
+/// request body limit.
+const MAX_BODY: usize = 2 * 1024 * 1024;
+
+/// Response headers kept for replay; the rest describe the original
+/// exchange rather than the resource.
+const STORED_HEADERS: [HeaderName; 3] = [header::CONTENT_TYPE, header::ETAG, header::LOCATION];
+
+/// Response extension for bodies holding a secret that is shown only once,
+/// such as a new API key's token. Their bodies are never stored.
+#[derive(Debug, Clone, Copy)]
+pub struct OneTimeSecret;
+
+/// Middleware replaying responses for repeated `Idempotency-Key`s. Must run
+/// inside [`authenticate`](crate::auth::authenticate).
+pub async fn enforce(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = idempotency_key(request.headers())? else {
+        return Ok(next.run(request).await);
+    };
+    let scope = request
+        .extensions()
+        .get::<Principal>()
+        .map(|principal| principal.subject.clone())
+        .ok_or_else(|| ApiError::Internal("idempotency check ran before authentication".into()))?;
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_BODY)
+        .await
+        .map_err(|err| ApiError::BadRequest(format!("cannot read request body: {err}")))?;
+    let fingerprint = fingerprint(&parts.method, parts.uri.to_string().as_bytes(), &body);
+
+    let claim = IdempotencyRecord {
+        scope,
+        key,
+        fingerprint,
+        response: None,
+        expires_at: Utc::now() + PENDING_LEASE,
+    };
+    if let Some(existing) = state.storage.claim_idempotency_key(&claim).await? {
+        if existing.fingerprint != claim.fingerprint {
+            return Err(ApiError::IdempotencyKeyReused);
+        }
+        return match existing.response {
+            Some(stored) => Ok(replay(stored)),
+            None => Err(ApiError::Conflict(
+                "a request with this idempotency key is still in progress".into(),
+            )),
+        };
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if response.status().is_server_error() {
+        if let Err(err) = state
+            .storage
+            .release_idempotency_key(&claim.scope, &claim.key)
+            .await
+        {
+            tracing::warn!(%err, "failed to release idempotency key");
+        }
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|err| ApiError::Internal(format!("cannot buffer response: {err}")))?;
+    let stored = if parts.extensions.get::<OneTimeSecret>().is_some() {
+        withheld().await?
+    } else {
+        store(&parts, &body)
+    };
+    let expires_at = Utc::now() + state.idempotency_ttl;
+    if let Err(err) = state
+        .storage
+        .complete_idempotency_key(&claim.scope, &claim.key, &stored, expires_at)
+        .await
+    {
+        // The write itself went through; failing the request now would
+        // invite exactly the retry this is meant to make safe.
+        tracing::warn!(%err, "failed to store idempotent response");
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// What is kept of a response for replay.
+fn store(parts: &Parts, body: &[u8]) -> StoredResponse {
+    StoredResponse {
+        status: parts.status.as_u16(),
+        headers: STORED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                let value = parts.headers.get(name)?.to_str().ok()?;
+                Some((name.to_string(), value.to_owned()))
+            })
+            .collect(),
+        body: body.to_vec(),
+    }
+}
+
+/// What is kept instead of a response holding a [`OneTimeSecret`].
+async fn withheld() -> Result<StoredResponse, ApiError> {
+    let (parts, body) = ApiError::Conflict(
+        "this request already succeeded; its response held a one-time secret and is not kept"
+            .into(),
+    )
+    .into_response()
+    .into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .map_err(|err| ApiError::Internal(format!("cannot buffer response: {err}")))?;
+    Ok(store(&parts, &body))
+}
+
+/// The request's `Idempotency-Key`, if any. Keys are 1 to 255 visible ASCII
+/// characters.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    match value.to_str() {
+        Ok(key)
+            if !key.is_empty()
+                && key.len() <= MAX_KEY_LEN
+                && key.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            Ok(Some(key.to_owned()))
+        }
+        _ => Err(ApiError::BadRequest(format!(
+            "Idempotency-Key must be 1 to {MAX_KEY_LEN} visible ASCII characters"
+        ))),
+    }
+}
+
+fn fingerprint(method: &Method, target: &[u8], body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    for part in [method.as_str().as_bytes(), target, body] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, Body::from(stored.body)).into_response();
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.insert(name, value);
+        }
+    }
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
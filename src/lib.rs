@@ -0,0 +1,34 @@
+//! A small versioned JSON API.
+//!
+//! The binary in `main.rs` only parses the command line and hands over to
+//! [`server::run`]; everything else lives in this library so integration
+//! tests can build the same router the server uses.
+
+pub mod app;
+pub mod audit;
+pub mod auth;
+pub mod bulk;
+pub mod conditional;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod extract;
+pub mod health;
+pub mod idempotency;
+pub mod logging;
+pub mod metrics;
+pub mod model;
+pub mod negotiate;
+pub mod openapi;
+pub mod pagination;
+pub mod patch;
+pub mod rate_limit;
+pub mod routes;
+pub mod search;
+pub mod server;
+pub mod storage;
+pub mod trash;
+pub mod validation;
+pub mod webhooks;
+
+pub use app::{AppState, router};
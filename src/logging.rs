@@ -0,0 +1,118 @@
+//! Request logging and log output.
+//!
+//! Every request gets an `X-Request-Id`, taken from the client when it sends
+//! one and generated otherwise. The id is echoed in the response and recorded
+//! on a `request` span that covers the whole handler, so every log line
+//! emitted while serving the request carries it. When the response is ready
+//! a `request completed` line records its status and latency.
+
+use std::{io, time::Duration};
+
+use axum::{
+    Router,
+    extract::{MatchedPath, Request},
+    http::{HeaderName, HeaderValue},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use tower_http::{
+    request_id::{MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{Span, Subscriber};
+use tracing_subscriber::{EnvFilter, fmt::MakeWriter};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Output format of log lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable, for development.
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+/// Builds the subscriber writing logs of `level` (an `EnvFilter` directive)
+/// in `format` to `writer`.
+pub fn subscriber<W>(level: &str, format: LogFormat, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(level))
+        .with_writer(writer);
+    match format {
+        LogFormat::Pretty => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .finish(),
+        ),
+    }
+}
+
+/// Installs the process-wide subscriber writing to stdout.
+pub fn init(level: &str, format: LogFormat) {
+    tracing::subscriber::set_global_default(subscriber(level, format, io::stdout))
+        .expect("logging is initialised once");
+}
+
+/// Wraps `router` with request id handling and request logging.
+pub fn layer(router: Router) -> Router {
+    // Added innermost first: the id is set before the span is created and
+    // copied to the response after the handler ran.
+    router
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(make_span)
+                .on_response(on_response)
+                .on_failure(()),
+        )
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeUuid))
+}
+
+/// Generates ids for requests that did not bring one.
+#[derive(Debug, Clone, Copy)]
+struct MakeUuid;
+
+impl MakeRequestId for MakeUuid {
+    fn make_request_id<B>(&mut self, _request: &axum::http::Request<B>) -> Option<RequestId> {
+        let id = Uuid::now_v7().to_string();
+        Some(RequestId::new(HeaderValue::from_str(&id).ok()?))
+    }
+}
+
+fn make_span(request: &Request) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str);
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = request.uri().path(),
+        route,
+        request_id,
+    )
+}
+
+fn on_response(response: &Response, latency: Duration, _span: &Span) {
+    tracing::info!(
+        status = response.status().as_u16(),
+        latency_ms = latency.as_secs_f64() * 1000.0,
+        "request completed"
+    );
+}
@@ -0,0 +1,85 @@
+use std::{error::Error, process::ExitCode};
+
+use api_app::{
+    AppState, auth,
+    config::{Cli, Config},
+    logging,
+    rate_limit::RateLimits,
+    router, server,
+    storage::{MemoryStorage, SqliteStorage},
+    trash, webhooks,
+};
+use clap::Parser;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = match Config::load(&cli, std::env::vars()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if cli.print_config {
+        print!("{}", config.to_redacted_toml());
+        return ExitCode::SUCCESS;
+    }
+
+    logging::init(&config.log.level, config.log.format);
+
+    match run(config).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            tracing::error!(%err, "fatal error");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let state = match &config.database.path {
+        Some(path) => {
+            tracing::info!(path = %path.display(), "using sqlite storage");
+            AppState::new(SqliteStorage::open(path)?)
+        }
+        None => {
+            tracing::warn!("no database configured, data will not survive a restart");
+            AppState::new(MemoryStorage::new())
+        }
+    };
+
+    let state = match config.auth.jwt.build()? {
+        Some(jwt) => {
+            tracing::info!(algorithm = %config.auth.jwt.algorithm, "JWT sessions enabled");
+            state.with_jwt(jwt)
+        }
+        None => state,
+    };
+
+    let state = state
+        .with_rate_limits(RateLimits::new(&(&config.rate_limits).into()))
+        .with_required_if_match(config.api.require_if_match)
+        .with_idempotency_ttl(config.api.idempotency_ttl())
+        .with_compression_min_size(config.api.compression_min_size)
+        .with_max_import_size(config.api.max_import_size)
+        .with_webhook_settings(config.webhooks.settings())
+        .with_trash_settings(config.trash.settings());
+
+    if let Some(token) = &config.auth.bootstrap_key {
+        auth::bootstrap_admin(&state, token.expose()).await?;
+    }
+
+    tokio::spawn(webhooks::run(state.clone()));
+    tokio::spawn(trash::run(state.clone()));
+
+    let readiness = state.readiness.clone();
+    server::run(
+        config.server.addr(),
+        router(state),
+        readiness,
+        config.server.shutdown_delay(),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Prometheus metrics, served at [`METRICS_PATH`].
+//!
+//! HTTP requests are counted and timed by [`track`], labelled by method,
+//! route template (`/api/v1/items/{id}` rather than the concrete path, to
+//! keep cardinality bounded) and status. Storage calls are timed by
+//! [`InstrumentedStorage`](crate::storage::InstrumentedStorage). Each
+//! [`Metrics`] owns its registry, so several apps in one process (as in
+//! the tests) do not share counters.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use crate::error::ApiError;
+
+/// Where the metrics are served.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Route label of requests that matched no route.
+const UNMATCHED: &str = "unmatched";
+
+/// Every metric the service exports.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    in_flight: IntGauge,
+    storage_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests served"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time from receiving a request to having its response ready",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let in_flight = IntGauge::new(
+            "http_requests_in_flight",
+            "HTTP requests currently being served",
+        )
+        .unwrap();
+        let storage_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "storage_operation_duration_seconds",
+                "Time spent in storage backend calls",
+            )
+            .buckets(prometheus::exponential_buckets(0.0001, 4.0, 8).unwrap()),
+            &["operation", "outcome"],
+        )
+        .unwrap();
+
+        for collector in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(request_duration.clone()),
+            Box::new(in_flight.clone()),
+            Box::new(storage_duration.clone()),
+        ] {
+            registry.register(collector).unwrap();
+        }
+        Self {
+            registry,
+            requests,
+            request_duration,
+            in_flight,
+            storage_duration,
+        }
+    }
+
+    /// Records one storage call. `outcome` is `ok` or `error`.
+    pub fn observe_storage(&self, operation: &str, ok: bool, elapsed: Duration) {
+        let outcome = if ok { "ok" } else { "error" };
+        self.storage_duration
+            .with_label_values(&[operation, outcome])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Renders every metric in the Prometheus text format.
+    pub fn render(&self) -> Result<String, ApiError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|err| ApiError::Internal(err.to_string()))?;
+        String::from_utf8(buffer).map_err(|err| ApiError::Internal(err.to_string()))
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps the in-flight gauge right even if a request is dropped midway.
+struct InFlight(IntGauge);
+
+impl InFlight {
+    fn start(gauge: &IntGauge) -> Self {
+        gauge.inc();
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Middleware counting and timing every request.
+pub async fn track(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let _in_flight = InFlight::start(&metrics.in_flight);
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED, MatchedPath::as_str)
+        .to_owned();
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let status = response.status();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    metrics.requests.with_label_values(&labels).inc();
+    metrics
+        .request_duration
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+/// `GET /metrics`.
+pub async fn serve(State(metrics): State<Arc<Metrics>>) -> Result<Response, ApiError> {
+    let body = metrics.render()?;
+    Ok(([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response())
+}
@@ -0,0 +1,522 @@
+//! Resource types exchanged over the API and persisted by the storage layer.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    auth::Scope,
+    pagination::{Direction, ListQuery, Sort, SortField, SortKey},
+    validation,
+};
+
+/// A stored item.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Item {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub quantity: i64,
+    /// Starts at 1 and increases with every update; also sent as `ETag`.
+    pub version: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When the item was moved to the trash; `null` unless it is there.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Client-supplied fields of an item, used for both create and full update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ItemInput {
+    #[validate(length(min = 1, max = 200))]
+    #[schema(min_length = 1, max_length = 200)]
+    pub name: String,
+    #[serde(default)]
+    #[validate(length(max = 2000))]
+    #[schema(max_length = 2000)]
+    pub description: Option<String>,
+    #[serde(default)]
+    #[validate(range(min = 0, max = 1_000_000))]
+    #[schema(minimum = 0, maximum = 1_000_000)]
+    pub quantity: i64,
+}
+
+impl Item {
+    /// Builds a fresh item from client input with a new id and timestamps.
+    pub fn new(input: ItemInput) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::now_v7(),
+            name: input.name,
+            description: input.description,
+            quantity: input.quantity,
+            version: 1,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+        }
+    }
+
+    /// Moves the item to the trash, or with `None` takes it back out, and
+    /// bumps `version` and `updated_at`.
+    pub fn set_deleted_at(&mut self, deleted_at: Option<DateTime<Utc>>) {
+        self.deleted_at = deleted_at;
+        self.version += 1;
+        self.updated_at = Utc::now();
+    }
+
+    /// Replaces the client-editable fields and bumps `version` and
+    /// `updated_at`.
+    pub fn apply(&mut self, input: ItemInput) {
+        self.name = input.name;
+        self.description = input.description;
+        self.quantity = input.quantity;
+        self.version += 1;
+        self.updated_at = Utc::now();
+    }
+}
+
+impl From<&Item> for ItemInput {
+    fn from(item: &Item) -> Self {
+        Self {
+            name: item.name.clone(),
+            description: item.description.clone(),
+            quantity: item.quantity,
+        }
+    }
+}
+
+/// Fields items can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemSort {
+    CreatedAt,
+    UpdatedAt,
+    Name,
+    Quantity,
+}
+
+impl SortField for ItemSort {
+    const DEFAULT: Sort<Self> = Sort {
+        field: ItemSort::CreatedAt,
+        direction: Direction::Asc,
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "created_at" => ItemSort::CreatedAt,
+            "updated_at" => ItemSort::UpdatedAt,
+            "name" => ItemSort::Name,
+            "quantity" => ItemSort::Quantity,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ItemSort::CreatedAt => "created_at",
+            ItemSort::UpdatedAt => "updated_at",
+            ItemSort::Name => "name",
+            ItemSort::Quantity => "quantity",
+        }
+    }
+
+    fn all() -> &'static [&'static str] {
+        &["created_at", "updated_at", "name", "quantity"]
+    }
+}
+
+/// Filters accepted by the item list endpoint. All present filters must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemFilter {
+    /// Case-insensitive substring of the name.
+    pub name: Option<String>,
+    pub min_quantity: Option<i64>,
+    pub max_quantity: Option<i64>,
+    /// Also list items in the trash.
+    pub include_deleted: bool,
+}
+
+impl ItemFilter {
+    pub fn matches(&self, item: &Item) -> bool {
+        (self.include_deleted || item.deleted_at.is_none())
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|needle| item.name.to_lowercase().contains(&needle.to_lowercase()))
+            && self.min_quantity.is_none_or(|min| item.quantity >= min)
+            && self.max_quantity.is_none_or(|max| item.quantity <= max)
+    }
+}
+
+pub type ItemQuery = ListQuery<ItemSort, ItemFilter>;
+
+impl Item {
+    /// Sort key and tie-breaker for keyset pagination.
+    pub fn sort_key(&self, field: ItemSort) -> (SortKey, Uuid) {
+        let key = match field {
+            ItemSort::CreatedAt => SortKey::Time(self.created_at),
+            ItemSort::UpdatedAt => SortKey::Time(self.updated_at),
+            ItemSort::Name => SortKey::Text(self.name.clone()),
+            ItemSort::Quantity => SortKey::Int(self.quantity),
+        };
+        (key, self.id)
+    }
+}
+
+/// An API key as stored. The secret token itself is never persisted, only
+/// its hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    /// First characters of the token, so a key can be recognised in lists.
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Body of `POST /admin/api-keys`.
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyInput {
+    #[validate(length(min = 1, max = 100))]
+    #[schema(min_length = 1, max_length = 100)]
+    pub name: String,
+    #[validate(length(min = 1, max = 3))]
+    #[schema(min_items = 1, max_items = 3)]
+    pub scopes: Vec<Scope>,
+}
+
+/// API keys are only ever listed by creation time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeySort {
+    CreatedAt,
+}
+
+impl SortField for ApiKeySort {
+    const DEFAULT: Sort<Self> = Sort {
+        field: ApiKeySort::CreatedAt,
+        direction: Direction::Asc,
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        (name == "created_at").then_some(ApiKeySort::CreatedAt)
+    }
+
+    fn name(self) -> &'static str {
+        "created_at"
+    }
+
+    fn all() -> &'static [&'static str] {
+        &["created_at"]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApiKeyFilter {
+    /// When false, revoked keys are left out.
+    pub include_revoked: bool,
+}
+
+pub type ApiKeyQuery = ListQuery<ApiKeySort, ApiKeyFilter>;
+
+impl ApiKey {
+    pub fn sort_key(&self, field: ApiKeySort) -> (SortKey, Uuid) {
+        match field {
+            ApiKeySort::CreatedAt => (SortKey::Time(self.created_at), self.id),
+        }
+    }
+}
+
+/// A user who can log in with a password and receive JWT sessions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /admin/users`.
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UserInput {
+    #[validate(length(min = 3, max = 64))]
+    #[schema(min_length = 3, max_length = 64)]
+    pub username: String,
+    #[validate(length(min = 8, max = 128))]
+    #[schema(min_length = 8, max_length = 128, format = Password)]
+    pub password: String,
+    #[validate(length(min = 1, max = 3))]
+    #[schema(min_items = 1, max_items = 3)]
+    pub scopes: Vec<Scope>,
+}
+
+/// A refresh token as stored; like API keys, only the hash is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A request made with an `Idempotency-Key`, and its response once known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyRecord {
+    /// Who sent the request; keys from different callers never collide.
+    pub scope: String,
+    pub key: String,
+    /// Hash of the method, target and body of the first request.
+    pub fingerprint: String,
+    /// `None` while the first request is still being handled.
+    pub response: Option<StoredResponse>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A response kept for replay.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// A URL that receives change events by `POST`. Its signing secret is
+/// stored alongside but never listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    /// Event types delivered, e.g. `item.created`.
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /admin/webhooks`.
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookInput {
+    /// `http` or `https` URL to deliver to.
+    #[validate(length(max = 2000), custom(function = "validation::http_url"))]
+    #[schema(max_length = 2000, format = "uri")]
+    pub url: String,
+    /// Event types to deliver: `<resource>.<action>`, e.g. `item.created`.
+    #[validate(length(min = 1, max = 6), custom(function = "validation::event_types"))]
+    #[schema(min_items = 1, max_items = 6)]
+    pub events: Vec<String>,
+}
+
+/// Webhooks are only ever listed by creation time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookSort {
+    CreatedAt,
+}
+
+impl SortField for WebhookSort {
+    const DEFAULT: Sort<Self> = Sort {
+        field: WebhookSort::CreatedAt,
+        direction: Direction::Asc,
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        (name == "created_at").then_some(WebhookSort::CreatedAt)
+    }
+
+    fn name(self) -> &'static str {
+        "created_at"
+    }
+
+    fn all() -> &'static [&'static str] {
+        &["created_at"]
+    }
+}
+
+pub type WebhookQuery = ListQuery<WebhookSort, ()>;
+
+impl Webhook {
+    pub fn sort_key(&self, field: WebhookSort) -> (SortKey, Uuid) {
+        match field {
+            WebhookSort::CreatedAt => (SortKey::Time(self.created_at), self.id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Not delivered yet; `next_attempt_at` says when it is tried again.
+    Pending,
+    Succeeded,
+    /// Gave up after the configured number of attempts.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Succeeded => "succeeded",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Some(match raw {
+            "pending" => DeliveryStatus::Pending,
+            "succeeded" => DeliveryStatus::Succeeded,
+            "failed" => DeliveryStatus::Failed,
+            _ => return None,
+        })
+    }
+}
+
+/// One change event queued for one webhook, with the outcome of its
+/// latest attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    /// The change event sent as the request body.
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// `null` once the delivery succeeded or failed for good.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// Status code the receiver answered the latest attempt with.
+    pub response_status: Option<u16>,
+    /// Why the latest attempt failed.
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Deliveries are listed newest first by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliverySort {
+    CreatedAt,
+}
+
+impl SortField for DeliverySort {
+    const DEFAULT: Sort<Self> = Sort {
+        field: DeliverySort::CreatedAt,
+        direction: Direction::Desc,
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        (name == "created_at").then_some(DeliverySort::CreatedAt)
+    }
+
+    fn name(self) -> &'static str {
+        "created_at"
+    }
+
+    fn all() -> &'static [&'static str] {
+        &["created_at"]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryFilter {
+    pub webhook_id: Uuid,
+    pub status: Option<DeliveryStatus>,
+}
+
+pub type DeliveryQuery = ListQuery<DeliverySort, DeliveryFilter>;
+
+impl WebhookDelivery {
+    pub fn sort_key(&self, field: DeliverySort) -> (SortKey, Uuid) {
+        match field {
+            DeliverySort::CreatedAt => (SortKey::Time(self.created_at), self.id),
+        }
+    }
+}
+
+/// One entry of the append-only audit log: who changed what, and when.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    /// Who made the change: `key:<uuid>` for API keys, `user:<uuid>` for
+    /// JWT sessions.
+    pub actor: String,
+    /// `<resource>.<action>`, e.g. `item.updated`.
+    pub action: String,
+    pub resource_type: String,
+    /// `null` when many resources changed at once, as in an import.
+    pub resource_id: Option<Uuid>,
+    /// Each changed field as `{"before": ..., "after": ...}`, with `null`
+    /// before a creation and after a deletion.
+    #[schema(value_type = Object)]
+    pub changes: serde_json::Value,
+    /// `X-Request-Id` of the request that made the change.
+    pub request_id: Option<String>,
+}
+
+/// The audit log is listed newest first by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditSort {
+    OccurredAt,
+}
+
+impl SortField for AuditSort {
+    const DEFAULT: Sort<Self> = Sort {
+        field: AuditSort::OccurredAt,
+        direction: Direction::Desc,
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        (name == "occurred_at").then_some(AuditSort::OccurredAt)
+    }
+
+    fn name(self) -> &'static str {
+        "occurred_at"
+    }
+
+    fn all() -> &'static [&'static str] {
+        &["occurred_at"]
+    }
+}
+
+/// Filters accepted by the audit log endpoint. All present filters must
+/// match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub resource_id: Option<Uuid>,
+    /// Inclusive.
+    pub since: Option<DateTime<Utc>>,
+    /// Exclusive.
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| entry.actor == *actor)
+            && self
+                .resource_id
+                .is_none_or(|id| entry.resource_id == Some(id))
+            && self.since.is_none_or(|since| entry.occurred_at >= since)
+            && self.until.is_none_or(|until| entry.occurred_at < until)
+    }
+}
+
+pub type AuditQuery = ListQuery<AuditSort, AuditFilter>;
+
+impl AuditEntry {
+    pub fn sort_key(&self, field: AuditSort) -> (SortKey, Uuid) {
+        match field {
+            AuditSort::OccurredAt => (SortKey::Time(self.occurred_at), self.id),
+        }
+    }
+}
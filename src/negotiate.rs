@@ -0,0 +1,230 @@
+//! Content negotiation: `Accept-Encoding` and `Accept`.
+//!
+//! Responses are compressed with gzip, brotli or zstd when the client asks
+//! for it and they are at least a configurable size; event streams never
+//! are, as that would hold events back in the encoder.
+//!
+//! Every JSON response of the API can also be had as MessagePack. Item
+//! resources are available as CSV too, and exports and the change feed have
+//! formats of their own. [`respond`] runs around every `/api/v1` route: it
+//! answers `406 Not Acceptable` when `Accept` rules out everything the API
+//! could send (before the handler runs, so writes are never half-answered)
+//! and transcodes JSON responses for clients that prefer MessagePack.
+//! Handlers with further representations pick one with
+//! [`Accept::negotiate`].
+
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    extract::{FromRequestParts, Request},
+    http::{
+        Extensions, HeaderMap, HeaderValue, Method, StatusCode, Version, header, request::Parts,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{NotForContentType, Predicate, SizeAbove},
+};
+
+use crate::{bulk, error::ApiError, extract::media_type};
+
+pub const JSON: &str = "application/json";
+pub const MSGPACK: &str = "application/msgpack";
+pub const EVENT_STREAM: &str = "text/event-stream";
+
+/// Other names clients use for MessagePack.
+const MSGPACK_ALIASES: [&str; 2] = ["application/x-msgpack", "application/vnd.msgpack"];
+
+/// Everything some `GET` route can produce.
+const PRODUCED: [&str; 6] = [
+    JSON,
+    MSGPACK,
+    bulk::CSV,
+    bulk::NDJSON,
+    "application/jsonl",
+    EVENT_STREAM,
+];
+
+/// Responses smaller than this many bytes are sent uncompressed.
+pub const DEFAULT_COMPRESSION_MIN_SIZE: u16 = 1024;
+
+/// Compresses responses of at least `min_size` bytes in the encoding the
+/// client prefers.
+pub fn compression(min_size: u16) -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new().compress_when(
+        SizeAbove::new(min_size)
+            .and(has_body)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE),
+    )
+}
+
+fn has_body(status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions) -> bool {
+    !(status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED)
+}
+
+/// The media ranges of a request's `Accept` header. A missing or
+/// unparseable header accepts anything.
+#[derive(Debug, Clone, Default)]
+pub struct Accept(Vec<MediaRange>);
+
+#[derive(Debug, Clone)]
+struct MediaRange {
+    /// Lowercased `type/subtype`, either of which may be `*`.
+    essence: String,
+    quality: f32,
+}
+
+impl Accept {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let ranges = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(parse_range)
+            .collect();
+        Accept(ranges)
+    }
+
+    /// How much the client wants `media_type`, from 0 to 1, going by the
+    /// most specific range that matches it.
+    fn quality(&self, media_type: &str) -> f32 {
+        if self.0.is_empty() {
+            return 1.0;
+        }
+        let (kind, _) = media_type.split_once('/').unwrap_or((media_type, ""));
+        self.0
+            .iter()
+            .filter_map(|range| {
+                let specificity = if range.essence == media_type {
+                    2
+                } else if range.essence.strip_suffix("/*") == Some(kind) {
+                    1
+                } else if range.essence == "*/*" {
+                    0
+                } else {
+                    return None;
+                };
+                Some((specificity, range.quality))
+            })
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0.0, |(_, quality)| quality)
+    }
+
+    /// The most wanted of `offered`, earlier ones winning ties.
+    pub fn preferred<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        let mut best = None;
+        let mut best_quality = 0.0;
+        for &media_type in offered {
+            let quality = self.quality(media_type);
+            if quality > best_quality {
+                best = Some(media_type);
+                best_quality = quality;
+            }
+        }
+        best
+    }
+
+    /// Like [`Accept::preferred`], failing with 406 if nothing offered is
+    /// acceptable.
+    pub fn negotiate<'a>(&self, offered: &[&'a str]) -> Result<&'a str, ApiError> {
+        self.preferred(offered).ok_or_else(|| {
+            ApiError::NotAcceptable(format!(
+                "`Accept` rules out every available type: {}",
+                offered.join(", ")
+            ))
+        })
+    }
+}
+
+/// Parses `type/subtype;q=0.5`, skipping anything that is not a media
+/// range. Parameters other than `q` are ignored.
+fn parse_range(raw: &str) -> Option<MediaRange> {
+    let mut parts = raw.split(';');
+    let essence = parts.next()?.trim().to_ascii_lowercase();
+    if !essence.contains('/') {
+        return None;
+    }
+    let quality = parts
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+        .and_then(|(_, value)| value.trim().parse::<f32>().ok())
+        .map_or(1.0, |q| q.clamp(0.0, 1.0));
+    let essence = if MSGPACK_ALIASES.contains(&essence.as_str()) {
+        MSGPACK.to_owned()
+    } else {
+        essence
+    };
+    Some(MediaRange { essence, quality })
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Accept {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Accept::from_headers(&parts.headers))
+    }
+}
+
+/// Middleware enforcing `Accept` and transcoding JSON to MessagePack, see
+/// the module documentation.
+pub async fn respond(request: Request, next: Next) -> Response {
+    let accept = Accept::from_headers(request.headers());
+    // Writes only ever answer with JSON, or its MessagePack translation.
+    let offered: &[&str] = match *request.method() {
+        Method::GET | Method::HEAD => &PRODUCED,
+        _ => &[JSON, MSGPACK],
+    };
+    let mut response = match accept.negotiate(offered) {
+        Err(err) => err.into_response(),
+        Ok(_) => {
+            let response = next.run(request).await;
+            if media_type(response.headers()).as_deref() == Some(JSON) {
+                match accept.negotiate(&[JSON, MSGPACK]) {
+                    Ok(MSGPACK) => to_msgpack(response).await,
+                    Ok(_) => response,
+                    Err(err) if response.status().is_success() => err.into_response(),
+                    // Errors are sent as JSON rather than hidden behind a 406.
+                    Err(_) => response,
+                }
+            } else {
+                response
+            }
+        }
+    };
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// Re-encodes a JSON response as MessagePack, keeping status and headers.
+async fn to_msgpack(response: Response) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let encoded = async {
+        let bytes = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .map_err(|err| err.to_string())?;
+        let value: Value = serde_json::from_slice(&bytes).map_err(|err| err.to_string())?;
+        rmp_serde::to_vec_named(&value).map_err(|err| err.to_string())
+    };
+    match encoded.await {
+        Ok(bytes) => {
+            parts
+                .headers
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(bytes))
+        }
+        Err(err) => ApiError::Internal(format!("cannot encode response as MessagePack: {err}"))
+            .into_response(),
+    }
+}
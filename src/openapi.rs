@@ -0,0 +1,145 @@
+//! OpenAPI 3.1 description of the API.
+//!
+//! Paths come from the `#[utoipa::path]` attributes on the handlers, which
+//! are registered through `utoipa_axum::routes!` so a route cannot exist
+//! without being documented. Schemas come from the `ToSchema` derives on the
+//! request and response types. [`complete`] then adds the error responses
+//! every operation shares, derived from its security and inputs.
+
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        self, ContentBuilder, PathItem, Ref, RefOr, ResponseBuilder,
+        path::Operation,
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+};
+
+use crate::{
+    error::ErrorBody,
+    negotiate::{JSON, MSGPACK},
+};
+
+/// Where the document is served.
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+
+/// Name of the bearer security scheme referenced by protected operations.
+pub const BEARER: &str = "bearer";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "api-app", description = "Inventory items, API keys and sessions."),
+    modifiers(&BearerAuth),
+    components(schemas(ErrorBody)),
+    tags(
+        (name = "items", description = "Inventory items"),
+        (name = "events", description = "Live feed of changes, over SSE or WebSocket"),
+        (name = "admin", description = "API keys and users; requires the `admin` scope"),
+        (name = "auth", description = "Password login and JWT sessions, when enabled"),
+    )
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, api: &mut openapi::OpenApi) {
+        let components = api.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            BEARER,
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "An API key (`ak_...`) or a JWT access token. The listed \
+                         scopes are the ones the operation requires.",
+                    ))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Adds the error responses implied by each operation's shape: 406 for all,
+/// 401, 403 and 429 for authenticated ones, 400 for those taking parameters,
+/// and 415 and 422 for those taking a JSON body. Every JSON response is then
+/// also listed as MessagePack, which [`crate::negotiate`] translates it to.
+pub fn complete(api: &mut openapi::OpenApi) {
+    for item in api.paths.paths.values_mut() {
+        for operation in operations(item) {
+            let mut errors = vec![("406", "`Accept` rules out every available type")];
+            if operation.security.is_some() {
+                errors.extend([
+                    ("401", "Missing, invalid or expired credentials"),
+                    ("403", "The credentials lack the required scope"),
+                    ("429", "Rate limit exceeded; see `Retry-After`"),
+                ]);
+            }
+            if operation.parameters.is_some() || operation.request_body.is_some() {
+                errors.push(("400", "Malformed path, query string or body"));
+            }
+            if operation.request_body.is_some() {
+                errors.extend([
+                    ("415", "The body is not `application/json`"),
+                    ("422", "The body failed validation; see `details`"),
+                ]);
+            }
+            let idempotent = operation
+                .parameters
+                .iter()
+                .flatten()
+                .any(|parameter| parameter.name == "Idempotency-Key");
+            if idempotent {
+                errors.extend([
+                    (
+                        "409",
+                        "A request with this `Idempotency-Key` is still in progress",
+                    ),
+                    (
+                        "422",
+                        "The `Idempotency-Key` was used for a different request",
+                    ),
+                ]);
+            }
+            for (status, description) in errors {
+                operation
+                    .responses
+                    .responses
+                    .entry(status.to_owned())
+                    .or_insert_with(|| error_response(description));
+            }
+            for response in operation.responses.responses.values_mut() {
+                if let RefOr::T(response) = response
+                    && let Some(json) = response.content.get(JSON).cloned()
+                {
+                    response.content.insert(MSGPACK.to_owned(), json);
+                }
+            }
+        }
+    }
+}
+
+fn operations(item: &mut PathItem) -> impl Iterator<Item = &mut Operation> {
+    [
+        &mut item.get,
+        &mut item.put,
+        &mut item.post,
+        &mut item.delete,
+        &mut item.patch,
+    ]
+    .into_iter()
+    .filter_map(Option::as_mut)
+}
+
+fn error_response(description: &str) -> RefOr<openapi::Response> {
+    ResponseBuilder::new()
+        .description(description)
+        .content(
+            JSON,
+            ContentBuilder::new()
+                .schema(Some(Ref::from_schema_name("ErrorBody")))
+                .build(),
+        )
+        .build()
+        .into()
+}
@@ -0,0 +1,247 @@
+//! Cursor pagination, sorting and the list response envelope shared by
+//! every list endpoint.
+//!
+//! Lists are paged by keyset: the cursor handed back in `next_cursor`
+//! encodes the sort key and id of the last row served, and the next page
+//! starts strictly after it. Cursors are opaque to clients and only valid
+//! for the sort order they were issued for.
+
+use std::cmp::Ordering;
+
+use axum::http::{HeaderValue, Uri};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{error::ApiError, validation::FieldError};
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 200;
+
+/// Response envelope of every list endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    /// Pass as `cursor` to fetch the next page; `null` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// A field a resource can be sorted by.
+pub trait SortField: Copy + Eq + std::fmt::Debug {
+    /// Order used when the client does not ask for one.
+    const DEFAULT: Sort<Self>;
+
+    fn parse(name: &str) -> Option<Self>;
+
+    fn name(self) -> &'static str;
+
+    /// Names accepted by [`SortField::parse`], for error messages.
+    fn all() -> &'static [&'static str];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort<F> {
+    pub field: F,
+    pub direction: Direction,
+}
+
+impl<F: SortField> Sort<F> {
+    /// Parses `field`, `-field`, `field:asc` or `field:desc`.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (name, direction) = match raw.split_once(':') {
+            Some((name, "asc")) => (name, Direction::Asc),
+            Some((name, "desc")) => (name, Direction::Desc),
+            Some(_) => return None,
+            None => match raw.strip_prefix('-') {
+                Some(name) => (name, Direction::Desc),
+                None => (raw, Direction::Asc),
+            },
+        };
+        F::parse(name).map(|field| Sort { field, direction })
+    }
+
+    fn signature(&self) -> String {
+        let direction = match self.direction {
+            Direction::Asc => "asc",
+            Direction::Desc => "desc",
+        };
+        format!("{}:{direction}", self.field.name())
+    }
+}
+
+/// Value of the sort field for one row. Comparison matches the ordering
+/// the storage backends use for the corresponding column.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    Int(i64),
+    Text(String),
+    Time(DateTime<Utc>),
+}
+
+/// Position after which a page starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct After {
+    pub key: SortKey,
+    pub id: Uuid,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    key: SortKey,
+    id: Uuid,
+}
+
+/// Raw paging parameters as they arrive in the query string.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageParams {
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+}
+
+/// A validated list request handed to the storage layer.
+#[derive(Debug, Clone)]
+pub struct ListQuery<F, Filter> {
+    pub limit: usize,
+    pub sort: Sort<F>,
+    pub after: Option<After>,
+    pub filter: Filter,
+}
+
+impl<F: SortField, Filter> ListQuery<F, Filter> {
+    /// Validates paging parameters, collecting every problem at once.
+    pub fn new(params: PageParams, filter: Filter) -> Result<Self, ApiError> {
+        let mut errors = Vec::new();
+
+        let limit = match params.limit {
+            None => DEFAULT_LIMIT,
+            Some(limit) if (1..=MAX_LIMIT).contains(&limit) => limit,
+            Some(_) => {
+                errors.push(FieldError::new(
+                    "limit",
+                    "out_of_range",
+                    format!("must be between 1 and {MAX_LIMIT}"),
+                ));
+                DEFAULT_LIMIT
+            }
+        };
+
+        let sort = match params.sort.as_deref() {
+            None => F::DEFAULT,
+            Some(raw) => Sort::parse(raw).unwrap_or_else(|| {
+                errors.push(FieldError::new(
+                    "sort",
+                    "invalid_value",
+                    format!(
+                        "must be one of {}, optionally prefixed with `-` or suffixed with `:asc`/`:desc`",
+                        F::all().join(", ")
+                    ),
+                ));
+                F::DEFAULT
+            }),
+        };
+
+        let after = match params.cursor.as_deref() {
+            None => None,
+            Some(raw) => match decode_cursor(raw) {
+                Some(cursor) if cursor.sort == sort.signature() => Some(After {
+                    key: cursor.key,
+                    id: cursor.id,
+                }),
+                _ => {
+                    errors.push(FieldError::new(
+                        "cursor",
+                        "invalid_value",
+                        "is malformed or was issued for a different sort order",
+                    ));
+                    None
+                }
+            },
+        };
+
+        if !errors.is_empty() {
+            return Err(ApiError::Validation(errors));
+        }
+        Ok(Self {
+            limit,
+            sort,
+            after,
+            filter,
+        })
+    }
+
+    /// Whether a row at `(key, id)` comes after the cursor position.
+    pub fn admits(&self, key: &SortKey, id: Uuid) -> bool {
+        self.after.as_ref().is_none_or(|after| {
+            self.compare((key, id), (&after.key, after.id)) == Ordering::Greater
+        })
+    }
+
+    /// Orders two rows according to the requested sort, ties broken by id.
+    pub fn compare(&self, a: (&SortKey, Uuid), b: (&SortKey, Uuid)) -> Ordering {
+        let ordering = a.0.cmp(b.0).then(a.1.cmp(&b.1));
+        match self.sort.direction {
+            Direction::Asc => ordering,
+            Direction::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Builds a page from rows fetched by the backend.
+    ///
+    /// Backends return up to `limit + 1` rows; the extra one only tells us
+    /// that another page exists.
+    pub fn page<T>(&self, mut rows: Vec<T>, key: impl Fn(&T, F) -> (SortKey, Uuid)) -> Page<T> {
+        let next_cursor = if rows.len() > self.limit {
+            rows.truncate(self.limit);
+            rows.last().map(|last| {
+                let (key, id) = key(last, self.sort.field);
+                encode_cursor(&Cursor {
+                    sort: self.sort.signature(),
+                    key,
+                    id,
+                })
+            })
+        } else {
+            None
+        };
+        Page {
+            data: rows,
+            next_cursor,
+        }
+    }
+}
+
+/// A `Link` header pointing from the page at `uri` to the next one, for
+/// representations such as CSV that have no room for `next_cursor`.
+pub fn next_link(uri: &Uri, next_cursor: &str) -> HeaderValue {
+    let cursor = format!("cursor={next_cursor}");
+    let query: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("cursor="))
+        .chain([cursor.as_str()])
+        .collect();
+    let link = format!("<{}?{}>; rel=\"next\"", uri.path(), query.join("&"));
+    HeaderValue::from_str(&link).expect("URIs and cursors are valid header values")
+}
+
+fn encode_cursor(cursor: &Cursor) -> String {
+    let json = serde_json::to_vec(cursor).expect("cursor serialises");
+    URL_SAFE_NO_PAD.encode(json)
+}
+
+fn decode_cursor(raw: &str) -> Option<Cursor> {
+    let json = URL_SAFE_NO_PAD.decode(raw).ok()?;
+    serde_json::from_slice(&json).ok()
+}
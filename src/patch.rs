@@ -0,0 +1,85 @@
+//! Partial updates with `PATCH`.
+//!
+//! The format is chosen by `Content-Type`: JSON Merge Patch (RFC 7396,
+//! [`MERGE_PATCH`]) or JSON Patch (RFC 6902, [`JSON_PATCH`]). A patch is
+//! applied to the resource's client-editable fields, and the result is
+//! decoded and validated exactly like a `PUT` body, so a patch can never
+//! produce a resource a full replacement could not.
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use validator::Validate;
+
+use crate::{
+    error::ApiError,
+    extract::{decode_json, media_type},
+};
+
+pub const MERGE_PATCH: &str = "application/merge-patch+json";
+pub const JSON_PATCH: &str = "application/json-patch+json";
+
+/// A patch document from the request body.
+#[derive(Debug, Clone)]
+pub enum Patch {
+    /// An object whose members replace (or, when `null`, remove) the
+    /// target's members, recursively.
+    Merge(Value),
+    /// A list of operations applied in order, all or nothing.
+    Json(json_patch::Patch),
+}
+
+impl Patch {
+    /// Applies the patch to `target`, then decodes and validates the result.
+    ///
+    /// Operations that do not fit the target (a failed `test`, a path that
+    /// does not exist) are a 409; an invalid result is a 422 naming the
+    /// offending fields.
+    pub fn apply<T>(&self, target: &T) -> Result<T, ApiError>
+    where
+        T: Serialize + DeserializeOwned + Validate,
+    {
+        let mut doc =
+            serde_json::to_value(target).map_err(|err| ApiError::Internal(err.to_string()))?;
+        match self {
+            Patch::Merge(patch) => json_patch::merge(&mut doc, patch),
+            Patch::Json(patch) => json_patch::patch(&mut doc, patch)
+                .map_err(|err| ApiError::Conflict(format!("cannot apply patch: {err}")))?,
+        }
+        let bytes = serde_json::to_vec(&doc).map_err(|err| ApiError::Internal(err.to_string()))?;
+        let value: T = decode_json(&bytes)?;
+        value.validate()?;
+        Ok(value)
+    }
+}
+
+impl<S: Send + Sync> FromRequest<S> for Patch {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let media_type = media_type(req.headers()).unwrap_or_default();
+        if media_type != MERGE_PATCH && media_type != JSON_PATCH {
+            return Err(ApiError::UnsupportedMediaType(format!(
+                "expected `Content-Type: {MERGE_PATCH}` or `{JSON_PATCH}`"
+            )));
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+        if media_type == JSON_PATCH {
+            return serde_json::from_slice(&bytes)
+                .map(Patch::Json)
+                .map_err(|err| ApiError::BadRequest(format!("malformed JSON Patch: {err}")));
+        }
+        match serde_json::from_slice(&bytes) {
+            Ok(patch @ Value::Object(_)) => Ok(Patch::Merge(patch)),
+            Ok(_) => Err(ApiError::BadRequest(
+                "a merge patch must be a JSON object".into(),
+            )),
+            Err(err) => Err(ApiError::BadRequest(format!("malformed JSON body: {err}"))),
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Per-client token-bucket rate limiting.
+//!
+//! Each route group (see [`RouteGroup`]) can have its own [`Quota`]. Clients
+//! are keyed by the authenticated principal when there is one and by their
+//! IP address otherwise. Every limited response carries `X-RateLimit-*`
+//! headers; rejected requests get a 429 with `Retry-After`.
+//!
+//! Requests whose credentials are rejected never reach a group's limiter, so
+//! [`enforce_failed_auth`] charges them to the `auth` quota by IP instead.
+//! Behind reverse proxies, client addresses come from `X-Forwarded-For`.
+//!
+//! State lives in process memory. Time comes from a [`Clock`] so tests can
+//! drive it with a [`ManualClock`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::{auth::Principal, error::ApiError};
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Buckets beyond this count trigger a sweep of idle (full) buckets.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Source of the current time.
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<Instant>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            now: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// `requests` per `period`, with bursts of up to `requests`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            period: Duration::from_secs(60),
+        }
+    }
+
+    /// Time it takes to earn back one request.
+    fn interval(&self) -> Duration {
+        self.period / self.requests
+    }
+}
+
+impl FromStr for Quota {
+    type Err = String;
+
+    /// Parses `<requests>/<period>`, where the period is `s`, `min`, `h` or
+    /// a number of seconds, e.g. `120/min` or `10/30`.
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid quota `{raw}`, expected e.g. `120/min`");
+        let (requests, period) = raw.split_once('/').ok_or_else(invalid)?;
+        let requests: u32 = requests.trim().parse().map_err(|_| invalid())?;
+        let period = match period.trim() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            secs => Duration::from_secs(secs.parse().map_err(|_| invalid())?),
+        };
+        if requests == 0 || period.is_zero() {
+            return Err(invalid());
+        }
+        Ok(Self { requests, period })
+    }
+}
+
+impl fmt::Display for Quota {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period.as_secs() {
+            1 => write!(f, "{}/s", self.requests),
+            60 => write!(f, "{}/min", self.requests),
+            3600 => write!(f, "{}/h", self.requests),
+            secs => write!(f, "{}/{secs}", self.requests),
+        }
+    }
+}
+
+/// Groups of routes that are limited independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    /// Resource endpoints such as `/items`.
+    Api,
+    /// `/admin/*`.
+    Admin,
+    /// `/auth/*`: login and token refresh, plus failed authentication
+    /// anywhere else.
+    Auth,
+}
+
+/// Quota per route group; `None` leaves a group unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    pub api: Option<Quota>,
+    pub admin: Option<Quota>,
+    pub auth: Option<Quota>,
+    /// Reverse proxies in front of the server whose `X-Forwarded-For`
+    /// entries identify clients; with none, the peer address does.
+    pub trusted_proxies: usize,
+}
+
+/// Limiters for every configured route group.
+#[derive(Clone, Default)]
+pub struct RateLimits {
+    api: Option<Arc<RateLimiter>>,
+    admin: Option<Arc<RateLimiter>>,
+    auth: Option<Arc<RateLimiter>>,
+}
+
+impl RateLimits {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self::with_clock(config, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(config: &RateLimitConfig, clock: Arc<dyn Clock>) -> Self {
+        let build = |quota: Option<Quota>| {
+            quota.map(|quota| {
+                Arc::new(
+                    RateLimiter::new(quota, clock.clone())
+                        .with_trusted_proxies(config.trusted_proxies),
+                )
+            })
+        };
+        Self {
+            api: build(config.api),
+            admin: build(config.admin),
+            auth: build(config.auth),
+        }
+    }
+
+    pub fn get(&self, group: RouteGroup) -> Option<Arc<RateLimiter>> {
+        match group {
+            RouteGroup::Api => self.api.clone(),
+            RouteGroup::Admin => self.admin.clone(),
+            RouteGroup::Auth => self.auth.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Outcome of a rate limit check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset_after: Duration,
+    /// Until the next request would be allowed; zero if allowed now.
+    pub retry_after: Duration,
+}
+
+/// Token buckets for one quota, keyed by client.
+pub struct RateLimiter {
+    quota: Quota,
+    clock: Arc<dyn Clock>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    trusted_proxies: usize,
+}
+
+impl RateLimiter {
+    pub fn new(quota: Quota, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            quota,
+            clock,
+            buckets: Mutex::new(HashMap::new()),
+            trusted_proxies: 0,
+        }
+    }
+
+    /// Identifies anonymous clients by `X-Forwarded-For` as set by this
+    /// many reverse proxies, see [`RateLimitConfig::trusted_proxies`].
+    pub fn with_trusted_proxies(mut self, hops: usize) -> Self {
+        self.trusted_proxies = hops;
+        self
+    }
+
+    /// Takes one token from `key`'s bucket if one is available.
+    pub fn check(&self, key: &str) -> Decision {
+        let now = self.clock.now();
+        let capacity = f64::from(self.quota.requests);
+        let interval = self.quota.interval().as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() / interval
+                    < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let earned = now.duration_since(bucket.updated).as_secs_f64() / interval;
+        bucket.tokens = (bucket.tokens + earned).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let retry_after = if allowed {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - bucket.tokens) * interval)
+        };
+        Decision {
+            allowed,
+            limit: self.quota.requests,
+            remaining: bucket.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64((capacity - bucket.tokens) * interval),
+            retry_after,
+        }
+    }
+}
+
+/// Middleware enforcing `limiter`. Must run after authentication so the
+/// principal, if any, is known. Anonymous requests from an unknown address
+/// are not limited, rather than all sharing one bucket.
+pub async fn enforce(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = client_key(&request, limiter.trusted_proxies) else {
+        return next.run(request).await;
+    };
+    let decision = limiter.check(&key);
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        tracing::debug!(%key, "rate limited");
+        ApiError::RateLimited {
+            retry_after: ceil_secs(decision.retry_after),
+        }
+        .into_response()
+    };
+    set_headers(response.headers_mut(), &decision);
+    response
+}
+
+/// Middleware charging `limiter` by client IP for every request that fails
+/// authentication; once a client's bucket is empty, its failures are
+/// answered with 429 instead of 401. Authenticated requests always pass.
+/// Must run outside authentication.
+pub async fn enforce_failed_auth(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(&request, limiter.trusted_proxies);
+    let mut response = next.run(request).await;
+    let Some(ip) = ip.filter(|_| response.status() == StatusCode::UNAUTHORIZED) else {
+        return response;
+    };
+
+    let key = format!("ip:{ip}");
+    let decision = limiter.check(&key);
+    if !decision.allowed {
+        tracing::debug!(%key, "rate limited after failed authentication");
+        response = ApiError::RateLimited {
+            retry_after: ceil_secs(decision.retry_after),
+        }
+        .into_response();
+    }
+    set_headers(response.headers_mut(), &decision);
+    response
+}
+
+fn client_key(request: &Request, trusted_proxies: usize) -> Option<String> {
+    match request.extensions().get::<Principal>() {
+        Some(principal) => Some(principal.subject.clone()),
+        None => client_ip(request, trusted_proxies).map(|ip| format!("ip:{ip}")),
+    }
+}
+
+/// The address of the client behind `trusted_proxies` reverse proxies: the
+/// peer itself when there are none, otherwise the `X-Forwarded-For` entry
+/// the outermost trusted proxy added. Entries further left come from the
+/// client and are ignored.
+fn client_ip(request: &Request, trusted_proxies: usize) -> Option<IpAddr> {
+    if trusted_proxies == 0 {
+        return request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+    }
+    let forwarded: Vec<&str> = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    let index = forwarded.len().checked_sub(trusted_proxies)?;
+    forwarded[index].trim().parse().ok()
+}
+
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let mut set = |name: &'static str, value: u64| {
+        headers.insert(name, HeaderValue::from(value));
+    };
+    set("x-ratelimit-limit", decision.limit.into());
+    set("x-ratelimit-remaining", decision.remaining.into());
+    set("x-ratelimit-reset", ceil_secs(decision.reset_after));
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
@@ -0,0 +1,134 @@
+//! `/admin/api-keys`: issuing, listing and revoking API keys.
+
+use axum::{Extension, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    audit::Audit,
+    auth::{self, Principal, Scope},
+    error::{ApiError, ApiResult, ErrorBody},
+    events::Action,
+    extract::{Json, Path, Query, ValidJson},
+    idempotency::OneTimeSecret,
+    model::{ApiKey, ApiKeyFilter, ApiKeyInput, ApiKeyQuery},
+    pagination::{Page, PageParams},
+    storage::Updated,
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(list, create))
+        .routes(routes!(revoke))
+}
+
+/// A freshly issued key. `token` is only ever shown in this response.
+#[derive(Debug, Serialize, ToSchema)]
+struct IssuedKey {
+    #[serde(flatten)]
+    key: ApiKey,
+    token: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Page size, 1 to 200; defaults to 50.
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// `created_at` or `-created_at`.
+    sort: Option<String>,
+    /// Also list revoked keys.
+    #[serde(default)]
+    include_revoked: bool,
+}
+
+/// List API keys
+#[utoipa::path(
+    get,
+    path = "/admin/api-keys",
+    tag = "admin",
+    params(ListParams),
+    security(("bearer" = ["admin"])),
+    responses((status = 200, description = "A page of keys", body = Page<ApiKey>)),
+)]
+async fn list(
+    State(state): State<AppState>,
+    principal: Principal,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Json<Page<ApiKey>>> {
+    principal.require(Scope::Admin)?;
+    let query = ApiKeyQuery::new(
+        PageParams {
+            limit: params.limit,
+            cursor: params.cursor,
+            sort: params.sort,
+        },
+        ApiKeyFilter {
+            include_revoked: params.include_revoked,
+        },
+    )?;
+    let rows = state.storage.list_api_keys(&query).await?;
+    Ok(Json(query.page(rows, ApiKey::sort_key)))
+}
+
+/// Issue an API key
+#[utoipa::path(
+    post,
+    path = "/admin/api-keys",
+    tag = "admin",
+    request_body = ApiKeyInput,
+    security(("bearer" = ["admin"])),
+    responses((status = 201, description = "The key, with its one-time token", body = IssuedKey)),
+)]
+async fn create(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    ValidJson(input): ValidJson<ApiKeyInput>,
+) -> ApiResult<(StatusCode, Extension<OneTimeSecret>, Json<IssuedKey>)> {
+    principal.require(Scope::Admin)?;
+    let (key, token) = auth::issue_key(&state, input.name, input.scopes).await?;
+    tracing::info!(id = %key.id, by = %principal.subject, "issued api key");
+    audit.created(&state, &key).await;
+    state.changed(Action::Created, &key).await;
+    Ok((
+        StatusCode::CREATED,
+        Extension(OneTimeSecret),
+        Json(IssuedKey { key, token }),
+    ))
+}
+
+/// Revoke an API key
+#[utoipa::path(
+    delete,
+    path = "/admin/api-keys/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path)),
+    security(("bearer" = ["admin"])),
+    responses(
+        (status = 204, description = "Revoked"),
+        (status = 404, description = "No such key", body = ErrorBody),
+    ),
+)]
+async fn revoke(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    principal.require(Scope::Admin)?;
+    match state.storage.revoke_api_key(id).await? {
+        Some(Updated { before, after }) => {
+            tracing::info!(%id, by = %principal.subject, "revoked api key");
+            audit.updated(&state, &before, &after).await;
+            state.changed(Action::Updated, &after).await;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(ApiError::NotFound("api key")),
+    }
+}
@@ -0,0 +1,75 @@
+//! `/admin/audit-log`: the record of who changed what, see [`crate::audit`].
+
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    auth::{Principal, Scope},
+    error::ApiResult,
+    extract::{Json, Query},
+    model::{AuditEntry, AuditFilter, AuditQuery},
+    pagination::{Page, PageParams},
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new().routes(routes!(list))
+}
+
+/// Query string of `GET /admin/audit-log`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Page size, 1 to 200; defaults to 50.
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// `occurred_at` or `-occurred_at` (the default).
+    sort: Option<String>,
+    /// Only changes by this actor, e.g. `key:<uuid>` or `user:<uuid>`.
+    actor: Option<String>,
+    /// Only changes to this resource.
+    resource_id: Option<Uuid>,
+    /// Only changes at or after this time (RFC 3339).
+    since: Option<DateTime<Utc>>,
+    /// Only changes before this time (RFC 3339).
+    until: Option<DateTime<Utc>>,
+}
+
+/// List audit log entries
+///
+/// Newest first by default.
+#[utoipa::path(
+    get,
+    path = "/admin/audit-log",
+    tag = "admin",
+    params(ListParams),
+    security(("bearer" = ["admin"])),
+    responses((status = 200, description = "A page of audit log entries", body = Page<AuditEntry>)),
+)]
+async fn list(
+    State(state): State<AppState>,
+    principal: Principal,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Json<Page<AuditEntry>>> {
+    principal.require(Scope::Admin)?;
+    let query = AuditQuery::new(
+        PageParams {
+            limit: params.limit,
+            cursor: params.cursor,
+            sort: params.sort,
+        },
+        AuditFilter {
+            actor: params.actor,
+            resource_id: params.resource_id,
+            since: params.since,
+            until: params.until,
+        },
+    )?;
+    let rows = state.storage.list_audit_entries(&query).await?;
+    Ok(Json(query.page(rows, AuditEntry::sort_key)))
+}
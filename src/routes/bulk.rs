@@ -0,0 +1,275 @@
+//! `/items/export` and `/items/import`: moving items in bulk, see
+//! [`crate::bulk`].
+
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use futures_util::{StreamExt, stream};
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    app::AppState,
+    audit::Audit,
+    auth::{Principal, Scope},
+    bulk::{self, Format, ImportReport, Rows},
+    error::{ApiError, ApiResult, ErrorBody},
+    extract::{Json, Query, media_type},
+    model::{ItemFilter, ItemQuery},
+    negotiate::Accept,
+    pagination::{After, MAX_LIMIT, PageParams},
+    storage::StorageError,
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(export))
+        .routes(routes!(import))
+}
+
+/// What an export can be sent as, JSON Lines unless `Accept` prefers CSV.
+const EXPORT_TYPES: [&str; 3] = [bulk::NDJSON, "application/jsonl", bulk::CSV];
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum FormatParam {
+    Ndjson,
+    Csv,
+}
+
+/// Query string of `GET /items/export`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ExportParams {
+    /// Overrides `Accept`; JSON Lines unless either asks for CSV.
+    #[param(inline)]
+    format: Option<FormatParam>,
+    /// As for `GET /items`; defaults to `created_at`.
+    sort: Option<String>,
+    /// Case-insensitive substring of the name.
+    name: Option<String>,
+    min_quantity: Option<i64>,
+    max_quantity: Option<i64>,
+}
+
+/// Export items
+///
+/// Streams every matching item as JSON Lines or CSV, read from storage a
+/// page at a time.
+#[utoipa::path(
+    get,
+    path = "/items/export",
+    tag = "items",
+    params(ExportParams),
+    security(("bearer" = ["read"])),
+    responses((
+        status = 200,
+        description = "All matching items, one per line",
+        content((String = "application/x-ndjson"), (String = "text/csv")),
+    )),
+)]
+async fn export(
+    State(state): State<AppState>,
+    principal: Principal,
+    accept: Accept,
+    Query(params): Query<ExportParams>,
+) -> ApiResult<Response> {
+    principal.require(Scope::Read)?;
+    let format = match params.format {
+        Some(FormatParam::Ndjson) => Format::Ndjson,
+        Some(FormatParam::Csv) => Format::Csv,
+        None => accept.negotiate(&EXPORT_TYPES).map(|media_type| {
+            Format::from_media_type(media_type).expect("offered types are known")
+        })?,
+    };
+    // Validating up front turns bad parameters into a proper error rather
+    // than a broken stream.
+    let query = ItemQuery::new(
+        PageParams {
+            limit: Some(MAX_LIMIT),
+            cursor: None,
+            sort: params.sort,
+        },
+        ItemFilter {
+            name: params.name,
+            min_quantity: params.min_quantity,
+            max_quantity: params.max_quantity,
+            include_deleted: false,
+        },
+    )?;
+
+    let storage = state.storage.clone();
+    let pages = stream::try_unfold(Some(query), move |query| {
+        let storage = storage.clone();
+        async move {
+            let Some(mut query) = query else {
+                return Ok(None);
+            };
+            let mut rows = storage.list_items(&query).await?;
+            let more = rows.len() > query.limit;
+            rows.truncate(query.limit);
+            let chunk = Bytes::from(format.encode(&rows));
+            let next = match rows.last() {
+                Some(last) if more => {
+                    let (key, id) = last.sort_key(query.sort.field);
+                    query.after = Some(After { key, id });
+                    Some(query)
+                }
+                _ => None,
+            };
+            Ok::<_, StorageError>(Some((chunk, next)))
+        }
+    })
+    .inspect(|chunk| {
+        if let Err(err) = chunk {
+            // The status line is long gone; all we can do is cut the body
+            // short.
+            tracing::error!(%err, "export failed");
+        }
+    });
+    let body = stream::iter(format.preamble().map(|header| Ok(Bytes::from(header)))).chain(pages);
+
+    let disposition = format!("attachment; filename=\"items.{}\"", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_owned()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum ImportMode {
+    /// Import nothing unless every row is valid.
+    #[default]
+    Atomic,
+    /// Import the valid rows and report the rest.
+    Partial,
+}
+
+/// Query string of `POST /items/import`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ImportParams {
+    /// `atomic` (the default) imports nothing unless every row is valid;
+    /// `partial` imports the valid rows.
+    #[param(inline)]
+    mode: Option<ImportMode>,
+}
+
+/// Import items
+///
+/// Reads JSON Lines or CSV, as declared by `Content-Type`, in the shape
+/// `GET /items/export` writes. Each row is validated like a `POST /items`
+/// body; valid rows are stored in a single transaction. Imported items are
+/// not published one by one: subscribers of the change feed get a `resync`
+/// and webhooks are not called.
+#[utoipa::path(
+    post,
+    path = "/items/import",
+    tag = "items",
+    params(ImportParams),
+    request_body(
+        description = "One item per line",
+        content((String = "application/x-ndjson"), (String = "text/csv")),
+    ),
+    security(("bearer" = ["write"])),
+    responses(
+        (status = 200, description = "The valid rows were imported", body = ImportReport),
+        (status = 413, description = "The body is larger than `api.max_import_size`", body = ErrorBody),
+        (status = 415, description = "The body is neither JSON Lines nor CSV", body = ErrorBody),
+        (status = 422, description = "Some rows are invalid and nothing was imported", body = ImportReport),
+    ),
+)]
+async fn import(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    headers: HeaderMap,
+    Query(params): Query<ImportParams>,
+    body: Body,
+) -> ApiResult<(StatusCode, Json<ImportReport>)> {
+    principal.require(Scope::Write)?;
+    let format = media_type(&headers)
+        .as_deref()
+        .and_then(Format::from_media_type)
+        .ok_or_else(|| {
+            ApiError::UnsupportedMediaType(
+                "expected `Content-Type: application/x-ndjson` or `text/csv`".into(),
+            )
+        })?;
+    let mode = params.mode.unwrap_or_default();
+
+    let upload = spool(body, state.max_import_size).await?;
+    let (mut report, upload) = tokio::task::spawn_blocking(move || {
+        let report = Rows::new(format, upload.try_clone()?).check()?;
+        let mut upload = upload;
+        upload.seek(SeekFrom::Start(0))?;
+        Ok::<_, std::io::Error>((report, upload))
+    })
+    .await
+    .map_err(|err| ApiError::Internal(format!("import check panicked: {err}")))?
+    .map_err(|err| ApiError::Internal(format!("cannot read spooled upload: {err}")))?;
+
+    if report.failed > 0 && matches!(mode, ImportMode::Atomic) {
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(report)));
+    }
+    // The second pass reads the spooled file while the backend writes; it
+    // is local disk, like the backend's own.
+    let inputs = Rows::new(format, upload).filter_map(|row| match row {
+        Ok(row) => row.input.ok().map(Ok),
+        Err(err) => Some(Err(StorageError::Backend(format!(
+            "cannot read spooled upload: {err}"
+        )))),
+    });
+    report.imported = state.storage.import_items(Box::new(inputs)).await?;
+    if report.imported > 0 {
+        audit.imported(&state, "item", report.imported).await;
+        state.events.invalidate();
+    }
+    tracing::info!(
+        imported = report.imported,
+        failed = report.failed,
+        by = %principal.subject,
+        "imported items"
+    );
+    Ok((StatusCode::OK, Json(report)))
+}
+
+/// Copies the request body to an anonymous temporary file, so uploads
+/// are bounded by disk rather than memory. Bodies over `max_size` bytes are
+/// refused.
+async fn spool(body: Body, max_size: u64) -> ApiResult<File> {
+    let io_error = |err: std::io::Error| ApiError::Internal(format!("cannot spool upload: {err}"));
+    let too_large =
+        || ApiError::PayloadTooLarge(format!("imports are limited to {max_size} bytes"));
+    let mut file = tokio::fs::File::from_std(tempfile::tempfile().map_err(io_error)?);
+    let mut chunks = body.into_data_stream();
+    let mut size = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk
+            .map_err(|err| ApiError::BadRequest(format!("cannot read request body: {err}")))?;
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(too_large());
+        }
+        file.write_all(&chunk).await.map_err(io_error)?;
+    }
+    file.flush().await.map_err(io_error)?;
+    let mut file = file.into_std().await;
+    file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+    Ok(file)
+}
@@ -0,0 +1,218 @@
+//! `/events`: the change feed over Server-Sent Events and WebSocket.
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{CloseFrame, Message, WebSocket, close_code},
+    },
+    http::HeaderMap,
+    response::{
+        Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use chrono::Utc;
+use futures_util::{Stream, StreamExt, stream};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    app::AppState,
+    auth::{Credential, Principal, Scope},
+    error::{ApiError, ApiResult},
+    events::{ChangeEvent, FeedMessage, ResourceType, Subscription},
+    extract::Query,
+    health::Readiness,
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(sse))
+        .routes(routes!(websocket))
+}
+
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// How often open streams check that their API key has not been revoked.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Query string of both feed endpoints.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FeedParams {
+    /// Comma-separated resource types to watch: `item`, `api_key`. Defaults
+    /// to every type the caller may read.
+    types: Option<String>,
+    /// Resume after this event id.
+    last_event_id: Option<u64>,
+}
+
+impl FeedParams {
+    /// The requested types, checked against the caller's scopes.
+    fn types(&self, principal: &Principal) -> Result<Vec<ResourceType>, ApiError> {
+        principal.require(Scope::Read)?;
+        let Some(types) = &self.types else {
+            return Ok(ResourceType::ALL
+                .into_iter()
+                .filter(|kind| principal.has(kind.scope()))
+                .collect());
+        };
+        types
+            .split(',')
+            .map(|name| {
+                let kind: ResourceType = name.trim().parse().map_err(ApiError::BadRequest)?;
+                principal.require(kind.scope())?;
+                Ok(kind)
+            })
+            .collect()
+    }
+}
+
+/// Stream changes as Server-Sent Events
+///
+/// Each change is an event named `<resource>.<action>` (e.g.
+/// `item.updated`) whose id can be sent back as `Last-Event-ID` to resume.
+/// A `resync` event means changes were missed.
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(
+        FeedParams,
+        ("Last-Event-ID" = Option<u64>, Header, description = "Resume after this event id; overrides `last_event_id`"),
+    ),
+    security(("bearer" = ["read"])),
+    responses((status = 200, description = "An endless `text/event-stream`", content_type = "text/event-stream", body = ChangeEvent)),
+)]
+async fn sse(
+    State(state): State<AppState>,
+    principal: Principal,
+    headers: HeaderMap,
+    Query(params): Query<FeedParams>,
+) -> ApiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let types = params.types(&principal)?;
+    let last_seen = match headers.get(LAST_EVENT_ID) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or_else(|| ApiError::BadRequest("malformed Last-Event-ID header".into()))?,
+        ),
+        None => params.last_event_id,
+    };
+    let subscription = state.events.subscribe(last_seen, types);
+    let readiness = state.readiness.clone();
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let message = subscription.next().await?;
+        Some((Ok(sse_event(&message)), subscription))
+    })
+    .take_until(async move {
+        tokio::select! {
+            () = readiness.shutdown_begun() => {}
+            () = lapsed(state, principal) => {}
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn sse_event(message: &FeedMessage) -> Event {
+    match message {
+        FeedMessage::Change(change) => Event::default()
+            .id(change.id.to_string())
+            .event(change.event_type())
+            .json_data(change)
+            .expect("change events serialise"),
+        FeedMessage::Resync => Event::default().event("resync").data("{}"),
+    }
+}
+
+/// Stream changes over a WebSocket
+///
+/// Every text message is a JSON `FeedMessage`. The client is not expected
+/// to send anything.
+#[utoipa::path(
+    get,
+    path = "/events/ws",
+    tag = "events",
+    params(FeedParams),
+    security(("bearer" = ["read"])),
+    responses((status = 101, description = "Switched to a WebSocket carrying feed messages", body = FeedMessage)),
+)]
+async fn websocket(
+    State(state): State<AppState>,
+    principal: Principal,
+    Query(params): Query<FeedParams>,
+    upgrade: WebSocketUpgrade,
+) -> ApiResult<Response> {
+    let types = params.types(&principal)?;
+    let subscription = state.events.subscribe(params.last_event_id, types);
+    let readiness = state.readiness.clone();
+    let lapsed = lapsed(state, principal);
+    Ok(upgrade.on_upgrade(move |socket| relay(socket, subscription, readiness, lapsed)))
+}
+
+async fn relay(
+    mut socket: WebSocket,
+    mut subscription: Subscription,
+    readiness: Readiness,
+    lapsed: impl Future<Output = ()>,
+) {
+    tokio::pin!(lapsed);
+    loop {
+        tokio::select! {
+            message = subscription.next() => {
+                let Some(message) = message else { return };
+                let text = serde_json::to_string(&message).expect("feed messages serialise");
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    return;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                // Pings are answered by the protocol layer; anything else
+                // is ignored.
+                Some(Ok(_)) => {}
+            },
+            () = readiness.shutdown_begun() => {
+                let close = CloseFrame {
+                    code: close_code::AWAY,
+                    reason: "server shutting down".into(),
+                };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                return;
+            }
+            () = &mut lapsed => {
+                let close = CloseFrame {
+                    code: close_code::POLICY,
+                    reason: "credentials expired or revoked".into(),
+                };
+                let _ = socket.send(Message::Close(Some(close))).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Resolves once `principal`'s credential is no longer accepted: when its
+/// access token expires, or at the first check after its API key is
+/// revoked.
+async fn lapsed(state: AppState, principal: Principal) {
+    loop {
+        let mut wait = RECHECK_INTERVAL;
+        if let Credential::AccessToken { expires_at } = principal.credential {
+            let left = (expires_at * 1000 - Utc::now().timestamp_millis()).max(0);
+            wait = wait.min(Duration::from_millis(left as u64));
+        }
+        tokio::time::sleep(wait).await;
+        match principal.still_valid(&state).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            // Keep the stream rather than drop it over a passing failure.
+            Err(err) => tracing::warn!(%err, "cannot recheck stream credentials"),
+        }
+    }
+}
@@ -0,0 +1,362 @@
+//! `/items` resource.
+
+use axum::{
+    extract::{OriginalUri, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    audit::Audit,
+    auth::{Principal, Scope},
+    bulk::{self, Format},
+    conditional::{IfNoneMatch, Preconditions, Tagged},
+    error::{ApiError, ApiResult, ErrorBody},
+    events::{Action, ResourceType},
+    extract::{Json, Path, Query, ValidJson},
+    model::{Item, ItemFilter, ItemInput, ItemQuery},
+    negotiate::{Accept, JSON, MSGPACK},
+    pagination::{self, Page, PageParams},
+    patch::Patch,
+    storage::{StorageError, Updated, VersionGuard},
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(list, create))
+        .routes(routes!(show, update, patch, destroy))
+        .routes(routes!(restore))
+}
+
+const NOT_FOUND: ApiError = ApiError::NotFound("item");
+
+/// Representations of items when read. MessagePack is transcoded from the
+/// JSON response by [`crate::negotiate::respond`].
+const REPRESENTATIONS: [&str; 3] = [JSON, MSGPACK, bulk::CSV];
+
+/// How often `PATCH` re-reads an item that changed between its read and its
+/// write before giving up.
+const PATCH_ATTEMPTS: usize = 3;
+
+/// Query string of `GET /items`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Page size, 1 to 200; defaults to 50.
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// `created_at`, `updated_at`, `name` or `quantity`, optionally prefixed
+    /// with `-` or suffixed with `:desc` for descending order.
+    sort: Option<String>,
+    /// Case-insensitive substring of the name.
+    name: Option<String>,
+    min_quantity: Option<i64>,
+    max_quantity: Option<i64>,
+    /// Also list deleted items that have not been purged yet. Requires the
+    /// `admin` scope.
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+/// List items
+#[utoipa::path(
+    get,
+    path = "/items",
+    tag = "items",
+    params(ListParams),
+    security(("bearer" = ["read"])),
+    responses((
+        status = 200,
+        description = "A page of items. As CSV, the next page is linked in `Link`.",
+        content((Page<Item> = "application/json"), (String = "text/csv")),
+        headers(("Link" = String, description = "`<...>; rel=\"next\"`, for CSV responses")),
+    )),
+)]
+async fn list(
+    State(state): State<AppState>,
+    principal: Principal,
+    accept: Accept,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Response> {
+    principal.require(if params.include_deleted {
+        Scope::Admin
+    } else {
+        Scope::Read
+    })?;
+    let representation = accept.negotiate(&REPRESENTATIONS)?;
+    let query = ItemQuery::new(
+        PageParams {
+            limit: params.limit,
+            cursor: params.cursor,
+            sort: params.sort,
+        },
+        ItemFilter {
+            name: params.name,
+            min_quantity: params.min_quantity,
+            max_quantity: params.max_quantity,
+            include_deleted: params.include_deleted,
+        },
+    )?;
+    let rows = state.storage.list_items(&query).await?;
+    let page = query.page(rows, Item::sort_key);
+    if representation != bulk::CSV {
+        return Ok(Json(page).into_response());
+    }
+    let mut response = csv(&page.data);
+    if let Some(cursor) = &page.next_cursor {
+        response
+            .headers_mut()
+            .insert(header::LINK, pagination::next_link(&uri, cursor));
+    }
+    Ok(response)
+}
+
+/// `items` as CSV, in the columns of an export.
+fn csv(items: &[Item]) -> Response {
+    let mut body = Format::Csv.preamble().unwrap_or_default();
+    body.extend(Format::Csv.encode(items));
+    ([(header::CONTENT_TYPE, Format::Csv.content_type())], body).into_response()
+}
+
+/// Fetch an item
+#[utoipa::path(
+    get,
+    path = "/items/{id}",
+    tag = "items",
+    params(
+        ("id" = Uuid, Path),
+        ("If-None-Match" = Option<String>, Header, description = "Answer 304 if the item still has one of these ETags"),
+    ),
+    security(("bearer" = ["read"])),
+    responses(
+        (status = 200, description = "The item", content((Item = "application/json"), (String = "text/csv")), headers(("ETag" = String, description = "Version of the item"))),
+        (status = 304, description = "The item still has the given ETag"),
+        (status = 404, description = "No such item", body = ErrorBody),
+    ),
+)]
+async fn show(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<Uuid>,
+    accept: Accept,
+    if_none_match: IfNoneMatch,
+) -> ApiResult<Response> {
+    principal.require(Scope::Read)?;
+    let representation = accept.negotiate(&REPRESENTATIONS)?;
+    let item = state.storage.get_item(id).await?.ok_or(NOT_FOUND)?;
+    if let Some(response) = if_none_match.not_modified(item.version, representation) {
+        return Ok(response);
+    }
+    if representation == bulk::CSV {
+        return Ok(Tagged(item.version, csv(&[item])).into_response());
+    }
+    Ok(Tagged(item.version, Json(item)).into_response())
+}
+
+/// Create an item
+#[utoipa::path(
+    post,
+    path = "/items",
+    tag = "items",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response if this key was seen before"),
+    ),
+    request_body = ItemInput,
+    security(("bearer" = ["write"])),
+    responses((status = 201, description = "The created item", body = Item, headers(("ETag" = String, description = "Version of the item")))),
+)]
+async fn create(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    ValidJson(input): ValidJson<ItemInput>,
+) -> ApiResult<(StatusCode, Tagged<Json<Item>>)> {
+    principal.require(Scope::Write)?;
+    let item = state.storage.create_item(input).await?;
+    audit.created(&state, &item).await;
+    state.changed(Action::Created, &item).await;
+    Ok((StatusCode::CREATED, Tagged(item.version, Json(item))))
+}
+
+/// Replace an item
+#[utoipa::path(
+    put,
+    path = "/items/{id}",
+    tag = "items",
+    params(
+        ("id" = Uuid, Path),
+        ("If-Match" = Option<String>, Header, description = "Only replace the item if it still has one of these ETags"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response if this key was seen before"),
+    ),
+    request_body = ItemInput,
+    security(("bearer" = ["write"])),
+    responses(
+        (status = 200, description = "The updated item", body = Item, headers(("ETag" = String, description = "New version of the item"))),
+        (status = 404, description = "No such item", body = ErrorBody),
+        (status = 412, description = "The item has changed since it was read", body = ErrorBody),
+        (status = 428, description = "`If-Match` is required", body = ErrorBody),
+    ),
+)]
+async fn update(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    Path(id): Path<Uuid>,
+    preconditions: Preconditions,
+    ValidJson(input): ValidJson<ItemInput>,
+) -> ApiResult<Tagged<Json<Item>>> {
+    principal.require(Scope::Write)?;
+    let guard = preconditions.write_guard(state.require_if_match)?;
+    let Updated { before, after } = state
+        .storage
+        .update_item(id, input, &guard)
+        .await?
+        .ok_or(NOT_FOUND)?;
+    audit.updated(&state, &before, &after).await;
+    state.changed(Action::Updated, &after).await;
+    Ok(Tagged(after.version, Json(after)))
+}
+
+/// Update some fields of an item
+#[utoipa::path(
+    patch,
+    path = "/items/{id}",
+    tag = "items",
+    params(
+        ("id" = Uuid, Path),
+        ("If-Match" = Option<String>, Header, description = "Only patch the item if it still has one of these ETags"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response if this key was seen before"),
+    ),
+    request_body(
+        description = "A JSON Merge Patch or JSON Patch against the item's editable fields",
+        content(
+            (Object = "application/merge-patch+json"),
+            (json_patch::Patch = "application/json-patch+json"),
+        ),
+    ),
+    security(("bearer" = ["write"])),
+    responses(
+        (status = 200, description = "The updated item", body = Item, headers(("ETag" = String, description = "New version of the item"))),
+        (status = 404, description = "No such item", body = ErrorBody),
+        (status = 409, description = "The patch does not apply to the item", body = ErrorBody),
+        (status = 412, description = "The item has changed since it was read", body = ErrorBody),
+        (status = 428, description = "`If-Match` is required", body = ErrorBody),
+    ),
+)]
+async fn patch(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    Path(id): Path<Uuid>,
+    preconditions: Preconditions,
+    patch: Patch,
+) -> ApiResult<Tagged<Json<Item>>> {
+    principal.require(Scope::Write)?;
+    let guard = preconditions.write_guard(state.require_if_match)?;
+    for _ in 0..PATCH_ATTEMPTS {
+        let item = state.storage.get_item(id).await?.ok_or(NOT_FOUND)?;
+        if !guard.admits(item.version) {
+            return Err(ApiError::PreconditionFailed);
+        }
+        let input = patch.apply(&ItemInput::from(&item))?;
+        // Write only over the version the patch was applied to, so a
+        // concurrent change is retried rather than overwritten.
+        let applied_to = VersionGuard::OneOf(vec![item.version]);
+        match state.storage.update_item(id, input, &applied_to).await {
+            Ok(Some(Updated { before, after })) => {
+                audit.updated(&state, &before, &after).await;
+                state.changed(Action::Updated, &after).await;
+                return Ok(Tagged(after.version, Json(after)));
+            }
+            Ok(None) => return Err(NOT_FOUND),
+            Err(StorageError::VersionMismatch) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Err(ApiError::Conflict(
+        "item is being modified concurrently; retry".into(),
+    ))
+}
+
+/// Delete an item
+///
+/// Moves the item to the trash, from which an admin can restore it until it
+/// is purged.
+#[utoipa::path(
+    delete,
+    path = "/items/{id}",
+    tag = "items",
+    params(
+        ("id" = Uuid, Path),
+        ("If-Match" = Option<String>, Header, description = "Only delete the item if it still has one of these ETags"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response if this key was seen before"),
+    ),
+    security(("bearer" = ["write"])),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such item", body = ErrorBody),
+        (status = 412, description = "The item has changed since it was read", body = ErrorBody),
+        (status = 428, description = "`If-Match` is required", body = ErrorBody),
+    ),
+)]
+async fn destroy(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    Path(id): Path<Uuid>,
+    preconditions: Preconditions,
+) -> ApiResult<StatusCode> {
+    principal.require(Scope::Write)?;
+    let guard = preconditions.write_guard(state.require_if_match)?;
+    let Updated { before, .. } = state
+        .storage
+        .delete_item(id, &guard)
+        .await?
+        .ok_or(NOT_FOUND)?;
+    audit.deleted(&state, &before).await;
+    state.deleted(ResourceType::Item, id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restore a deleted item
+///
+/// Takes the item out of the trash, as long as it has not been purged.
+#[utoipa::path(
+    post,
+    path = "/items/{id}/restore",
+    tag = "items",
+    params(
+        ("id" = Uuid, Path),
+        ("Idempotency-Key" = Option<String>, Header, description = "Replay the stored response if this key was seen before"),
+    ),
+    security(("bearer" = ["admin"])),
+    responses(
+        (status = 200, description = "The restored item", body = Item, headers(("ETag" = String, description = "New version of the item"))),
+        (status = 404, description = "No such item", body = ErrorBody),
+        (status = 409, description = "The item is not deleted", body = ErrorBody),
+    ),
+)]
+async fn restore(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Tagged<Json<Item>>> {
+    principal.require(Scope::Admin)?;
+    let Some(Updated { before, after }) = state.storage.restore_item(id).await? else {
+        return Err(match state.storage.get_item(id).await? {
+            Some(_) => ApiError::Conflict("item is not deleted".into()),
+            None => NOT_FOUND,
+        });
+    };
+    audit.restored(&state, &before, &after).await;
+    state.changed(Action::Restored, &after).await;
+    Ok(Tagged(after.version, Json(after)))
+}
@@ -0,0 +1,11 @@
+//! HTTP handlers, one module per resource.
+
+pub mod api_keys;
+pub mod audit;
+pub mod bulk;
+pub mod events;
+pub mod items;
+pub mod search;
+pub mod session;
+pub mod users;
+pub mod webhooks;
@@ -0,0 +1,56 @@
+//! `/search`: full-text search over items, see [`crate::search`].
+
+use axum::extract::State;
+use serde::Deserialize;
+use utoipa::IntoParams;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    app::AppState,
+    auth::{Principal, Scope},
+    error::ApiResult,
+    extract::{Json, Query},
+    pagination::Page,
+    search::{SearchHit, SearchQuery},
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new().routes(routes!(search))
+}
+
+/// Query string of `GET /search`.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SearchParams {
+    /// Words that must all occur in an item's name or description.
+    /// `"quoted words"` must occur together, and `wid*` matches any word
+    /// starting with `wid`.
+    q: Option<String>,
+    /// Page size, 1 to 200; defaults to 50.
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+}
+
+/// Search items
+///
+/// Most relevant first; matches in the name count more than matches in the
+/// description. Matching words are wrapped in `<mark>` in `highlights`.
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "items",
+    params(SearchParams),
+    security(("bearer" = ["read"])),
+    responses((status = 200, description = "A page of matching items", body = Page<SearchHit>)),
+)]
+async fn search(
+    State(state): State<AppState>,
+    principal: Principal,
+    Query(params): Query<SearchParams>,
+) -> ApiResult<Json<Page<SearchHit>>> {
+    principal.require(Scope::Read)?;
+    let query = SearchQuery::new(params.q.as_deref(), params.limit, params.cursor.as_deref())?;
+    let hits = state.storage.search_items(&query).await?;
+    Ok(Json(query.page(hits)))
+}
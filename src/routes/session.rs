@@ -0,0 +1,197 @@
+//! `/auth`: password login and JWT session management.
+//!
+//! Only mounted when JWT sessions are configured.
+
+use axum::{extract::State, http::StatusCode};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use validator::Validate;
+
+use crate::{
+    app::AppState,
+    auth::{REFRESH_TOKEN_PREFIX, generate_token, hash_token, jwt::Jwt, password},
+    error::{ApiError, ApiResult, ErrorBody},
+    extract::{Json, ValidJson},
+    model::{RefreshToken, User},
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(login))
+        .routes(routes!(refresh))
+        .routes(routes!(logout))
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct LoginRequest {
+    #[validate(length(min = 1, max = 64))]
+    #[schema(min_length = 1, max_length = 64)]
+    username: String,
+    #[validate(length(min = 1, max = 128))]
+    #[schema(min_length = 1, max_length = 128, format = Password)]
+    password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct RefreshRequest {
+    #[validate(length(min = 1, max = 128))]
+    #[schema(min_length = 1, max_length = 128)]
+    refresh_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    /// Seconds until the access token expires.
+    expires_in: u64,
+    refresh_token: String,
+    refresh_expires_in: u64,
+}
+
+fn jwt(state: &AppState) -> &Jwt {
+    state
+        .jwt
+        .as_deref()
+        .expect("session routes are only mounted with JWT configured")
+}
+
+/// Log in with a password
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "A new session", body = TokenResponse),
+        (status = 401, description = "Invalid username or password", body = ErrorBody),
+        (status = 429, description = "Too many attempts; see `Retry-After`", body = ErrorBody),
+    ),
+)]
+async fn login(
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<LoginRequest>,
+) -> ApiResult<Json<TokenResponse>> {
+    let found = state.storage.find_user(&input.username).await?;
+    // Verify against a dummy hash for unknown users so both cases cost the same.
+    let hash = found
+        .as_ref()
+        .map_or(password::dummy_hash(), |(_, hash)| hash.as_str())
+        .to_owned();
+    // Argon2 is slow on purpose; keep it off the async workers.
+    let valid = tokio::task::spawn_blocking(move || password::verify(&input.password, &hash))
+        .await
+        .map_err(|err| ApiError::Internal(format!("password check panicked: {err}")))?;
+    let user = match found {
+        Some((user, _)) if valid => user,
+        _ => return Err(ApiError::Unauthorized("invalid username or password")),
+    };
+
+    tracing::info!(user = %user.id, "login");
+    Ok(Json(start_session(&state, &user).await?))
+}
+
+/// Rotate a refresh token
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "A new session; the old refresh token is spent", body = TokenResponse),
+        (status = 401, description = "Invalid, expired or reused refresh token", body = ErrorBody),
+        (status = 429, description = "Too many attempts; see `Retry-After`", body = ErrorBody),
+    ),
+)]
+async fn refresh(
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<RefreshRequest>,
+) -> ApiResult<Json<TokenResponse>> {
+    const INVALID: ApiError = ApiError::Unauthorized("invalid or expired refresh token");
+
+    let hash = hash_token(&input.refresh_token);
+    let token = state
+        .storage
+        .find_refresh_token(&hash)
+        .await?
+        .ok_or(INVALID)?;
+
+    if token.revoked_at.is_some() {
+        // A rotated token came back: someone holds a copy. End every session.
+        tracing::warn!(user = %token.user_id, "refresh token reuse, revoking all sessions");
+        state
+            .storage
+            .revoke_user_refresh_tokens(token.user_id)
+            .await?;
+        return Err(INVALID);
+    }
+    if token.expires_at <= Utc::now() {
+        return Err(INVALID);
+    }
+    // Losing this race means a concurrent refresh already rotated the token.
+    if !state.storage.revoke_refresh_token(&hash).await? {
+        return Err(INVALID);
+    }
+    let user = state
+        .storage
+        .get_user(token.user_id)
+        .await?
+        .ok_or(INVALID)?;
+
+    Ok(Json(start_session(&state, &user).await?))
+}
+
+/// Revoke a refresh token
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 204, description = "Revoked, or already invalid"),
+        (status = 429, description = "Too many attempts; see `Retry-After`", body = ErrorBody),
+    ),
+)]
+async fn logout(
+    State(state): State<AppState>,
+    ValidJson(input): ValidJson<RefreshRequest>,
+) -> ApiResult<StatusCode> {
+    state
+        .storage
+        .revoke_refresh_token(&hash_token(&input.refresh_token))
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Issues an access token and stores a new refresh token for `user`.
+async fn start_session(state: &AppState, user: &User) -> ApiResult<TokenResponse> {
+    let jwt = jwt(state);
+    let settings = jwt.settings();
+    let refresh_token = generate_token(REFRESH_TOKEN_PREFIX);
+    let now = Utc::now();
+    let refresh_ttl = chrono::Duration::from_std(settings.refresh_ttl)
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+
+    state
+        .storage
+        .create_refresh_token(&RefreshToken {
+            token_hash: hash_token(&refresh_token),
+            user_id: user.id,
+            created_at: now,
+            expires_at: now + refresh_ttl,
+            revoked_at: None,
+        })
+        .await?;
+
+    Ok(TokenResponse {
+        access_token: jwt.issue(user),
+        token_type: "Bearer",
+        expires_in: settings.access_ttl.as_secs(),
+        refresh_token,
+        refresh_expires_in: settings.refresh_ttl.as_secs(),
+    })
+}
@@ -0,0 +1,57 @@
+//! `/admin/users`: accounts that can log in for JWT sessions.
+
+use axum::{extract::State, http::StatusCode};
+use chrono::Utc;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    audit::Audit,
+    auth::{Principal, Scope, password},
+    error::{ApiError, ApiResult, ErrorBody},
+    extract::{Json, ValidJson},
+    model::{User, UserInput},
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new().routes(routes!(create))
+}
+
+/// Create a user
+#[utoipa::path(
+    post,
+    path = "/admin/users",
+    tag = "admin",
+    request_body = UserInput,
+    security(("bearer" = ["admin"])),
+    responses(
+        (status = 201, description = "The created user", body = User),
+        (status = 409, description = "The username is taken", body = ErrorBody),
+    ),
+)]
+async fn create(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    ValidJson(input): ValidJson<UserInput>,
+) -> ApiResult<(StatusCode, Json<User>)> {
+    principal.require(Scope::Admin)?;
+    let mut scopes = input.scopes;
+    scopes.sort();
+    scopes.dedup();
+    let user = User {
+        id: Uuid::now_v7(),
+        username: input.username,
+        scopes,
+        created_at: Utc::now(),
+    };
+    // Argon2 is slow on purpose; keep it off the async workers.
+    let hash = tokio::task::spawn_blocking(move || password::hash(&input.password))
+        .await
+        .map_err(|err| ApiError::Internal(format!("password hashing panicked: {err}")))?;
+    state.storage.create_user(&user, &hash).await?;
+    tracing::info!(id = %user.id, by = %principal.subject, "created user");
+    audit.created(&state, &user).await;
+    Ok((StatusCode::CREATED, Json(user)))
+}
@@ -0,0 +1,222 @@
+//! `/admin/webhooks`: registering webhooks and inspecting their deliveries.
+
+use axum::{Extension, extract::State, http::StatusCode};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    audit::Audit,
+    auth::{self, Principal, Scope},
+    error::{ApiError, ApiResult, ErrorBody},
+    extract::{Json, Path, Query, ValidJson},
+    idempotency::OneTimeSecret,
+    model::{
+        DeliveryFilter, DeliveryQuery, DeliveryStatus, Webhook, WebhookDelivery, WebhookInput,
+        WebhookQuery,
+    },
+    pagination::{Page, PageParams},
+    webhooks::SECRET_PREFIX,
+};
+
+pub fn router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(list, create))
+        .routes(routes!(show, destroy))
+        .routes(routes!(deliveries))
+}
+
+/// A freshly registered webhook. `secret` is only ever shown in this
+/// response.
+#[derive(Debug, Serialize, ToSchema)]
+struct CreatedWebhook {
+    #[serde(flatten)]
+    webhook: Webhook,
+    /// Key of the HMAC in `Webhook-Signature`.
+    secret: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Page size, 1 to 200; defaults to 50.
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// `created_at` or `-created_at`.
+    sort: Option<String>,
+}
+
+/// List webhooks
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks",
+    tag = "admin",
+    params(ListParams),
+    security(("bearer" = ["admin"])),
+    responses((status = 200, description = "A page of webhooks", body = Page<Webhook>)),
+)]
+async fn list(
+    State(state): State<AppState>,
+    principal: Principal,
+    Query(params): Query<ListParams>,
+) -> ApiResult<Json<Page<Webhook>>> {
+    principal.require(Scope::Admin)?;
+    let query = WebhookQuery::new(
+        PageParams {
+            limit: params.limit,
+            cursor: params.cursor,
+            sort: params.sort,
+        },
+        (),
+    )?;
+    let rows = state.storage.list_webhooks(&query).await?;
+    Ok(Json(query.page(rows, Webhook::sort_key)))
+}
+
+/// Register a webhook
+#[utoipa::path(
+    post,
+    path = "/admin/webhooks",
+    tag = "admin",
+    request_body = WebhookInput,
+    security(("bearer" = ["admin"])),
+    responses((status = 201, description = "The webhook, with its one-time secret", body = CreatedWebhook)),
+)]
+async fn create(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    ValidJson(input): ValidJson<WebhookInput>,
+) -> ApiResult<(StatusCode, Extension<OneTimeSecret>, Json<CreatedWebhook>)> {
+    principal.require(Scope::Admin)?;
+    let mut events = input.events;
+    events.sort();
+    events.dedup();
+    let webhook = Webhook {
+        id: Uuid::now_v7(),
+        url: input.url,
+        events,
+        created_at: Utc::now(),
+    };
+    let secret = auth::generate_token(SECRET_PREFIX);
+    state.storage.create_webhook(&webhook, &secret).await?;
+    tracing::info!(id = %webhook.id, url = %webhook.url, by = %principal.subject, "registered webhook");
+    audit.created(&state, &webhook).await;
+    Ok((
+        StatusCode::CREATED,
+        Extension(OneTimeSecret),
+        Json(CreatedWebhook { webhook, secret }),
+    ))
+}
+
+/// Fetch a webhook
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path)),
+    security(("bearer" = ["admin"])),
+    responses(
+        (status = 200, description = "The webhook", body = Webhook),
+        (status = 404, description = "No such webhook", body = ErrorBody),
+    ),
+)]
+async fn show(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<Webhook>> {
+    principal.require(Scope::Admin)?;
+    match state.storage.find_webhook(id).await? {
+        Some((webhook, _)) => Ok(Json(webhook)),
+        None => Err(ApiError::NotFound("webhook")),
+    }
+}
+
+/// Delete a webhook
+///
+/// Pending deliveries are dropped along with its delivery log.
+#[utoipa::path(
+    delete,
+    path = "/admin/webhooks/{id}",
+    tag = "admin",
+    params(("id" = Uuid, Path)),
+    security(("bearer" = ["admin"])),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such webhook", body = ErrorBody),
+    ),
+)]
+async fn destroy(
+    State(state): State<AppState>,
+    principal: Principal,
+    audit: Audit,
+    Path(id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    principal.require(Scope::Admin)?;
+    let webhook = state
+        .storage
+        .delete_webhook(id)
+        .await?
+        .ok_or(ApiError::NotFound("webhook"))?;
+    tracing::info!(%id, by = %principal.subject, "deleted webhook");
+    audit.deleted(&state, &webhook).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DeliveryParams {
+    /// Page size, 1 to 200; defaults to 50.
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+    /// `created_at` or `-created_at` (the default).
+    sort: Option<String>,
+    /// Only deliveries in this state.
+    #[param(inline)]
+    status: Option<DeliveryStatus>,
+}
+
+/// List a webhook's deliveries
+///
+/// Newest first by default, each with the outcome of its latest attempt.
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/{id}/deliveries",
+    tag = "admin",
+    params(("id" = Uuid, Path), DeliveryParams),
+    security(("bearer" = ["admin"])),
+    responses(
+        (status = 200, description = "A page of deliveries", body = Page<WebhookDelivery>),
+        (status = 404, description = "No such webhook", body = ErrorBody),
+    ),
+)]
+async fn deliveries(
+    State(state): State<AppState>,
+    principal: Principal,
+    Path(id): Path<Uuid>,
+    Query(params): Query<DeliveryParams>,
+) -> ApiResult<Json<Page<WebhookDelivery>>> {
+    principal.require(Scope::Admin)?;
+    let query = DeliveryQuery::new(
+        PageParams {
+            limit: params.limit,
+            cursor: params.cursor,
+            sort: params.sort,
+        },
+        DeliveryFilter {
+            webhook_id: id,
+            status: params.status,
+        },
+    )?;
+    if state.storage.find_webhook(id).await?.is_none() {
+        return Err(ApiError::NotFound("webhook"));
+    }
+    let rows = state.storage.list_deliveries(&query).await?;
+    Ok(Json(query.page(rows, WebhookDelivery::sort_key)))
+}
@@ -0,0 +1,471 @@
+//! Full-text search over items.
+//!
+//! A query is a list of terms that must all occur in an item's name or
+//! description: plain words, `"quoted phrases"` and `prefix*` words. Text
+//! is split into words at anything that is not a letter or digit and
+//! matched case-insensitively. Results are ranked by BM25, hits in the name
+//! counting [`NAME_WEIGHT`] times as much as hits in the description, and
+//! carry the matching text between [`MARK_START`] and [`MARK_END`], the
+//! rest of it HTML-escaped.
+//!
+//! The SQLite backend keeps an FTS5 index in step with the items table by
+//! triggers. The memory backend has no index and scans every item with
+//! [`scan`], which follows the same rules and ranking.
+
+use std::{cmp::Ordering, collections::HashSet, ops::Range};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    error::ApiError,
+    model::Item,
+    pagination::{DEFAULT_LIMIT, MAX_LIMIT, Page},
+    validation::FieldError,
+};
+
+pub const MARK_START: &str = "<mark>";
+pub const MARK_END: &str = "</mark>";
+/// Marks handed to SQLite, which cannot escape the text around them;
+/// [`escape_marked`] turns them into [`MARK_START`] and [`MARK_END`].
+pub const RAW_MARK_START: &str = "\u{2}";
+pub const RAW_MARK_END: &str = "\u{3}";
+/// Marks text left out of a snippet.
+pub const ELLIPSIS: &str = "…";
+/// Words in a description snippet.
+pub const SNIPPET_WORDS: usize = 16;
+/// BM25 weight of the name relative to the description.
+pub const NAME_WEIGHT: f64 = 4.0;
+/// Longest query string accepted, in characters.
+pub const MAX_QUERY_LENGTH: usize = 500;
+
+/// BM25 parameters, as used by SQLite FTS5.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// One matching item.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SearchHit {
+    pub item: Item,
+    /// Relevance; higher is better. Only comparable within one result list.
+    pub score: f64,
+    pub highlights: Highlights,
+}
+
+/// Item text with matches between `<mark>` and `</mark>`. Everything else
+/// is HTML-escaped.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Highlights {
+    /// The whole name.
+    pub name: String,
+    /// A snippet of the description around its best matches; `null` if
+    /// the description did not match.
+    pub description: Option<String>,
+}
+
+/// `text` with `&`, `<`, `>`, `"` and `'` replaced by character references.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Escapes `text` marked with [`RAW_MARK_START`] and [`RAW_MARK_END`], then
+/// swaps in the real marks.
+pub fn escape_marked(text: &str) -> String {
+    escape_html(text)
+        .replace(RAW_MARK_START, MARK_START)
+        .replace(RAW_MARK_END, MARK_END)
+}
+
+/// A word or a run of consecutive words, the last of which may be a
+/// prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub words: Vec<String>,
+    pub prefix: bool,
+}
+
+/// A validated search handed to the storage layer.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub terms: Vec<Term>,
+    pub limit: usize,
+    /// Hits to skip, from the cursor.
+    pub offset: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    query: String,
+    offset: usize,
+}
+
+impl SearchQuery {
+    /// Parses `q` and validates paging parameters, collecting every problem
+    /// at once.
+    pub fn new(
+        q: Option<&str>,
+        limit: Option<usize>,
+        cursor: Option<&str>,
+    ) -> Result<Self, ApiError> {
+        let mut errors = Vec::new();
+
+        let terms = match q {
+            None => {
+                errors.push(FieldError::new("q", "missing", "is required"));
+                Vec::new()
+            }
+            Some(q) if q.chars().count() > MAX_QUERY_LENGTH => {
+                errors.push(FieldError::new(
+                    "q",
+                    "too_long",
+                    format!("must be at most {MAX_QUERY_LENGTH} characters long"),
+                ));
+                Vec::new()
+            }
+            Some(q) => {
+                let terms = parse(q);
+                if terms.is_empty() {
+                    errors.push(FieldError::new(
+                        "q",
+                        "invalid_value",
+                        "must contain at least one word",
+                    ));
+                }
+                terms
+            }
+        };
+
+        let limit = match limit {
+            None => DEFAULT_LIMIT,
+            Some(limit) if (1..=MAX_LIMIT).contains(&limit) => limit,
+            Some(_) => {
+                errors.push(FieldError::new(
+                    "limit",
+                    "out_of_range",
+                    format!("must be between 1 and {MAX_LIMIT}"),
+                ));
+                DEFAULT_LIMIT
+            }
+        };
+
+        let mut query = Self {
+            terms,
+            limit,
+            offset: 0,
+        };
+        if let Some(raw) = cursor {
+            match decode_cursor(raw) {
+                Some(cursor) if cursor.query == query.fts5() => query.offset = cursor.offset,
+                _ => errors.push(FieldError::new(
+                    "cursor",
+                    "invalid_value",
+                    "is malformed or was issued for a different query",
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(ApiError::Validation(errors));
+        }
+        Ok(query)
+    }
+
+    /// The query as an FTS5 `MATCH` expression. Every term is quoted, so
+    /// nothing a client sends is read as FTS5 syntax.
+    pub fn fts5(&self) -> String {
+        self.terms
+            .iter()
+            .map(|term| {
+                let star = if term.prefix { "*" } else { "" };
+                format!("\"{}\"{star}", term.words.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Builds a page from hits fetched by the backend, which returns up to
+    /// `limit + 1` of them.
+    pub fn page(&self, mut hits: Vec<SearchHit>) -> Page<SearchHit> {
+        let next_cursor = (hits.len() > self.limit).then(|| {
+            hits.truncate(self.limit);
+            let cursor = Cursor {
+                query: self.fts5(),
+                offset: self.offset + self.limit,
+            };
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).expect("cursor serialises"))
+        });
+        Page {
+            data: hits,
+            next_cursor,
+        }
+    }
+}
+
+fn decode_cursor(raw: &str) -> Option<Cursor> {
+    let json = URL_SAFE_NO_PAD.decode(raw).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Splits a query into terms: quoted runs become phrases, other words
+/// stand alone unless they hold punctuation (`wi-fi` is the phrase
+/// `"wi fi"`), and a trailing `*` makes the last word a prefix.
+fn parse(q: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+    // Odd segments are inside quotes; an unclosed quote runs to the end.
+    for (index, segment) in q.split('"').enumerate() {
+        let chunks: Vec<&str> = if index % 2 == 1 {
+            vec![segment]
+        } else {
+            segment.split_whitespace().collect()
+        };
+        for chunk in chunks {
+            let trimmed = chunk.trim_end();
+            let words: Vec<String> = words(trimmed).into_iter().map(|word| word.text).collect();
+            if !words.is_empty() {
+                terms.push(Term {
+                    words,
+                    prefix: trimmed.ends_with('*'),
+                });
+            }
+        }
+    }
+    terms
+}
+
+/// One word of some text.
+#[derive(Debug, Clone)]
+struct Word {
+    /// Lowercased.
+    text: String,
+    /// Where it is in the original text, in bytes.
+    span: Range<usize>,
+}
+
+/// The words of `text`: runs of letters and digits.
+fn words(text: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(index),
+            (Some(from), false) => {
+                words.push(Word {
+                    text: text[from..index].to_lowercase(),
+                    span: from..index,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// An item field split into words.
+struct Field<'a> {
+    text: &'a str,
+    words: Vec<Word>,
+}
+
+impl<'a> Field<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            words: words(text),
+        }
+    }
+
+    /// Word indices at which `term` starts.
+    fn occurrences(&self, term: &Term) -> Vec<usize> {
+        let n = term.words.len();
+        if self.words.len() < n {
+            return Vec::new();
+        }
+        (0..=self.words.len() - n)
+            .filter(|&start| {
+                term.words.iter().enumerate().all(|(offset, wanted)| {
+                    let word = &self.words[start + offset].text;
+                    if term.prefix && offset == n - 1 {
+                        word.starts_with(wanted.as_str())
+                    } else {
+                        word == wanted
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Indices of every word belonging to an occurrence of some term.
+    fn matched(&self, terms: &[Term]) -> HashSet<usize> {
+        terms
+            .iter()
+            .flat_map(|term| {
+                self.occurrences(term)
+                    .into_iter()
+                    .flat_map(move |start| start..start + term.words.len())
+            })
+            .collect()
+    }
+
+    /// `words[range]` with surrounding text, matched runs marked.
+    fn render(&self, range: Range<usize>, matched: &HashSet<usize>) -> String {
+        let mut out = String::new();
+        let (from, to) = if range.start == 0 && range.end == self.words.len() {
+            (0, self.text.len())
+        } else {
+            (
+                self.words[range.start].span.start,
+                self.words[range.end - 1].span.end,
+            )
+        };
+        let mut cursor = from;
+        let mut index = range.start;
+        while index < range.end {
+            if !matched.contains(&index) {
+                index += 1;
+                continue;
+            }
+            let first = index;
+            while index + 1 < range.end && matched.contains(&(index + 1)) {
+                index += 1;
+            }
+            let (start, end) = (self.words[first].span.start, self.words[index].span.end);
+            out.push_str(&escape_html(&self.text[cursor..start]));
+            out.push_str(MARK_START);
+            out.push_str(&escape_html(&self.text[start..end]));
+            out.push_str(MARK_END);
+            cursor = end;
+            index += 1;
+        }
+        out.push_str(&escape_html(&self.text[cursor..to]));
+        out
+    }
+
+    /// The whole text, matches marked.
+    fn highlight(&self, terms: &[Term]) -> String {
+        self.render(0..self.words.len(), &self.matched(terms))
+    }
+
+    /// The [`SNIPPET_WORDS`] words holding the most matched words, marked,
+    /// or `None` if nothing matched.
+    fn snippet(&self, terms: &[Term]) -> Option<String> {
+        let matched = self.matched(terms);
+        if matched.is_empty() {
+            return None;
+        }
+        let size = SNIPPET_WORDS.min(self.words.len());
+        let start = (0..=self.words.len() - size)
+            .max_by(|&a, &b| {
+                let count = |start: usize| {
+                    (start..start + size)
+                        .filter(|i| matched.contains(i))
+                        .count()
+                };
+                // Prefer earlier windows on ties.
+                count(a).cmp(&count(b)).then(b.cmp(&a))
+            })
+            .unwrap_or(0);
+        let end = start + size;
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push_str(ELLIPSIS);
+        }
+        snippet.push_str(&self.render(start..end, &matched));
+        if end < self.words.len() {
+            snippet.push_str(ELLIPSIS);
+        }
+        Some(snippet)
+    }
+}
+
+/// Ranks `items` against `query` without an index, mirroring SQLite FTS5:
+/// BM25 with [`NAME_WEIGHT`], best first, ties broken by id. Returns up to
+/// `query.limit + 1` hits after `query.offset`.
+pub fn scan<'a>(query: &SearchQuery, items: impl Iterator<Item = &'a Item>) -> Vec<SearchHit> {
+    let documents: Vec<(&Item, Field<'_>, Field<'_>)> = items
+        .map(|item| {
+            (
+                item,
+                Field::new(&item.name),
+                Field::new(item.description.as_deref().unwrap_or_default()),
+            )
+        })
+        .collect();
+    let total = documents.len() as f64;
+    let length = |name: &Field<'_>, description: &Field<'_>| {
+        (name.words.len() + description.words.len()) as f64
+    };
+    let average_length = documents
+        .iter()
+        .map(|(_, name, description)| length(name, description))
+        .sum::<f64>()
+        / total.max(1.0);
+
+    // Weighted hits of every term in every document.
+    let hits: Vec<Vec<f64>> = documents
+        .iter()
+        .map(|(_, name, description)| {
+            query
+                .terms
+                .iter()
+                .map(|term| {
+                    NAME_WEIGHT * name.occurrences(term).len() as f64
+                        + description.occurrences(term).len() as f64
+                })
+                .collect()
+        })
+        .collect();
+    let idf: Vec<f64> = (0..query.terms.len())
+        .map(|term| {
+            let containing = hits.iter().filter(|hits| hits[term] > 0.0).count() as f64;
+            ((total - containing + 0.5) / (containing + 0.5))
+                .ln()
+                .max(1e-6)
+        })
+        .collect();
+
+    let mut results: Vec<SearchHit> = documents
+        .iter()
+        .zip(&hits)
+        .filter(|(_, hits)| hits.iter().all(|&hits| hits > 0.0))
+        .map(|((item, name, description), hits)| {
+            let normalised = K1 * (1.0 - B + B * length(name, description) / average_length);
+            let score = hits
+                .iter()
+                .zip(&idf)
+                .map(|(&hits, idf)| idf * hits * (K1 + 1.0) / (hits + normalised))
+                .sum();
+            SearchHit {
+                item: (*item).clone(),
+                score,
+                highlights: Highlights {
+                    name: name.highlight(&query.terms),
+                    description: description.snippet(&query.terms),
+                },
+            }
+        })
+        .collect();
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then(a.item.id.cmp(&b.item.id))
+    });
+    results
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit + 1)
+        .collect()
+}
@@ -0,0 +1,87 @@
+//! Listener setup and graceful shutdown.
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use axum::Router;
+use tokio::net::TcpListener;
+
+use crate::health::Readiness;
+
+/// Binds `addr` and serves `app` until SIGINT or SIGTERM is received and
+/// `drain_for` has passed, see [`drain`].
+pub async fn run(
+    addr: SocketAddr,
+    app: Router,
+    readiness: Readiness,
+    drain_for: Duration,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!(addr = %listener.local_addr()?, "listening");
+    serve(
+        listener,
+        app,
+        drain(readiness, drain_for, shutdown_signal()),
+    )
+    .await
+}
+
+/// Resolves `delay` after `signal`. In between the service reports itself
+/// unready but keeps accepting requests, giving load balancers time to
+/// notice before the listener closes.
+pub async fn drain(readiness: Readiness, delay: Duration, signal: impl Future<Output = ()>) {
+    signal.await;
+    readiness.begin_shutdown();
+    if !delay.is_zero() {
+        tracing::info!(?delay, "unready, draining before shutdown");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Serves `app` on an already bound listener until `shutdown` resolves.
+///
+/// In-flight requests are allowed to finish before this returns.
+pub async fn serve<F>(listener: TcpListener, app: Router, shutdown: F) -> io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown)
+    .await?;
+    tracing::info!("server stopped");
+    Ok(())
+}
+
+/// Resolves once the process receives SIGINT (Ctrl-C) or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!(%err, "failed to listen for SIGINT");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(err) => {
+                tracing::error!(%err, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => tracing::info!("received SIGINT, shutting down"),
+        () = terminate => tracing::info!("received SIGTERM, shutting down"),
+    }
+}
@@ -0,0 +1,306 @@
+//! Decorator timing every call into another backend.
+
+use std::{future::Future, sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::{
+    ApiKeyStore, AuditStore, DynStorage, HealthStore, IdempotencyStore, ItemStore,
+    RefreshTokenStore, StorageError, Updated, UserStore, VersionGuard, WebhookStore,
+};
+use crate::{
+    metrics::Metrics,
+    model::{
+        ApiKey, ApiKeyQuery, AuditEntry, AuditQuery, DeliveryQuery, IdempotencyRecord, Item,
+        ItemInput, ItemQuery, RefreshToken, StoredResponse, User, Webhook, WebhookDelivery,
+        WebhookQuery,
+    },
+    search::{SearchHit, SearchQuery},
+};
+
+/// Wraps a backend and records how long each operation takes.
+pub struct InstrumentedStorage {
+    inner: DynStorage,
+    metrics: Arc<Metrics>,
+}
+
+impl InstrumentedStorage {
+    pub fn new(inner: DynStorage, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    async fn timed<T>(
+        &self,
+        operation: &str,
+        call: impl Future<Output = Result<T, StorageError>>,
+    ) -> Result<T, StorageError> {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics
+            .observe_storage(operation, result.is_ok(), started.elapsed());
+        result
+    }
+}
+
+#[async_trait]
+impl ItemStore for InstrumentedStorage {
+    async fn list_items(&self, query: &ItemQuery) -> Result<Vec<Item>, StorageError> {
+        self.timed("list_items", self.inner.list_items(query)).await
+    }
+
+    async fn get_item(&self, id: Uuid) -> Result<Option<Item>, StorageError> {
+        self.timed("get_item", self.inner.get_item(id)).await
+    }
+
+    async fn search_items(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, StorageError> {
+        self.timed("search_items", self.inner.search_items(query))
+            .await
+    }
+
+    async fn create_item(&self, input: ItemInput) -> Result<Item, StorageError> {
+        self.timed("create_item", self.inner.create_item(input))
+            .await
+    }
+
+    async fn import_items(
+        &self,
+        inputs: Box<dyn Iterator<Item = Result<ItemInput, StorageError>> + Send>,
+    ) -> Result<u64, StorageError> {
+        self.timed("import_items", self.inner.import_items(inputs))
+            .await
+    }
+
+    async fn update_item(
+        &self,
+        id: Uuid,
+        input: ItemInput,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError> {
+        self.timed("update_item", self.inner.update_item(id, input, guard))
+            .await
+    }
+
+    async fn delete_item(
+        &self,
+        id: Uuid,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError> {
+        self.timed("delete_item", self.inner.delete_item(id, guard))
+            .await
+    }
+
+    async fn restore_item(&self, id: Uuid) -> Result<Option<Updated<Item>>, StorageError> {
+        self.timed("restore_item", self.inner.restore_item(id))
+            .await
+    }
+
+    async fn purge_items(&self, deleted_before: DateTime<Utc>) -> Result<u64, StorageError> {
+        self.timed("purge_items", self.inner.purge_items(deleted_before))
+            .await
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for InstrumentedStorage {
+    async fn create_api_key(&self, key: &ApiKey, token_hash: &str) -> Result<(), StorageError> {
+        self.timed("create_api_key", self.inner.create_api_key(key, token_hash))
+            .await
+    }
+
+    async fn find_api_key(&self, token_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        self.timed("find_api_key", self.inner.find_api_key(token_hash))
+            .await
+    }
+
+    async fn list_api_keys(&self, query: &ApiKeyQuery) -> Result<Vec<ApiKey>, StorageError> {
+        self.timed("list_api_keys", self.inner.list_api_keys(query))
+            .await
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<Option<Updated<ApiKey>>, StorageError> {
+        self.timed("revoke_api_key", self.inner.revoke_api_key(id))
+            .await
+    }
+}
+
+#[async_trait]
+impl UserStore for InstrumentedStorage {
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<(), StorageError> {
+        self.timed("create_user", self.inner.create_user(user, password_hash))
+            .await
+    }
+
+    async fn find_user(&self, username: &str) -> Result<Option<(User, String)>, StorageError> {
+        self.timed("find_user", self.inner.find_user(username))
+            .await
+    }
+
+    async fn get_user(&self, id: Uuid) -> Result<Option<User>, StorageError> {
+        self.timed("get_user", self.inner.get_user(id)).await
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for InstrumentedStorage {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<(), StorageError> {
+        self.timed(
+            "create_refresh_token",
+            self.inner.create_refresh_token(token),
+        )
+        .await
+    }
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, StorageError> {
+        self.timed(
+            "find_refresh_token",
+            self.inner.find_refresh_token(token_hash),
+        )
+        .await
+    }
+
+    async fn revoke_refresh_token(&self, token_hash: &str) -> Result<bool, StorageError> {
+        self.timed(
+            "revoke_refresh_token",
+            self.inner.revoke_refresh_token(token_hash),
+        )
+        .await
+    }
+
+    async fn revoke_user_refresh_tokens(&self, user_id: Uuid) -> Result<(), StorageError> {
+        self.timed(
+            "revoke_user_refresh_tokens",
+            self.inner.revoke_user_refresh_tokens(user_id),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for InstrumentedStorage {
+    async fn claim_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, StorageError> {
+        self.timed(
+            "claim_idempotency_key",
+            self.inner.claim_idempotency_key(record),
+        )
+        .await
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        self.timed(
+            "complete_idempotency_key",
+            self.inner
+                .complete_idempotency_key(scope, key, response, expires_at),
+        )
+        .await
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), StorageError> {
+        self.timed(
+            "release_idempotency_key",
+            self.inner.release_idempotency_key(scope, key),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl WebhookStore for InstrumentedStorage {
+    async fn create_webhook(&self, webhook: &Webhook, secret: &str) -> Result<(), StorageError> {
+        self.timed("create_webhook", self.inner.create_webhook(webhook, secret))
+            .await
+    }
+
+    async fn find_webhook(&self, id: Uuid) -> Result<Option<(Webhook, String)>, StorageError> {
+        self.timed("find_webhook", self.inner.find_webhook(id))
+            .await
+    }
+
+    async fn list_webhooks(&self, query: &WebhookQuery) -> Result<Vec<Webhook>, StorageError> {
+        self.timed("list_webhooks", self.inner.list_webhooks(query))
+            .await
+    }
+
+    async fn subscribed_webhooks(&self, event_type: &str) -> Result<Vec<Webhook>, StorageError> {
+        self.timed(
+            "subscribed_webhooks",
+            self.inner.subscribed_webhooks(event_type),
+        )
+        .await
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<Option<Webhook>, StorageError> {
+        self.timed("delete_webhook", self.inner.delete_webhook(id))
+            .await
+    }
+
+    async fn create_deliveries(&self, deliveries: &[WebhookDelivery]) -> Result<(), StorageError> {
+        self.timed(
+            "create_deliveries",
+            self.inner.create_deliveries(deliveries),
+        )
+        .await
+    }
+
+    async fn claim_due_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, StorageError> {
+        self.timed(
+            "claim_due_deliveries",
+            self.inner.claim_due_deliveries(now, lease_until, limit),
+        )
+        .await
+    }
+
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), StorageError> {
+        self.timed("update_delivery", self.inner.update_delivery(delivery))
+            .await
+    }
+
+    async fn list_deliveries(
+        &self,
+        query: &DeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>, StorageError> {
+        self.timed("list_deliveries", self.inner.list_deliveries(query))
+            .await
+    }
+}
+
+#[async_trait]
+impl AuditStore for InstrumentedStorage {
+    async fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), StorageError> {
+        self.timed("append_audit_entry", self.inner.append_audit_entry(entry))
+            .await
+    }
+
+    async fn list_audit_entries(
+        &self,
+        query: &AuditQuery,
+    ) -> Result<Vec<AuditEntry>, StorageError> {
+        self.timed("list_audit_entries", self.inner.list_audit_entries(query))
+            .await
+    }
+}
+
+#[async_trait]
+impl HealthStore for InstrumentedStorage {
+    async fn check_health(&self) -> Result<(), StorageError> {
+        self.timed("check_health", self.inner.check_health()).await
+    }
+}
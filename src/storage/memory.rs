@@ -0,0 +1,448 @@
+//! Volatile backend keeping everything in process memory.
+
+use std::{collections::HashMap, sync::RwLock};
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    ApiKeyStore, AuditStore, HealthStore, IdempotencyStore, ItemStore, RefreshTokenStore,
+    StorageError, Updated, UserStore, VersionGuard, WebhookStore,
+};
+use crate::{
+    model::{
+        ApiKey, ApiKeyQuery, AuditEntry, AuditQuery, DeliveryQuery, DeliveryStatus,
+        IdempotencyRecord, Item, ItemInput, ItemQuery, RefreshToken, StoredResponse, User, Webhook,
+        WebhookDelivery, WebhookQuery,
+    },
+    pagination::{ListQuery, SortField, SortKey},
+    search::{self, SearchHit, SearchQuery},
+};
+
+/// In-memory backend. Data is lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    items: RwLock<HashMap<Uuid, Item>>,
+    /// Keyed by token hash.
+    api_keys: RwLock<HashMap<String, ApiKey>>,
+    /// Keyed by username, with the password hash.
+    users: RwLock<HashMap<String, (User, String)>>,
+    /// Keyed by token hash.
+    refresh_tokens: RwLock<HashMap<String, RefreshToken>>,
+    /// Keyed by scope and key.
+    idempotency: RwLock<HashMap<(String, String), IdempotencyRecord>>,
+    /// With their secrets.
+    webhooks: RwLock<HashMap<Uuid, (Webhook, String)>>,
+    deliveries: RwLock<HashMap<Uuid, WebhookDelivery>>,
+    audit_log: RwLock<Vec<AuditEntry>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Applies keyset pagination to an unordered set of candidate rows.
+fn page<'a, T: Clone + 'a, F: SortField, Filter>(
+    query: &ListQuery<F, Filter>,
+    rows: impl Iterator<Item = &'a T>,
+    sort_key: impl Fn(&T, F) -> (SortKey, Uuid),
+) -> Vec<T> {
+    let field = query.sort.field;
+    let mut rows: Vec<_> = rows
+        .map(|row| (row, sort_key(row, field)))
+        .filter(|(_, (key, id))| query.admits(key, *id))
+        .collect();
+    rows.sort_by(|(_, (ka, ia)), (_, (kb, ib))| query.compare((ka, *ia), (kb, *ib)));
+    rows.truncate(query.limit + 1);
+    rows.into_iter().map(|(row, _)| row.clone()).collect()
+}
+
+#[async_trait]
+impl ItemStore for MemoryStorage {
+    async fn list_items(&self, query: &ItemQuery) -> Result<Vec<Item>, StorageError> {
+        let items = self.items.read().unwrap();
+        Ok(page(
+            query,
+            items.values().filter(|item| query.filter.matches(item)),
+            Item::sort_key,
+        ))
+    }
+
+    async fn get_item(&self, id: Uuid) -> Result<Option<Item>, StorageError> {
+        let items = self.items.read().unwrap();
+        Ok(items
+            .get(&id)
+            .filter(|item| item.deleted_at.is_none())
+            .cloned())
+    }
+
+    async fn search_items(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, StorageError> {
+        let items = self.items.read().unwrap();
+        Ok(search::scan(
+            query,
+            items.values().filter(|item| item.deleted_at.is_none()),
+        ))
+    }
+
+    async fn create_item(&self, input: ItemInput) -> Result<Item, StorageError> {
+        let item = Item::new(input);
+        self.items.write().unwrap().insert(item.id, item.clone());
+        Ok(item)
+    }
+
+    async fn import_items(
+        &self,
+        inputs: Box<dyn Iterator<Item = Result<ItemInput, StorageError>> + Send>,
+    ) -> Result<u64, StorageError> {
+        let imported: Vec<Item> = tokio::task::spawn_blocking(move || {
+            inputs
+                .map(|input| input.map(Item::new))
+                .collect::<Result<_, _>>()
+        })
+        .await
+        .map_err(|err| StorageError::Backend(format!("import panicked: {err}")))??;
+        let count = imported.len() as u64;
+        let mut items = self.items.write().unwrap();
+        items.extend(imported.into_iter().map(|item| (item.id, item)));
+        Ok(count)
+    }
+
+    async fn update_item(
+        &self,
+        id: Uuid,
+        input: ItemInput,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError> {
+        let mut items = self.items.write().unwrap();
+        let Some(item) = items.get_mut(&id).filter(|item| item.deleted_at.is_none()) else {
+            return Ok(None);
+        };
+        if !guard.admits(item.version) {
+            return Err(StorageError::VersionMismatch);
+        }
+        let before = item.clone();
+        item.apply(input);
+        Ok(Some(Updated {
+            before,
+            after: item.clone(),
+        }))
+    }
+
+    async fn delete_item(
+        &self,
+        id: Uuid,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError> {
+        let mut items = self.items.write().unwrap();
+        let Some(item) = items.get_mut(&id).filter(|item| item.deleted_at.is_none()) else {
+            return Ok(None);
+        };
+        if !guard.admits(item.version) {
+            return Err(StorageError::VersionMismatch);
+        }
+        let before = item.clone();
+        item.set_deleted_at(Some(Utc::now()));
+        Ok(Some(Updated {
+            before,
+            after: item.clone(),
+        }))
+    }
+
+    async fn restore_item(&self, id: Uuid) -> Result<Option<Updated<Item>>, StorageError> {
+        let mut items = self.items.write().unwrap();
+        let Some(item) = items.get_mut(&id).filter(|item| item.deleted_at.is_some()) else {
+            return Ok(None);
+        };
+        let before = item.clone();
+        item.set_deleted_at(None);
+        Ok(Some(Updated {
+            before,
+            after: item.clone(),
+        }))
+    }
+
+    async fn purge_items(&self, deleted_before: DateTime<Utc>) -> Result<u64, StorageError> {
+        let mut items = self.items.write().unwrap();
+        let count = items.len();
+        items.retain(|_, item| item.deleted_at.is_none_or(|at| at >= deleted_before));
+        Ok((count - items.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for MemoryStorage {
+    async fn create_api_key(&self, key: &ApiKey, token_hash: &str) -> Result<(), StorageError> {
+        let mut keys = self.api_keys.write().unwrap();
+        if keys.contains_key(token_hash) {
+            return Err(StorageError::Conflict("api key already exists".into()));
+        }
+        keys.insert(token_hash.to_owned(), key.clone());
+        Ok(())
+    }
+
+    async fn find_api_key(&self, token_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        Ok(self.api_keys.read().unwrap().get(token_hash).cloned())
+    }
+
+    async fn list_api_keys(&self, query: &ApiKeyQuery) -> Result<Vec<ApiKey>, StorageError> {
+        let keys = self.api_keys.read().unwrap();
+        Ok(page(
+            query,
+            keys.values()
+                .filter(|key| query.filter.include_revoked || key.revoked_at.is_none()),
+            ApiKey::sort_key,
+        ))
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<Option<Updated<ApiKey>>, StorageError> {
+        let mut keys = self.api_keys.write().unwrap();
+        Ok(keys.values_mut().find(|key| key.id == id).map(|key| {
+            let before = key.clone();
+            key.revoked_at.get_or_insert_with(Utc::now);
+            Updated {
+                before,
+                after: key.clone(),
+            }
+        }))
+    }
+}
+
+#[async_trait]
+impl UserStore for MemoryStorage {
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<(), StorageError> {
+        let mut users = self.users.write().unwrap();
+        if users.contains_key(&user.username) {
+            return Err(StorageError::Conflict(format!(
+                "username `{}` is taken",
+                user.username
+            )));
+        }
+        users.insert(
+            user.username.clone(),
+            (user.clone(), password_hash.to_owned()),
+        );
+        Ok(())
+    }
+
+    async fn find_user(&self, username: &str) -> Result<Option<(User, String)>, StorageError> {
+        Ok(self.users.read().unwrap().get(username).cloned())
+    }
+
+    async fn get_user(&self, id: Uuid) -> Result<Option<User>, StorageError> {
+        let users = self.users.read().unwrap();
+        Ok(users
+            .values()
+            .find(|(user, _)| user.id == id)
+            .map(|(user, _)| user.clone()))
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for MemoryStorage {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<(), StorageError> {
+        self.refresh_tokens
+            .write()
+            .unwrap()
+            .insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, StorageError> {
+        Ok(self.refresh_tokens.read().unwrap().get(token_hash).cloned())
+    }
+
+    async fn revoke_refresh_token(&self, token_hash: &str) -> Result<bool, StorageError> {
+        let mut tokens = self.refresh_tokens.write().unwrap();
+        Ok(match tokens.get_mut(token_hash) {
+            Some(token) if token.revoked_at.is_none() => {
+                token.revoked_at = Some(Utc::now());
+                true
+            }
+            _ => false,
+        })
+    }
+
+    async fn revoke_user_refresh_tokens(&self, user_id: Uuid) -> Result<(), StorageError> {
+        let now = Utc::now();
+        for token in self.refresh_tokens.write().unwrap().values_mut() {
+            if token.user_id == user_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryStorage {
+    async fn claim_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, StorageError> {
+        let now = Utc::now();
+        let mut records = self.idempotency.write().unwrap();
+        records.retain(|_, existing| existing.expires_at > now);
+        let id = (record.scope.clone(), record.key.clone());
+        if let Some(existing) = records.get(&id) {
+            return Ok(Some(existing.clone()));
+        }
+        records.insert(id, record.clone());
+        Ok(None)
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let mut records = self.idempotency.write().unwrap();
+        if let Some(record) = records.get_mut(&(scope.to_owned(), key.to_owned())) {
+            record.response = Some(response.clone());
+            record.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), StorageError> {
+        self.idempotency
+            .write()
+            .unwrap()
+            .remove(&(scope.to_owned(), key.to_owned()));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookStore for MemoryStorage {
+    async fn create_webhook(&self, webhook: &Webhook, secret: &str) -> Result<(), StorageError> {
+        self.webhooks
+            .write()
+            .unwrap()
+            .insert(webhook.id, (webhook.clone(), secret.to_owned()));
+        Ok(())
+    }
+
+    async fn find_webhook(&self, id: Uuid) -> Result<Option<(Webhook, String)>, StorageError> {
+        Ok(self.webhooks.read().unwrap().get(&id).cloned())
+    }
+
+    async fn list_webhooks(&self, query: &WebhookQuery) -> Result<Vec<Webhook>, StorageError> {
+        let webhooks = self.webhooks.read().unwrap();
+        Ok(page(
+            query,
+            webhooks.values().map(|(webhook, _)| webhook),
+            Webhook::sort_key,
+        ))
+    }
+
+    async fn subscribed_webhooks(&self, event_type: &str) -> Result<Vec<Webhook>, StorageError> {
+        let webhooks = self.webhooks.read().unwrap();
+        Ok(webhooks
+            .values()
+            .filter(|(webhook, _)| webhook.events.iter().any(|event| event == event_type))
+            .map(|(webhook, _)| webhook.clone())
+            .collect())
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<Option<Webhook>, StorageError> {
+        let mut webhooks = self.webhooks.write().unwrap();
+        let mut deliveries = self.deliveries.write().unwrap();
+        deliveries.retain(|_, delivery| delivery.webhook_id != id);
+        Ok(webhooks.remove(&id).map(|(webhook, _)| webhook))
+    }
+
+    async fn create_deliveries(&self, deliveries: &[WebhookDelivery]) -> Result<(), StorageError> {
+        let webhooks = self.webhooks.read().unwrap();
+        let mut stored = self.deliveries.write().unwrap();
+        for delivery in deliveries {
+            if webhooks.contains_key(&delivery.webhook_id) {
+                stored.insert(delivery.id, delivery.clone());
+            }
+        }
+        Ok(())
+    }
+
+    async fn claim_due_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, StorageError> {
+        let mut deliveries = self.deliveries.write().unwrap();
+        let mut due: Vec<&mut WebhookDelivery> = deliveries
+            .values_mut()
+            .filter(|delivery| {
+                delivery.status == DeliveryStatus::Pending
+                    && delivery.next_attempt_at.is_some_and(|at| at <= now)
+            })
+            .collect();
+        due.sort_by_key(|delivery| (delivery.next_attempt_at, delivery.id));
+        due.truncate(limit);
+        Ok(due
+            .into_iter()
+            .map(|delivery| {
+                delivery.next_attempt_at = Some(lease_until);
+                delivery.clone()
+            })
+            .collect())
+    }
+
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), StorageError> {
+        if let Some(stored) = self.deliveries.write().unwrap().get_mut(&delivery.id) {
+            *stored = delivery.clone();
+        }
+        Ok(())
+    }
+
+    async fn list_deliveries(
+        &self,
+        query: &DeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>, StorageError> {
+        let deliveries = self.deliveries.read().unwrap();
+        let filter = &query.filter;
+        Ok(page(
+            query,
+            deliveries.values().filter(|delivery| {
+                delivery.webhook_id == filter.webhook_id
+                    && filter.status.is_none_or(|status| delivery.status == status)
+            }),
+            WebhookDelivery::sort_key,
+        ))
+    }
+}
+
+#[async_trait]
+impl AuditStore for MemoryStorage {
+    async fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), StorageError> {
+        self.audit_log.write().unwrap().push(entry.clone());
+        Ok(())
+    }
+
+    async fn list_audit_entries(
+        &self,
+        query: &AuditQuery,
+    ) -> Result<Vec<AuditEntry>, StorageError> {
+        let entries = self.audit_log.read().unwrap();
+        Ok(page(
+            query,
+            entries.iter().filter(|entry| query.filter.matches(entry)),
+            AuditEntry::sort_key,
+        ))
+    }
+}
+
+#[async_trait]
+impl HealthStore for MemoryStorage {
+    async fn check_health(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+//! Versioned schema migrations for the SQLite backend.
+//!
+//! Migrations live in `migrations/` as numbered SQL files and are compiled
+//! into the binary. Each one runs in its own transaction and is recorded in
+//! `schema_migrations`, so applying them is idempotent.
+
+use rusqlite::{Connection, params};
+
+/// A single schema change.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// All known migrations, in the order they must be applied.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_items",
+        sql: include_str!("../../migrations/0001_create_items.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "item_sort_indexes",
+        sql: include_str!("../../migrations/0002_item_sort_indexes.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "create_api_keys",
+        sql: include_str!("../../migrations/0003_create_api_keys.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "create_users",
+        sql: include_str!("../../migrations/0004_create_users.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "item_versions",
+        sql: include_str!("../../migrations/0005_item_versions.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "create_idempotency_keys",
+        sql: include_str!("../../migrations/0006_create_idempotency_keys.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "create_webhooks",
+        sql: include_str!("../../migrations/0007_create_webhooks.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "create_audit_log",
+        sql: include_str!("../../migrations/0008_create_audit_log.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "create_item_search",
+        sql: include_str!("../../migrations/0009_create_item_search.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "item_trash",
+        sql: include_str!("../../migrations/0010_item_trash.sql"),
+    },
+];
+
+/// Applies every migration newer than the database's current version.
+///
+/// Returns the schema version the database ends up at.
+pub fn apply(conn: &mut Connection) -> rusqlite::Result<i64> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version    INTEGER PRIMARY KEY NOT NULL,
+            name       TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        )",
+    )?;
+    let applied = current_version(conn)?;
+    let mut current = applied;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+            params![
+                migration.version,
+                migration.name,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+        tx.commit()?;
+        tracing::info!(
+            version = migration.version,
+            name = migration.name,
+            "applied migration"
+        );
+        current = migration.version;
+    }
+
+    Ok(current)
+}
+
+/// Highest migration version recorded in the database, or 0 for a fresh one.
+pub fn current_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Version the compiled-in migrations bring a database to.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
@@ -0,0 +1,286 @@
+//! Storage abstraction.
+//!
+//! Handlers only ever talk to [`Storage`], so backends can be swapped
+//! without touching the HTTP layer. Each resource gets its own trait and
+//! [`Storage`] ties them together.
+
+mod instrumented;
+mod memory;
+pub mod migrations;
+mod sqlite;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    model::{
+        ApiKey, ApiKeyQuery, AuditEntry, AuditQuery, DeliveryQuery, IdempotencyRecord, Item,
+        ItemInput, ItemQuery, RefreshToken, StoredResponse, User, Webhook, WebhookDelivery,
+        WebhookQuery,
+    },
+    search::{SearchHit, SearchQuery},
+};
+
+pub use instrumented::InstrumentedStorage;
+pub use memory::MemoryStorage;
+pub use sqlite::SqliteStorage;
+
+/// Errors raised by a storage backend.
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    /// A uniqueness or integrity constraint was violated.
+    #[error("{0}")]
+    Conflict(String),
+
+    /// A conditional write found the record at a version its
+    /// [`VersionGuard`] does not accept.
+    #[error("record version does not match")]
+    VersionMismatch,
+
+    #[error("storage backend failure: {0}")]
+    Backend(String),
+}
+
+/// A record as it was before a write and as the write left it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Updated<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// Record versions a conditional write accepts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum VersionGuard {
+    /// Write unconditionally.
+    #[default]
+    Any,
+    /// Write only if the record is currently at one of these versions.
+    OneOf(Vec<i64>),
+}
+
+impl VersionGuard {
+    pub fn admits(&self, version: i64) -> bool {
+        match self {
+            VersionGuard::Any => true,
+            VersionGuard::OneOf(versions) => versions.contains(&version),
+        }
+    }
+}
+
+/// Persistence for [`Item`]s.
+#[async_trait]
+pub trait ItemStore: Send + Sync {
+    /// Returns the items matching `query.filter` that come after
+    /// `query.after`, in the requested order. Backends return up to
+    /// `query.limit + 1` rows so the caller can tell whether more exist.
+    async fn list_items(&self, query: &ItemQuery) -> Result<Vec<Item>, StorageError>;
+
+    /// Returns the item unless it does not exist or is in the trash.
+    async fn get_item(&self, id: Uuid) -> Result<Option<Item>, StorageError>;
+
+    /// Returns the items outside the trash matching every term of `query`,
+    /// most relevant first, skipping `query.offset` of them. Backends return
+    /// up to `query.limit + 1` hits so the caller can tell whether more
+    /// exist.
+    async fn search_items(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, StorageError>;
+
+    async fn create_item(&self, input: ItemInput) -> Result<Item, StorageError>;
+
+    /// Creates an item for every input, returning how many were stored.
+    /// Either all are stored or, if `inputs` yields an error or a write
+    /// fails, none are. `inputs` may block on I/O, so every backend must
+    /// drain it off the async workers, e.g. with `spawn_blocking`.
+    async fn import_items(
+        &self,
+        inputs: Box<dyn Iterator<Item = Result<ItemInput, StorageError>> + Send>,
+    ) -> Result<u64, StorageError>;
+
+    /// Replaces an item's fields and bumps its version, returning `None` if
+    /// it does not exist or is in the trash. Fails with [`StorageError::VersionMismatch`]
+    /// unless `guard` admits the current version; check and write are
+    /// atomic.
+    async fn update_item(
+        &self,
+        id: Uuid,
+        input: ItemInput,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError>;
+
+    /// Moves an item to the trash and bumps its version, returning `None`
+    /// if it does not exist or is there already. `guard` works as for
+    /// [`ItemStore::update_item`].
+    async fn delete_item(
+        &self,
+        id: Uuid,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError>;
+
+    /// Takes an item out of the trash and bumps its version, returning
+    /// `None` unless it is in the trash.
+    async fn restore_item(&self, id: Uuid) -> Result<Option<Updated<Item>>, StorageError>;
+
+    /// Removes the items moved to the trash before `deleted_before` for
+    /// good, returning how many there were.
+    async fn purge_items(&self, deleted_before: DateTime<Utc>) -> Result<u64, StorageError>;
+}
+
+/// Persistence for [`ApiKey`]s, looked up by the hash of their token.
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    async fn create_api_key(&self, key: &ApiKey, token_hash: &str) -> Result<(), StorageError>;
+
+    async fn find_api_key(&self, token_hash: &str) -> Result<Option<ApiKey>, StorageError>;
+
+    /// Same contract as [`ItemStore::list_items`].
+    async fn list_api_keys(&self, query: &ApiKeyQuery) -> Result<Vec<ApiKey>, StorageError>;
+
+    /// Marks a key as revoked, returning `None` if it does not exist.
+    /// Revoking an already revoked key keeps the original timestamp.
+    async fn revoke_api_key(&self, id: Uuid) -> Result<Option<Updated<ApiKey>>, StorageError>;
+}
+
+/// Persistence for [`User`]s and their password hashes.
+#[async_trait]
+pub trait UserStore: Send + Sync {
+    /// Fails with [`StorageError::Conflict`] if the username is taken.
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<(), StorageError>;
+
+    /// Looks a user up by username, returning it with its password hash.
+    async fn find_user(&self, username: &str) -> Result<Option<(User, String)>, StorageError>;
+
+    async fn get_user(&self, id: Uuid) -> Result<Option<User>, StorageError>;
+}
+
+/// Persistence for JWT refresh tokens, looked up by hash.
+#[async_trait]
+pub trait RefreshTokenStore: Send + Sync {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<(), StorageError>;
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, StorageError>;
+
+    /// Revokes one token. Returns `true` only if it existed and was still
+    /// active, so concurrent rotations of the same token cannot both win.
+    async fn revoke_refresh_token(&self, token_hash: &str) -> Result<bool, StorageError>;
+
+    /// Revokes every active token of a user.
+    async fn revoke_user_refresh_tokens(&self, user_id: Uuid) -> Result<(), StorageError>;
+}
+
+/// Persistence for [`IdempotencyRecord`]s. Records past their `expires_at`
+/// count as absent.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Stores `record` unless a live record exists for the same scope and
+    /// key, in which case that one is returned and nothing is written.
+    async fn claim_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, StorageError>;
+
+    /// Attaches the response to a claimed key and moves its expiry.
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StorageError>;
+
+    /// Drops a claim so the request can be retried with the same key.
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), StorageError>;
+}
+
+/// Persistence for [`Webhook`]s and the queue of their deliveries. The
+/// signing secret is stored with the webhook but only returned by
+/// [`WebhookStore::find_webhook`].
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn create_webhook(&self, webhook: &Webhook, secret: &str) -> Result<(), StorageError>;
+
+    /// Looks a webhook up, returning it with its secret.
+    async fn find_webhook(&self, id: Uuid) -> Result<Option<(Webhook, String)>, StorageError>;
+
+    /// Same contract as [`ItemStore::list_items`].
+    async fn list_webhooks(&self, query: &WebhookQuery) -> Result<Vec<Webhook>, StorageError>;
+
+    /// Webhooks subscribed to `event_type`.
+    async fn subscribed_webhooks(&self, event_type: &str) -> Result<Vec<Webhook>, StorageError>;
+
+    /// Removes a webhook and its deliveries, returning it if it existed.
+    async fn delete_webhook(&self, id: Uuid) -> Result<Option<Webhook>, StorageError>;
+
+    /// Queues deliveries. Those for webhooks deleted meanwhile are dropped.
+    async fn create_deliveries(&self, deliveries: &[WebhookDelivery]) -> Result<(), StorageError>;
+
+    /// Returns up to `limit` pending deliveries due at `now`, oldest first,
+    /// and moves their `next_attempt_at` to `lease_until` so no other
+    /// worker picks them up meanwhile.
+    async fn claim_due_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, StorageError>;
+
+    /// Records the outcome of an attempt. Deliveries of deleted webhooks
+    /// are ignored.
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), StorageError>;
+
+    /// Same contract as [`ItemStore::list_items`].
+    async fn list_deliveries(
+        &self,
+        query: &DeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>, StorageError>;
+}
+
+/// The append-only audit log. Entries are never changed or removed.
+#[async_trait]
+pub trait AuditStore: Send + Sync {
+    async fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), StorageError>;
+
+    /// Same contract as [`ItemStore::list_items`].
+    async fn list_audit_entries(&self, query: &AuditQuery)
+    -> Result<Vec<AuditEntry>, StorageError>;
+}
+
+/// Readiness of the backend itself.
+#[async_trait]
+pub trait HealthStore: Send + Sync {
+    /// Fails unless the backend is reachable and its schema is up to date.
+    async fn check_health(&self) -> Result<(), StorageError>;
+}
+
+/// Everything a backend has to provide.
+pub trait Storage:
+    ItemStore
+    + ApiKeyStore
+    + UserStore
+    + RefreshTokenStore
+    + IdempotencyStore
+    + WebhookStore
+    + AuditStore
+    + HealthStore
+{
+}
+
+impl<T> Storage for T where
+    T: ItemStore
+        + ApiKeyStore
+        + UserStore
+        + RefreshTokenStore
+        + IdempotencyStore
+        + WebhookStore
+        + AuditStore
+        + HealthStore
+{
+}
+
+/// Shared handle to the configured backend.
+pub type DynStorage = Arc<dyn Storage>;
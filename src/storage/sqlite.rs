@@ -0,0 +1,1093 @@
+//! Persistent backend on an embedded SQLite database.
+
+use std::{path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{
+    Connection, OptionalExtension, Row, functions::FunctionFlags, params, params_from_iter,
+    types::Value,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use super::{
+    ApiKeyStore, AuditStore, HealthStore, IdempotencyStore, ItemStore, RefreshTokenStore,
+    StorageError, Updated, UserStore, VersionGuard, WebhookStore, migrations,
+};
+use crate::{
+    auth::Scope,
+    model::{
+        ApiKey, ApiKeyQuery, ApiKeySort, AuditEntry, AuditQuery, AuditSort, DeliveryQuery,
+        DeliverySort, DeliveryStatus, IdempotencyRecord, Item, ItemInput, ItemQuery, ItemSort,
+        RefreshToken, StoredResponse, User, Webhook, WebhookDelivery, WebhookQuery, WebhookSort,
+    },
+    pagination::{After, Direction, SortKey},
+    search::{self, Highlights, SearchHit, SearchQuery},
+};
+
+/// SQLite backend. A single connection is shared behind an async mutex;
+/// SQLite serialises writers anyway and the queries involved are short.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the database at `path` and applies pending migrations.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Self::init(conn)
+    }
+
+    /// Opens a private in-memory database, mostly useful for tests.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self, StorageError> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        // SQLite's own `lower()` and `LIKE` only fold ASCII; this folds the
+        // way `MemoryStorage` does, so filters agree across backends.
+        conn.create_scalar_function(
+            "fold_case",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                Ok(ctx
+                    .get::<Option<String>>(0)?
+                    .map(|text| text.to_lowercase()))
+            },
+        )?;
+        migrations::apply(&mut conn)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::ConstraintViolation) => {
+                StorageError::Conflict(err.to_string())
+            }
+            _ => StorageError::Backend(err.to_string()),
+        }
+    }
+}
+
+/// Timestamps are stored as fixed-width RFC 3339 strings so that they sort
+/// lexicographically and round-trip without losing precision.
+fn encode_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn decode_time(idx: usize, raw: String) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&raw)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(
+                idx,
+                rusqlite::types::Type::Text,
+                Box::new(err),
+            )
+        })
+}
+
+fn decode_uuid(idx: usize, raw: String) -> rusqlite::Result<Uuid> {
+    Uuid::parse_str(&raw).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(err))
+    })
+}
+
+/// Accumulates `WHERE` conditions and their parameters for a list query.
+struct Select {
+    conditions: Vec<&'static str>,
+    params: Vec<Value>,
+}
+
+/// `WHERE ... ORDER BY ... LIMIT ...` suffix with positional parameters.
+struct SelectTail {
+    tail: String,
+    params: Vec<Value>,
+}
+
+impl Select {
+    fn new() -> Self {
+        Self {
+            conditions: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, condition: &'static str, param: impl Into<Value>) {
+        self.conditions.push(condition);
+        self.params.push(param.into());
+    }
+
+    /// Adds a condition without parameters.
+    fn condition(&mut self, condition: &'static str) {
+        self.conditions.push(condition);
+    }
+
+    /// Adds keyset pagination on `column` (ties broken by `id`) and renders
+    /// the statement suffix, fetching one row more than `limit`.
+    fn keyset(
+        mut self,
+        column: &str,
+        direction: Direction,
+        after: Option<&After>,
+        limit: usize,
+    ) -> SelectTail {
+        let (cmp, order) = match direction {
+            Direction::Asc => (">", "ASC"),
+            Direction::Desc => ("<", "DESC"),
+        };
+        let mut conditions: Vec<String> = self.conditions.iter().map(|c| c.to_string()).collect();
+        if let Some(after) = after {
+            conditions.push(format!("({column}, id) {cmp} (?, ?)"));
+            self.params.push(sort_key_value(&after.key));
+            self.params.push(after.id.to_string().into());
+        }
+        let mut tail = String::new();
+        if !conditions.is_empty() {
+            tail.push_str("WHERE ");
+            tail.push_str(&conditions.join(" AND "));
+        }
+        tail.push_str(&format!(" ORDER BY {column} {order}, id {order} LIMIT ?"));
+        self.params.push(Value::Integer(limit as i64 + 1));
+        SelectTail {
+            tail,
+            params: self.params,
+        }
+    }
+}
+
+fn sort_key_value(key: &SortKey) -> Value {
+    match key {
+        SortKey::Int(n) => Value::Integer(*n),
+        SortKey::Text(s) => Value::Text(s.clone()),
+        SortKey::Time(t) => Value::Text(encode_time(t)),
+    }
+}
+
+const ITEM_COLUMNS: &str =
+    "id, name, description, quantity, version, created_at, updated_at, deleted_at";
+
+fn item_from_row(row: &Row<'_>) -> rusqlite::Result<Item> {
+    Ok(Item {
+        id: decode_uuid(0, row.get(0)?)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        quantity: row.get(3)?,
+        version: row.get(4)?,
+        created_at: decode_time(5, row.get(5)?)?,
+        updated_at: decode_time(6, row.get(6)?)?,
+        deleted_at: decode_optional_time(7, row.get(7)?)?,
+    })
+}
+
+/// The item `id` if it is outside the trash, or with `trashed` if it is in
+/// there.
+fn select_item(conn: &Connection, id: Uuid, trashed: bool) -> rusqlite::Result<Option<Item>> {
+    let condition = if trashed { "IS NOT NULL" } else { "IS NULL" };
+    conn.query_row(
+        &format!("SELECT {ITEM_COLUMNS} FROM items WHERE id = ?1 AND deleted_at {condition}"),
+        [id.to_string()],
+        item_from_row,
+    )
+    .optional()
+}
+
+/// Writes the trash state of `item` along with the version and timestamp
+/// that change with it.
+fn update_deleted_at(conn: &Connection, item: &Item) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE items SET deleted_at = ?2, version = ?3, updated_at = ?4 WHERE id = ?1",
+        params![
+            item.id.to_string(),
+            item.deleted_at.as_ref().map(encode_time),
+            item.version,
+            encode_time(&item.updated_at),
+        ],
+    )?;
+    Ok(())
+}
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, created_at, revoked_at";
+
+fn api_key_from_row(row: &Row<'_>) -> rusqlite::Result<ApiKey> {
+    Ok(ApiKey {
+        id: decode_uuid(0, row.get(0)?)?,
+        name: row.get(1)?,
+        prefix: row.get(2)?,
+        scopes: decode_scopes(&row.get::<_, String>(3)?),
+        created_at: decode_time(4, row.get(4)?)?,
+        revoked_at: decode_optional_time(5, row.get(5)?)?,
+    })
+}
+
+fn decode_optional_time(
+    idx: usize,
+    raw: Option<String>,
+) -> rusqlite::Result<Option<DateTime<Utc>>> {
+    raw.map(|raw| decode_time(idx, raw)).transpose()
+}
+
+fn encode_scopes(scopes: &[Scope]) -> String {
+    let names: Vec<&str> = scopes.iter().map(|s| s.as_str()).collect();
+    names.join(" ")
+}
+
+fn decode_scopes(raw: &str) -> Vec<Scope> {
+    raw.split_whitespace().filter_map(Scope::parse).collect()
+}
+
+const USER_COLUMNS: &str = "id, username, scopes, created_at, password_hash";
+
+fn user_from_row(row: &Row<'_>) -> rusqlite::Result<(User, String)> {
+    let user = User {
+        id: decode_uuid(0, row.get(0)?)?,
+        username: row.get(1)?,
+        scopes: decode_scopes(&row.get::<_, String>(2)?),
+        created_at: decode_time(3, row.get(3)?)?,
+    };
+    Ok((user, row.get(4)?))
+}
+
+fn refresh_token_from_row(row: &Row<'_>) -> rusqlite::Result<RefreshToken> {
+    Ok(RefreshToken {
+        token_hash: row.get(0)?,
+        user_id: decode_uuid(1, row.get(1)?)?,
+        created_at: decode_time(2, row.get(2)?)?,
+        expires_at: decode_time(3, row.get(3)?)?,
+        revoked_at: decode_optional_time(4, row.get(4)?)?,
+    })
+}
+
+fn idempotency_record_from_row(row: &Row<'_>) -> rusqlite::Result<IdempotencyRecord> {
+    let status: Option<u16> = row.get(3)?;
+    let response = match status {
+        Some(status) => Some(StoredResponse {
+            status,
+            headers: serde_json::from_str(&row.get::<_, String>(4)?).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    4,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+            body: row.get(5)?,
+        }),
+        None => None,
+    };
+    Ok(IdempotencyRecord {
+        scope: row.get(0)?,
+        key: row.get(1)?,
+        fingerprint: row.get(2)?,
+        response,
+        expires_at: decode_time(6, row.get(6)?)?,
+    })
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, events, created_at, secret";
+
+fn webhook_from_row(row: &Row<'_>) -> rusqlite::Result<(Webhook, String)> {
+    let webhook = Webhook {
+        id: decode_uuid(0, row.get(0)?)?,
+        url: row.get(1)?,
+        events: row
+            .get::<_, String>(2)?
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect(),
+        created_at: decode_time(3, row.get(3)?)?,
+    };
+    Ok((webhook, row.get(4)?))
+}
+
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event_type, payload, status, attempts, \
+     next_attempt_at, response_status, error, created_at, updated_at";
+
+const AUDIT_COLUMNS: &str =
+    "id, occurred_at, actor, action, resource_type, resource_id, changes, request_id";
+
+fn audit_entry_from_row(row: &Row<'_>) -> rusqlite::Result<AuditEntry> {
+    Ok(AuditEntry {
+        id: decode_uuid(0, row.get(0)?)?,
+        occurred_at: decode_time(1, row.get(1)?)?,
+        actor: row.get(2)?,
+        action: row.get(3)?,
+        resource_type: row.get(4)?,
+        resource_id: row
+            .get::<_, Option<String>>(5)?
+            .map(|raw| decode_uuid(5, raw))
+            .transpose()?,
+        changes: serde_json::from_str(&row.get::<_, String>(6)?).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(err))
+        })?,
+        request_id: row.get(7)?,
+    })
+}
+
+fn delivery_from_row(row: &Row<'_>) -> rusqlite::Result<WebhookDelivery> {
+    let invalid = |idx, err: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, err)
+    };
+    let status: String = row.get(4)?;
+    Ok(WebhookDelivery {
+        id: decode_uuid(0, row.get(0)?)?,
+        webhook_id: decode_uuid(1, row.get(1)?)?,
+        event_type: row.get(2)?,
+        payload: serde_json::from_str(&row.get::<_, String>(3)?)
+            .map_err(|err| invalid(3, Box::new(err)))?,
+        status: DeliveryStatus::parse(&status)
+            .ok_or_else(|| invalid(4, format!("unknown delivery status `{status}`").into()))?,
+        attempts: row.get(5)?,
+        next_attempt_at: decode_optional_time(6, row.get(6)?)?,
+        response_status: row.get(7)?,
+        error: row.get(8)?,
+        created_at: decode_time(9, row.get(9)?)?,
+        updated_at: decode_time(10, row.get(10)?)?,
+    })
+}
+
+#[async_trait]
+impl ItemStore for SqliteStorage {
+    async fn list_items(&self, query: &ItemQuery) -> Result<Vec<Item>, StorageError> {
+        let mut sql = Select::new();
+        let filter = &query.filter;
+        if !filter.include_deleted {
+            sql.condition("deleted_at IS NULL");
+        }
+        if let Some(name) = &filter.name {
+            sql.push("instr(fold_case(name), ?) > 0", name.to_lowercase());
+        }
+        if let Some(min) = filter.min_quantity {
+            sql.push("quantity >= ?", min);
+        }
+        if let Some(max) = filter.max_quantity {
+            sql.push("quantity <= ?", max);
+        }
+        let column = match query.sort.field {
+            ItemSort::CreatedAt => "created_at",
+            ItemSort::UpdatedAt => "updated_at",
+            ItemSort::Name => "name",
+            ItemSort::Quantity => "quantity",
+        };
+        let sql = sql.keyset(
+            column,
+            query.sort.direction,
+            query.after.as_ref(),
+            query.limit,
+        );
+
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("SELECT {ITEM_COLUMNS} FROM items {}", sql.tail))?;
+        let items = stmt
+            .query_map(params_from_iter(sql.params), item_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items)
+    }
+
+    async fn get_item(&self, id: Uuid) -> Result<Option<Item>, StorageError> {
+        let conn = self.conn.lock().await;
+        Ok(select_item(&conn, id, false)?)
+    }
+
+    async fn search_items(&self, query: &SearchQuery) -> Result<Vec<SearchHit>, StorageError> {
+        let columns = ITEM_COLUMNS
+            .split(", ")
+            .map(|column| format!("items.{column}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "SELECT {columns}, bm25(items_fts, ?2, 1.0) AS rank,
+                    highlight(items_fts, 0, ?3, ?4),
+                    snippet(items_fts, 1, ?3, ?4, ?5, ?6)
+             FROM items_fts JOIN items ON items.seq = items_fts.rowid
+             WHERE items_fts MATCH ?1 AND items.deleted_at IS NULL
+             ORDER BY rank, items.id
+             LIMIT ?7 OFFSET ?8"
+        );
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&sql)?;
+        let hits = stmt
+            .query_map(
+                params![
+                    query.fts5(),
+                    search::NAME_WEIGHT,
+                    search::RAW_MARK_START,
+                    search::RAW_MARK_END,
+                    search::ELLIPSIS,
+                    search::SNIPPET_WORDS as i64,
+                    query.limit as i64 + 1,
+                    query.offset as i64,
+                ],
+                |row| {
+                    let snippet: Option<String> = row.get(10)?;
+                    Ok(SearchHit {
+                        item: item_from_row(row)?,
+                        // FTS5 ranks better matches lower.
+                        score: -row.get::<_, f64>(8)?,
+                        highlights: Highlights {
+                            name: search::escape_marked(&row.get::<_, String>(9)?),
+                            description: snippet
+                                .filter(|snippet| snippet.contains(search::RAW_MARK_START))
+                                .map(|snippet| search::escape_marked(&snippet)),
+                        },
+                    })
+                },
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(hits)
+    }
+
+    async fn create_item(&self, input: ItemInput) -> Result<Item, StorageError> {
+        let item = Item::new(input);
+        let conn = self.conn.lock().await;
+        conn.execute(
+            &format!("INSERT INTO items ({ITEM_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"),
+            params![
+                item.id.to_string(),
+                item.name,
+                item.description,
+                item.quantity,
+                item.version,
+                encode_time(&item.created_at),
+                encode_time(&item.updated_at),
+                item.deleted_at.as_ref().map(encode_time),
+            ],
+        )?;
+        Ok(item)
+    }
+
+    async fn import_items(
+        &self,
+        inputs: Box<dyn Iterator<Item = Result<ItemInput, StorageError>> + Send>,
+    ) -> Result<u64, StorageError> {
+        let mut conn = self.conn.clone().lock_owned().await;
+        // Both reading the inputs and inserting block, possibly for a long
+        // time; the connection stays locked throughout.
+        tokio::task::spawn_blocking(move || {
+            // Dropping the transaction on an early return rolls it back.
+            let tx = conn.transaction()?;
+            let mut count = 0;
+            {
+                let mut insert = tx.prepare(&format!(
+                    "INSERT INTO items ({ITEM_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+                ))?;
+                for input in inputs {
+                    let item = Item::new(input?);
+                    insert.execute(params![
+                        item.id.to_string(),
+                        item.name,
+                        item.description,
+                        item.quantity,
+                        item.version,
+                        encode_time(&item.created_at),
+                        encode_time(&item.updated_at),
+                        item.deleted_at.as_ref().map(encode_time),
+                    ])?;
+                    count += 1;
+                }
+            }
+            tx.commit()?;
+            Ok(count)
+        })
+        .await
+        .map_err(|err| StorageError::Backend(format!("import panicked: {err}")))?
+    }
+
+    async fn update_item(
+        &self,
+        id: Uuid,
+        input: ItemInput,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError> {
+        // The connection lock makes the version check and the write atomic.
+        let conn = self.conn.lock().await;
+        let Some(before) = select_item(&conn, id, false)? else {
+            return Ok(None);
+        };
+        if !guard.admits(before.version) {
+            return Err(StorageError::VersionMismatch);
+        }
+        let mut item = before.clone();
+        item.apply(input);
+        conn.execute(
+            "UPDATE items SET name = ?2, description = ?3, quantity = ?4, version = ?5, updated_at = ?6
+             WHERE id = ?1",
+            params![
+                item.id.to_string(),
+                item.name,
+                item.description,
+                item.quantity,
+                item.version,
+                encode_time(&item.updated_at),
+            ],
+        )?;
+        Ok(Some(Updated {
+            before,
+            after: item,
+        }))
+    }
+
+    async fn delete_item(
+        &self,
+        id: Uuid,
+        guard: &VersionGuard,
+    ) -> Result<Option<Updated<Item>>, StorageError> {
+        let conn = self.conn.lock().await;
+        let Some(before) = select_item(&conn, id, false)? else {
+            return Ok(None);
+        };
+        if !guard.admits(before.version) {
+            return Err(StorageError::VersionMismatch);
+        }
+        let mut item = before.clone();
+        item.set_deleted_at(Some(Utc::now()));
+        update_deleted_at(&conn, &item)?;
+        Ok(Some(Updated {
+            before,
+            after: item,
+        }))
+    }
+
+    async fn restore_item(&self, id: Uuid) -> Result<Option<Updated<Item>>, StorageError> {
+        let conn = self.conn.lock().await;
+        let Some(before) = select_item(&conn, id, true)? else {
+            return Ok(None);
+        };
+        let mut item = before.clone();
+        item.set_deleted_at(None);
+        update_deleted_at(&conn, &item)?;
+        Ok(Some(Updated {
+            before,
+            after: item,
+        }))
+    }
+
+    async fn purge_items(&self, deleted_before: DateTime<Utc>) -> Result<u64, StorageError> {
+        let conn = self.conn.lock().await;
+        let purged = conn.execute(
+            "DELETE FROM items WHERE deleted_at < ?1",
+            [encode_time(&deleted_before)],
+        )?;
+        Ok(purged as u64)
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for SqliteStorage {
+    async fn create_api_key(&self, key: &ApiKey, token_hash: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO api_keys (id, name, prefix, token_hash, scopes, created_at, revoked_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                key.id.to_string(),
+                key.name,
+                key.prefix,
+                token_hash,
+                encode_scopes(&key.scopes),
+                encode_time(&key.created_at),
+                key.revoked_at.as_ref().map(encode_time),
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn find_api_key(&self, token_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        let conn = self.conn.lock().await;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {API_KEY_COLUMNS} FROM api_keys WHERE token_hash = ?1"),
+                [token_hash],
+                api_key_from_row,
+            )
+            .optional()?)
+    }
+
+    async fn list_api_keys(&self, query: &ApiKeyQuery) -> Result<Vec<ApiKey>, StorageError> {
+        let mut sql = Select::new();
+        if !query.filter.include_revoked {
+            sql.condition("revoked_at IS NULL");
+        }
+        let column = match query.sort.field {
+            ApiKeySort::CreatedAt => "created_at",
+        };
+        let sql = sql.keyset(
+            column,
+            query.sort.direction,
+            query.after.as_ref(),
+            query.limit,
+        );
+
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {API_KEY_COLUMNS} FROM api_keys {}",
+            sql.tail
+        ))?;
+        let keys = stmt
+            .query_map(params_from_iter(sql.params), api_key_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(keys)
+    }
+
+    async fn revoke_api_key(&self, id: Uuid) -> Result<Option<Updated<ApiKey>>, StorageError> {
+        let conn = self.conn.lock().await;
+        let select = |conn: &Connection| {
+            conn.query_row(
+                &format!("SELECT {API_KEY_COLUMNS} FROM api_keys WHERE id = ?1"),
+                [id.to_string()],
+                api_key_from_row,
+            )
+            .optional()
+        };
+        let Some(before) = select(&conn)? else {
+            return Ok(None);
+        };
+        conn.execute(
+            "UPDATE api_keys SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL",
+            params![id.to_string(), encode_time(&Utc::now())],
+        )?;
+        let after = select(&conn)?.ok_or_else(|| {
+            StorageError::Backend(format!("api key {id} vanished while being revoked"))
+        })?;
+        Ok(Some(Updated { before, after }))
+    }
+}
+
+#[async_trait]
+impl UserStore for SqliteStorage {
+    async fn create_user(&self, user: &User, password_hash: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO users (id, username, password_hash, scopes, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                user.id.to_string(),
+                user.username,
+                password_hash,
+                encode_scopes(&user.scopes),
+                encode_time(&user.created_at),
+            ],
+        )
+        .map_err(|err| match StorageError::from(err) {
+            StorageError::Conflict(_) => {
+                StorageError::Conflict(format!("username `{}` is taken", user.username))
+            }
+            other => other,
+        })?;
+        Ok(())
+    }
+
+    async fn find_user(&self, username: &str) -> Result<Option<(User, String)>, StorageError> {
+        let conn = self.conn.lock().await;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE username = ?1"),
+                [username],
+                user_from_row,
+            )
+            .optional()?)
+    }
+
+    async fn get_user(&self, id: Uuid) -> Result<Option<User>, StorageError> {
+        let conn = self.conn.lock().await;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {USER_COLUMNS} FROM users WHERE id = ?1"),
+                [id.to_string()],
+                user_from_row,
+            )
+            .optional()?
+            .map(|(user, _)| user))
+    }
+}
+
+#[async_trait]
+impl RefreshTokenStore for SqliteStorage {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO refresh_tokens (token_hash, user_id, created_at, expires_at, revoked_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                token.token_hash,
+                token.user_id.to_string(),
+                encode_time(&token.created_at),
+                encode_time(&token.expires_at),
+                token.revoked_at.as_ref().map(encode_time),
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn find_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, StorageError> {
+        let conn = self.conn.lock().await;
+        Ok(conn
+            .query_row(
+                "SELECT token_hash, user_id, created_at, expires_at, revoked_at
+                 FROM refresh_tokens WHERE token_hash = ?1",
+                [token_hash],
+                refresh_token_from_row,
+            )
+            .optional()?)
+    }
+
+    async fn revoke_refresh_token(&self, token_hash: &str) -> Result<bool, StorageError> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE refresh_tokens SET revoked_at = ?2 WHERE token_hash = ?1 AND revoked_at IS NULL",
+            params![token_hash, encode_time(&Utc::now())],
+        )?;
+        Ok(updated > 0)
+    }
+
+    async fn revoke_user_refresh_tokens(&self, user_id: Uuid) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE refresh_tokens SET revoked_at = ?2 WHERE user_id = ?1 AND revoked_at IS NULL",
+            params![user_id.to_string(), encode_time(&Utc::now())],
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for SqliteStorage {
+    async fn claim_idempotency_key(
+        &self,
+        record: &IdempotencyRecord,
+    ) -> Result<Option<IdempotencyRecord>, StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "DELETE FROM idempotency_keys WHERE expires_at <= ?1",
+            [encode_time(&Utc::now())],
+        )?;
+        let existing = conn
+            .query_row(
+                "SELECT scope, key, fingerprint, status, headers, body, expires_at
+                 FROM idempotency_keys WHERE scope = ?1 AND key = ?2",
+                params![record.scope, record.key],
+                idempotency_record_from_row,
+            )
+            .optional()?;
+        if existing.is_none() {
+            conn.execute(
+                "INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    record.scope,
+                    record.key,
+                    record.fingerprint,
+                    encode_time(&record.expires_at),
+                ],
+            )?;
+        }
+        Ok(existing)
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let headers = serde_json::to_string(&response.headers)
+            .map_err(|err| StorageError::Backend(err.to_string()))?;
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE idempotency_keys SET status = ?3, headers = ?4, body = ?5, expires_at = ?6
+             WHERE scope = ?1 AND key = ?2",
+            params![
+                scope,
+                key,
+                response.status,
+                headers,
+                response.body,
+                encode_time(&expires_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "DELETE FROM idempotency_keys WHERE scope = ?1 AND key = ?2",
+            params![scope, key],
+        )?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookStore for SqliteStorage {
+    async fn create_webhook(&self, webhook: &Webhook, secret: &str) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            &format!("INSERT INTO webhooks ({WEBHOOK_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5)"),
+            params![
+                webhook.id.to_string(),
+                webhook.url,
+                webhook.events.join(" "),
+                encode_time(&webhook.created_at),
+                secret,
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn find_webhook(&self, id: Uuid) -> Result<Option<(Webhook, String)>, StorageError> {
+        let conn = self.conn.lock().await;
+        Ok(conn
+            .query_row(
+                &format!("SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id = ?1"),
+                [id.to_string()],
+                webhook_from_row,
+            )
+            .optional()?)
+    }
+
+    async fn list_webhooks(&self, query: &WebhookQuery) -> Result<Vec<Webhook>, StorageError> {
+        let column = match query.sort.field {
+            WebhookSort::CreatedAt => "created_at",
+        };
+        let sql = Select::new().keyset(
+            column,
+            query.sort.direction,
+            query.after.as_ref(),
+            query.limit,
+        );
+
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {WEBHOOK_COLUMNS} FROM webhooks {}",
+            sql.tail
+        ))?;
+        let webhooks = stmt
+            .query_map(params_from_iter(sql.params), webhook_from_row)?
+            .map(|row| row.map(|(webhook, _)| webhook))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(webhooks)
+    }
+
+    async fn subscribed_webhooks(&self, event_type: &str) -> Result<Vec<Webhook>, StorageError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE ' ' || events || ' ' LIKE ?1"
+        ))?;
+        let webhooks = stmt
+            .query_map([format!("% {event_type} %")], webhook_from_row)?
+            .map(|row| row.map(|(webhook, _)| webhook))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(webhooks)
+    }
+
+    async fn delete_webhook(&self, id: Uuid) -> Result<Option<Webhook>, StorageError> {
+        let conn = self.conn.lock().await;
+        // Deliveries go with it through `ON DELETE CASCADE`.
+        let deleted = conn
+            .query_row(
+                &format!("DELETE FROM webhooks WHERE id = ?1 RETURNING {WEBHOOK_COLUMNS}"),
+                [id.to_string()],
+                webhook_from_row,
+            )
+            .optional()?;
+        Ok(deleted.map(|(webhook, _)| webhook))
+    }
+
+    async fn create_deliveries(&self, deliveries: &[WebhookDelivery]) -> Result<(), StorageError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        for delivery in deliveries {
+            let payload = serde_json::to_string(&delivery.payload)
+                .map_err(|err| StorageError::Backend(err.to_string()))?;
+            tx.execute(
+                &format!(
+                    "INSERT INTO webhook_deliveries ({DELIVERY_COLUMNS})
+                     SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11
+                     WHERE EXISTS (SELECT 1 FROM webhooks WHERE id = ?2)"
+                ),
+                params![
+                    delivery.id.to_string(),
+                    delivery.webhook_id.to_string(),
+                    delivery.event_type,
+                    payload,
+                    delivery.status.as_str(),
+                    delivery.attempts,
+                    delivery.next_attempt_at.as_ref().map(encode_time),
+                    delivery.response_status,
+                    delivery.error,
+                    encode_time(&delivery.created_at),
+                    encode_time(&delivery.updated_at),
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    async fn claim_due_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<WebhookDelivery>, StorageError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "UPDATE webhook_deliveries SET next_attempt_at = ?2
+             WHERE id IN (
+                 SELECT id FROM webhook_deliveries
+                 WHERE status = 'pending' AND next_attempt_at <= ?1
+                 ORDER BY next_attempt_at, id LIMIT ?3
+             )
+             RETURNING {DELIVERY_COLUMNS}"
+        ))?;
+        let deliveries = stmt
+            .query_map(
+                params![encode_time(&now), encode_time(&lease_until), limit as i64],
+                delivery_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(deliveries)
+    }
+
+    async fn update_delivery(&self, delivery: &WebhookDelivery) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE webhook_deliveries
+             SET status = ?2, attempts = ?3, next_attempt_at = ?4, response_status = ?5,
+                 error = ?6, updated_at = ?7
+             WHERE id = ?1",
+            params![
+                delivery.id.to_string(),
+                delivery.status.as_str(),
+                delivery.attempts,
+                delivery.next_attempt_at.as_ref().map(encode_time),
+                delivery.response_status,
+                delivery.error,
+                encode_time(&delivery.updated_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn list_deliveries(
+        &self,
+        query: &DeliveryQuery,
+    ) -> Result<Vec<WebhookDelivery>, StorageError> {
+        let mut sql = Select::new();
+        sql.push("webhook_id = ?", query.filter.webhook_id.to_string());
+        if let Some(status) = query.filter.status {
+            sql.push("status = ?", status.as_str().to_owned());
+        }
+        let column = match query.sort.field {
+            DeliverySort::CreatedAt => "created_at",
+        };
+        let sql = sql.keyset(
+            column,
+            query.sort.direction,
+            query.after.as_ref(),
+            query.limit,
+        );
+
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {DELIVERY_COLUMNS} FROM webhook_deliveries {}",
+            sql.tail
+        ))?;
+        let deliveries = stmt
+            .query_map(params_from_iter(sql.params), delivery_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(deliveries)
+    }
+}
+
+#[async_trait]
+impl AuditStore for SqliteStorage {
+    async fn append_audit_entry(&self, entry: &AuditEntry) -> Result<(), StorageError> {
+        let changes = serde_json::to_string(&entry.changes)
+            .map_err(|err| StorageError::Backend(err.to_string()))?;
+        let conn = self.conn.lock().await;
+        conn.execute(
+            &format!(
+                "INSERT INTO audit_log ({AUDIT_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            ),
+            params![
+                entry.id.to_string(),
+                encode_time(&entry.occurred_at),
+                entry.actor,
+                entry.action,
+                entry.resource_type,
+                entry.resource_id.map(|id| id.to_string()),
+                changes,
+                entry.request_id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn list_audit_entries(
+        &self,
+        query: &AuditQuery,
+    ) -> Result<Vec<AuditEntry>, StorageError> {
+        let filter = &query.filter;
+        let mut sql = Select::new();
+        if let Some(actor) = &filter.actor {
+            sql.push("actor = ?", actor.clone());
+        }
+        if let Some(id) = filter.resource_id {
+            sql.push("resource_id = ?", id.to_string());
+        }
+        if let Some(since) = &filter.since {
+            sql.push("occurred_at >= ?", encode_time(since));
+        }
+        if let Some(until) = &filter.until {
+            sql.push("occurred_at < ?", encode_time(until));
+        }
+        let column = match query.sort.field {
+            AuditSort::OccurredAt => "occurred_at",
+        };
+        let sql = sql.keyset(
+            column,
+            query.sort.direction,
+            query.after.as_ref(),
+            query.limit,
+        );
+
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {AUDIT_COLUMNS} FROM audit_log {}",
+            sql.tail
+        ))?;
+        let entries = stmt
+            .query_map(params_from_iter(sql.params), audit_entry_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl HealthStore for SqliteStorage {
+    async fn check_health(&self) -> Result<(), StorageError> {
+        let conn = self.conn.lock().await;
+        let version = migrations::current_version(&conn)?;
+        let latest = migrations::latest_version();
+        if version < latest {
+            return Err(StorageError::Backend(format!(
+                "schema is at version {version}, expected {latest}"
+            )));
+        }
+        Ok(())
+    }
+}
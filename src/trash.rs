@@ -0,0 +1,60 @@
+//! The trash for deleted items.
+//!
+//! Deleting an item only moves it to the trash: it disappears from reads,
+//! lists, search and exports, but admins can still list it with
+//! `?include_deleted=true` and restore it. [`run`] purges items for good
+//! once they have been in the trash for the retention period.
+
+use std::time::Duration;
+
+use chrono::{TimeDelta, Utc};
+
+use crate::{app::AppState, audit::Audit, storage::StorageError};
+
+/// How long deleted items are kept and how often they are purged.
+#[derive(Debug, Clone)]
+pub struct TrashSettings {
+    /// How long an item stays restorable after it is deleted.
+    pub retention: Duration,
+    /// Wait between two purges.
+    pub purge_interval: Duration,
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+            purge_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Removes the items deleted longer than the retention period ago,
+/// returning how many there were. A purge that removes any is audited.
+pub async fn purge(state: &AppState) -> Result<u64, StorageError> {
+    let retention = TimeDelta::from_std(state.trash.retention).unwrap_or(TimeDelta::MAX);
+    let deleted_before = Utc::now().checked_sub_signed(retention).unwrap_or_default();
+    let count = state.storage.purge_items(deleted_before).await?;
+    if count > 0 {
+        Audit::system().purged(state, "item", count).await;
+    }
+    Ok(count)
+}
+
+/// Purges the trash every `purge_interval` until shutdown begins.
+pub async fn run(state: AppState) {
+    let shutdown = state.readiness.shutdown_begun();
+    tokio::pin!(shutdown);
+
+    loop {
+        match purge(&state).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "purged deleted items"),
+            Err(err) => tracing::error!(%err, "cannot purge deleted items"),
+        }
+        tokio::select! {
+            () = tokio::time::sleep(state.trash.purge_interval) => {}
+            () = &mut shutdown => return,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Field-level validation of request payloads.
+//!
+//! Request types declare their rules with `#[derive(Validate)]` from the
+//! `validator` crate. Both deserialisation failures (missing fields, wrong
+//! types) and rule violations are reported as a list of [`FieldError`]s,
+//! rendered by [`ApiError::Validation`](crate::error::ApiError::Validation)
+//! as a 422 response.
+
+use serde::Serialize;
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::events;
+
+/// One problem with one field of a request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Dotted path to the offending field, e.g. `tags[2].name`.
+    pub field: String,
+    /// Stable reason code: `missing`, `invalid_type`, `invalid_value`,
+    /// `unknown_field`, `too_short`, `too_long`, `out_of_range`, `malformed` or
+    /// `invalid`.
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(
+        field: impl Into<String>,
+        code: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            code: code.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Converts a deserialisation error into a field error, if it concerns a
+/// specific field. Syntax errors and the like return `None`.
+pub fn from_serde(err: &serde_path_to_error::Error<serde_json::Error>) -> Option<FieldError> {
+    let inner = err.inner();
+    if !inner.is_data() {
+        return None;
+    }
+    let path = err.path().to_string();
+    let message = inner.to_string();
+    // serde_json appends " at line X column Y"; clients don't need it.
+    let message = message
+        .rsplit_once(" at line ")
+        .map_or(message.as_str(), |(head, _)| head)
+        .to_owned();
+
+    let join = |name: &str| match path.as_str() {
+        "." => name.to_owned(),
+        parent => format!("{parent}.{name}"),
+    };
+
+    if let Some(name) = backticked(&message, "missing field ") {
+        return Some(FieldError::new(join(name), "missing", "field is required"));
+    }
+    // Unlike missing fields, unknown ones are already part of the path.
+    if message.starts_with("unknown field ") {
+        return Some(FieldError::new(
+            path,
+            "unknown_field",
+            "field is not recognised",
+        ));
+    }
+    let code = if message.starts_with("invalid type") {
+        "invalid_type"
+    } else {
+        "invalid_value"
+    };
+    let field = if path == "." { String::new() } else { path };
+    Some(FieldError::new(field, code, message))
+}
+
+fn backticked<'a>(message: &'a str, prefix: &str) -> Option<&'a str> {
+    message
+        .strip_prefix(prefix)?
+        .strip_prefix('`')?
+        .split('`')
+        .next()
+}
+
+/// Flattens `validator`'s nested error tree into a sorted list.
+pub fn from_validator(errors: &ValidationErrors) -> Vec<FieldError> {
+    let mut out = Vec::new();
+    collect(errors, "", &mut out);
+    out.sort_by(|a, b| a.field.cmp(&b.field).then_with(|| a.code.cmp(&b.code)));
+    out
+}
+
+fn collect(errors: &ValidationErrors, prefix: &str, out: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{prefix}.{field}")
+        };
+        match kind {
+            ValidationErrorsKind::Field(errs) => {
+                out.extend(errs.iter().map(|err| describe(&path, err)));
+            }
+            ValidationErrorsKind::Struct(nested) => collect(nested, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(nested, &format!("{path}[{index}]"), out);
+                }
+            }
+        }
+    }
+}
+
+fn describe(field: &str, err: &ValidationError) -> FieldError {
+    let param = |name: &str| err.params.get(name).and_then(serde_json::Value::as_f64);
+
+    let (code, default_message) = match err.code.as_ref() {
+        "length" => {
+            let len = err
+                .params
+                .get("value")
+                .and_then(|v| v.as_str())
+                .map(|s| s.chars().count() as f64);
+            match (len, param("min"), param("max")) {
+                (Some(len), Some(min), _) if len < min => (
+                    "too_short",
+                    format!("must be at least {min} characters long"),
+                ),
+                (_, _, Some(max)) => ("too_long", format!("must be at most {max} characters long")),
+                _ => ("invalid", "has an invalid length".to_owned()),
+            }
+        }
+        "range" => {
+            let message = match (param("min"), param("max")) {
+                (Some(min), Some(max)) => format!("must be between {min} and {max}"),
+                (Some(min), None) => format!("must be at least {min}"),
+                (None, Some(max)) => format!("must be at most {max}"),
+                (None, None) => "is out of range".to_owned(),
+            };
+            ("out_of_range", message)
+        }
+        other => (other, "is invalid".to_owned()),
+    };
+
+    let message = err
+        .message
+        .as_ref()
+        .map_or(default_message, |m| m.to_string());
+    FieldError::new(field, code, message)
+}
+
+/// Accepts absolute `http` and `https` URLs.
+pub fn http_url(raw: &str) -> Result<(), ValidationError> {
+    match reqwest::Url::parse(raw) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(()),
+        _ => Err(ValidationError::new("invalid_value")
+            .with_message("must be an absolute http or https URL".into())),
+    }
+}
+
+/// Accepts known change event types, see [`events::event_types`].
+pub fn event_types(types: &[String]) -> Result<(), ValidationError> {
+    match types
+        .iter()
+        .find(|name| !events::event_types().any(|known| known == **name))
+    {
+        Some(unknown) => Err(ValidationError::new("invalid_value").with_message(
+            format!(
+                "unknown event type `{unknown}`; expected one of {}",
+                events::event_types().collect::<Vec<_>>().join(", ")
+            )
+            .into(),
+        )),
+        None => Ok(()),
+    }
+}
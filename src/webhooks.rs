@@ -0,0 +1,283 @@
+//! Outbound webhooks.
+//!
+//! Every change published through [`AppState::changed`] is queued as a
+//! [`WebhookDelivery`] for each webhook subscribed to its event type. The
+//! queue lives in storage, so deliveries survive a restart; [`run`] drains
+//! it in the background, retrying failed attempts with exponential backoff.
+//!
+//! Requests carry the change event as JSON and are signed with the
+//! webhook's secret:
+//!
+//! ```text
+//! Webhook-Signature: t=1760000000,v1=<hex HMAC-SHA256 of "1760000000.<body>">
+//! ```
+//!
+//! Receivers should recompute the MAC and reject stale timestamps.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, header::CONTENT_TYPE, redirect::Policy};
+use sha2::Sha256;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::{
+    app::AppState,
+    events::ChangeEvent,
+    model::{DeliveryStatus, WebhookDelivery},
+    storage::{DynStorage, StorageError},
+};
+
+pub const SIGNATURE_HEADER: &str = "webhook-signature";
+pub const EVENT_HEADER: &str = "webhook-event";
+pub const DELIVERY_HEADER: &str = "webhook-id";
+
+/// Prefix of webhook signing secrets.
+pub const SECRET_PREFIX: &str = "whsec_";
+
+/// Longest wait between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Deliveries attempted at once.
+const BATCH: usize = 32;
+
+/// How delivery attempts are made and retried.
+#[derive(Debug, Clone)]
+pub struct WebhookSettings {
+    /// Attempts before a delivery is marked failed.
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled after every further failure.
+    pub retry_backoff: Duration,
+    /// Per-request timeout.
+    pub timeout: Duration,
+    /// How often the queue is checked for retries that became due.
+    pub poll_interval: Duration,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            retry_backoff: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl WebhookSettings {
+    /// Wait after the `attempts`-th failed attempt.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.retry_backoff.saturating_mul(factor).min(MAX_BACKOFF)
+    }
+}
+
+/// Delivery settings plus the signal that wakes [`run`] when new
+/// deliveries are queued.
+#[derive(Debug, Default)]
+pub struct Webhooks {
+    pub settings: WebhookSettings,
+    queued: Notify,
+}
+
+impl Webhooks {
+    pub fn new(settings: WebhookSettings) -> Self {
+        Self {
+            settings,
+            queued: Notify::new(),
+        }
+    }
+}
+
+/// `Webhook-Signature` value for `body` sent at `timestamp` (Unix seconds).
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!(
+        "t={timestamp},v1={}",
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// Queues `event` for every webhook subscribed to its type.
+pub async fn enqueue(state: &AppState, event: &ChangeEvent) -> Result<(), StorageError> {
+    let event_type = event.event_type();
+    let webhooks = state.storage.subscribed_webhooks(&event_type).await?;
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+    let payload = serde_json::to_value(event).expect("change events serialise");
+    let now = Utc::now();
+    let deliveries: Vec<_> = webhooks
+        .iter()
+        .map(|webhook| WebhookDelivery {
+            id: Uuid::now_v7(),
+            webhook_id: webhook.id,
+            event_type: event_type.clone(),
+            payload: payload.clone(),
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            response_status: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        })
+        .collect();
+    state.storage.create_deliveries(&deliveries).await?;
+    state.webhooks.queued.notify_one();
+    Ok(())
+}
+
+/// Delivers queued webhooks until shutdown begins.
+///
+/// Claimed deliveries are leased for a while, so an attempt cut short by a
+/// crash is picked up again once the lease runs out.
+pub async fn run(state: AppState) {
+    let settings = &state.webhooks.settings;
+    let client = Client::builder()
+        .timeout(settings.timeout)
+        .redirect(Policy::none())
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
+        .expect("HTTP client builds");
+    let lease = settings.timeout + Duration::from_secs(60);
+    let shutdown = state.readiness.shutdown_begun();
+    tokio::pin!(shutdown);
+
+    loop {
+        let now = Utc::now();
+        let due = match state
+            .storage
+            .claim_due_deliveries(now, now + lease, BATCH)
+            .await
+        {
+            Ok(due) => due,
+            Err(err) => {
+                tracing::error!(%err, "cannot read webhook queue");
+                Vec::new()
+            }
+        };
+        // A full batch suggests more are due already.
+        let full = due.len() == BATCH;
+        let attempts = join_all(
+            due.into_iter()
+                .map(|delivery| attempt(&state.storage, &client, settings, delivery)),
+        );
+        tokio::select! {
+            _ = attempts => {}
+            () = &mut shutdown => return,
+        }
+        if full {
+            continue;
+        }
+        tokio::select! {
+            () = state.webhooks.queued.notified() => {}
+            () = tokio::time::sleep(settings.poll_interval) => {}
+            () = &mut shutdown => return,
+        }
+    }
+}
+
+/// Makes one attempt and records its outcome.
+async fn attempt(
+    storage: &DynStorage,
+    client: &Client,
+    settings: &WebhookSettings,
+    mut delivery: WebhookDelivery,
+) {
+    let (webhook, secret) = match storage.find_webhook(delivery.webhook_id).await {
+        Ok(Some(found)) => found,
+        // Deleted meanwhile; its deliveries are gone too.
+        Ok(None) => return,
+        Err(err) => {
+            tracing::error!(%err, id = %delivery.id, "cannot load webhook");
+            return;
+        }
+    };
+    let body = serde_json::to_vec(&delivery.payload).expect("payloads serialise");
+    let signature = sign(&secret, Utc::now().timestamp(), &body);
+    let result = client
+        .post(&webhook.url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await;
+
+    let now = Utc::now();
+    delivery.attempts += 1;
+    delivery.updated_at = now;
+    let delivered = match result {
+        Ok(response) => {
+            let status = response.status();
+            delivery.response_status = Some(status.as_u16());
+            delivery.error = (!status.is_success()).then(|| format!("receiver answered {status}"));
+            status.is_success()
+        }
+        Err(err) => {
+            delivery.response_status = None;
+            delivery.error = Some(describe(&err));
+            false
+        }
+    };
+    if delivered {
+        delivery.status = DeliveryStatus::Succeeded;
+        delivery.next_attempt_at = None;
+        tracing::debug!(id = %delivery.id, url = %webhook.url, "delivered webhook");
+    } else if delivery.attempts >= settings.max_attempts {
+        delivery.status = DeliveryStatus::Failed;
+        delivery.next_attempt_at = None;
+        tracing::warn!(
+            id = %delivery.id,
+            url = %webhook.url,
+            attempts = delivery.attempts,
+            error = delivery.error.as_deref(),
+            "giving up on webhook delivery"
+        );
+    } else {
+        let backoff = settings.backoff(delivery.attempts);
+        delivery.next_attempt_at = Some(now + backoff);
+        tracing::info!(
+            id = %delivery.id,
+            url = %webhook.url,
+            attempts = delivery.attempts,
+            ?backoff,
+            error = delivery.error.as_deref(),
+            "webhook delivery failed, will retry"
+        );
+    }
+    if let Err(err) = storage.update_delivery(&delivery).await {
+        tracing::error!(%err, id = %delivery.id, "cannot record webhook delivery");
+    }
+}
+
+/// A short reason for a failed request, without the URL reqwest includes.
+fn describe(err: &reqwest::Error) -> String {
+    let reason = if err.is_timeout() {
+        "timed out"
+    } else if err.is_connect() {
+        "connection failed"
+    } else if err.is_redirect() {
+        "redirects are not followed"
+    } else {
+        "request failed"
+    };
+    match std::error::Error::source(err) {
+        Some(source) => format!("{reason}: {source}"),
+        None => reason.to_owned(),
+    }
+}
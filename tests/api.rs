@@ -0,0 +1,14 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+
+#[tokio::test]
+async fn index_reports_api_version() {
+    let app = common::app().await;
+
+    let (status, json) = common::send(&app, Method::GET, "/api/v1", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["name"], "api-app");
+    assert_eq!(json["api"], "v1");
+}
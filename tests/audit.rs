@@ -0,0 +1,278 @@
+mod common;
+
+use api_app::storage::SqliteStorage;
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+async fn entries(app: &Router, query: &str) -> Vec<Value> {
+    let (status, page) = common::send(
+        app,
+        Method::GET,
+        &format!("/api/v1/admin/audit-log{query}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{page}");
+    page["data"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn item_changes_are_audited() {
+    for (backend, app) in common::backends().await {
+        let (_, headers, item) = common::send_with(
+            &app,
+            Method::POST,
+            "/api/v1/items",
+            &[("x-request-id", "create-widget")],
+            Some(json!({ "name": "widget", "quantity": 1 })),
+        )
+        .await;
+        assert_eq!(headers["x-request-id"], "create-widget", "{backend}");
+        let id = item["id"].as_str().unwrap();
+        let uri = format!("/api/v1/items/{id}");
+        common::send(
+            &app,
+            Method::PUT,
+            &uri,
+            Some(json!({ "name": "gadget", "quantity": 1 })),
+        )
+        .await;
+        common::send_with(
+            &app,
+            Method::PATCH,
+            &uri,
+            &[("content-type", "application/merge-patch+json")],
+            Some(json!({ "quantity": 5 })),
+        )
+        .await;
+        common::send(&app, Method::DELETE, &uri, None).await;
+
+        let log = entries(&app, &format!("?resource_id={id}&sort=occurred_at")).await;
+        let actions: Vec<&str> = log.iter().map(|e| e["action"].as_str().unwrap()).collect();
+        assert_eq!(
+            actions,
+            [
+                "item.created",
+                "item.updated",
+                "item.updated",
+                "item.deleted"
+            ],
+            "{backend}"
+        );
+        assert!(
+            log.iter().all(|e| e["resource_type"] == "item"),
+            "{backend}"
+        );
+        assert!(log.iter().all(|e| e["resource_id"] == id), "{backend}");
+        assert!(
+            log.iter().all(|e| e["actor"] == log[0]["actor"]),
+            "{backend}"
+        );
+        assert!(
+            log[0]["actor"].as_str().unwrap().starts_with("key:"),
+            "{backend}"
+        );
+
+        let created = &log[0];
+        assert_eq!(created["request_id"], "create-widget", "{backend}");
+        assert_eq!(
+            created["changes"]["name"],
+            json!({ "before": null, "after": "widget" }),
+            "{backend}"
+        );
+
+        // Updates list only what changed.
+        let renamed = &log[1]["changes"];
+        assert_eq!(
+            renamed["name"],
+            json!({ "before": "widget", "after": "gadget" }),
+            "{backend}"
+        );
+        assert_eq!(
+            renamed["version"],
+            json!({ "before": 1, "after": 2 }),
+            "{backend}"
+        );
+        assert!(renamed.get("quantity").is_none(), "{backend}");
+        assert!(renamed.get("created_at").is_none(), "{backend}");
+        assert_eq!(
+            log[2]["changes"]["quantity"],
+            json!({ "before": 1, "after": 5 }),
+            "{backend}"
+        );
+
+        let deleted = &log[3]["changes"];
+        assert_eq!(
+            deleted["name"],
+            json!({ "before": "gadget", "after": null }),
+            "{backend}"
+        );
+        assert_eq!(deleted["quantity"]["before"], 5, "{backend}");
+
+        // Failed writes leave no trace.
+        common::send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(
+            entries(&app, &format!("?resource_id={id}")).await.len(),
+            4,
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn admin_changes_are_audited() {
+    let app = common::app().await;
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "ci", "scopes": ["write"] })),
+    )
+    .await;
+    let key_uri = format!("/api/v1/admin/api-keys/{}", issued["id"].as_str().unwrap());
+    common::send(&app, Method::DELETE, &key_uri, None).await;
+    // Revoking again changes nothing.
+    common::send(&app, Method::DELETE, &key_uri, None).await;
+
+    let (_, webhook) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/webhooks",
+        Some(json!({ "url": "http://127.0.0.1:9/hooks", "events": ["item.created"] })),
+    )
+    .await;
+    let webhook_uri = format!("/api/v1/admin/webhooks/{}", webhook["id"].as_str().unwrap());
+    common::send(&app, Method::DELETE, &webhook_uri, None).await;
+
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/users",
+        Some(
+            json!({ "username": "alice", "password": "correct horse battery", "scopes": ["read"] }),
+        ),
+    )
+    .await;
+
+    let log = entries(&app, "?sort=occurred_at").await;
+    let actions: Vec<&str> = log.iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(
+        actions,
+        [
+            "api_key.created",
+            "api_key.updated",
+            "webhook.created",
+            "webhook.deleted",
+            "user.created"
+        ]
+    );
+    assert_eq!(log[1]["changes"]["revoked_at"]["before"], Value::Null);
+    assert!(log[1]["changes"]["revoked_at"]["after"].is_string());
+    assert_eq!(log[4]["changes"]["username"]["after"], "alice");
+
+    // Secrets never make it into the log.
+    let text = serde_json::to_string(&log).unwrap();
+    assert!(!text.contains(issued["token"].as_str().unwrap()));
+    assert!(!text.contains(webhook["secret"].as_str().unwrap()));
+    assert!(!text.contains("correct horse"));
+}
+
+#[tokio::test]
+async fn the_log_can_be_filtered_by_actor_and_time() {
+    let app = common::app().await;
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "writer", "scopes": ["write"] })),
+    )
+    .await;
+    let writer = issued["token"].as_str().unwrap();
+    let (status, _) = common::send_as(
+        &app,
+        Some(writer),
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let actor = format!("key:{}", issued["id"].as_str().unwrap());
+    let by_writer = entries(&app, &format!("?actor={actor}")).await;
+    assert_eq!(by_writer.len(), 1);
+    assert_eq!(by_writer[0]["action"], "item.created");
+
+    let at = by_writer[0]["occurred_at"].as_str().unwrap();
+    let since = entries(&app, &format!("?since={at}")).await;
+    assert_eq!(since.len(), 1);
+    let until = entries(&app, &format!("?until={at}")).await;
+    assert_eq!(until.len(), 1);
+    assert_eq!(until[0]["action"], "api_key.created");
+
+    let (status, body) = common::send(
+        &app,
+        Method::GET,
+        "/api/v1/admin/audit-log?since=yesterday",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+    // Only admins may read the log.
+    let (status, _) = common::send_as(
+        &app,
+        Some(writer),
+        Method::GET,
+        "/api/v1/admin/audit-log",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn imports_are_audited_as_one_entry() {
+    let app = common::app().await;
+    let request = axum::http::Request::post("/api/v1/items/import")
+        .header("authorization", format!("Bearer {}", common::ADMIN_TOKEN))
+        .header("content-type", "text/csv")
+        .body(axum::body::Body::from("name\nwidget\ngadget\n"))
+        .unwrap();
+    let response = tower::ServiceExt::oneshot(app.clone(), request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let log = entries(&app, "").await;
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0]["action"], "item.imported");
+    assert_eq!(log[0]["resource_id"], Value::Null);
+    assert_eq!(log[0]["changes"]["count"]["after"], 2);
+}
+
+#[tokio::test]
+async fn sqlite_refuses_to_rewrite_the_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.db");
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let err = conn
+        .execute("UPDATE audit_log SET actor = 'someone else'", [])
+        .unwrap_err();
+    assert!(err.to_string().contains("append-only"), "{err}");
+    let err = conn.execute("DELETE FROM audit_log", []).unwrap_err();
+    assert!(err.to_string().contains("append-only"), "{err}");
+    assert_eq!(entries(&app, "").await.len(), 1);
+}
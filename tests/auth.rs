@@ -0,0 +1,138 @@
+mod common;
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+async fn issue(app: &Router, scopes: Value) -> (String, String) {
+    let (status, body) = common::send(
+        app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "test", "scopes": scopes })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    (
+        body["id"].as_str().unwrap().to_owned(),
+        body["token"].as_str().unwrap().to_owned(),
+    )
+}
+
+#[tokio::test]
+async fn requests_without_a_token_are_rejected() {
+    let app = common::app().await;
+
+    let (status, body) = common::send_as(&app, None, Method::GET, "/api/v1/items", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "unauthorized");
+
+    let (status, _) =
+        common::send_as(&app, Some("ak_bogus"), Method::GET, "/api/v1/items", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn index_stays_public() {
+    let app = common::app().await;
+
+    let (status, _) = common::send_as(&app, None, Method::GET, "/api/v1", None).await;
+
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn scopes_are_enforced() {
+    let app = common::app().await;
+    let (_, reader) = issue(&app, json!(["read"])).await;
+    let (_, writer) = issue(&app, json!(["read", "write"])).await;
+
+    let (status, _) =
+        common::send_as(&app, Some(&reader), Method::GET, "/api/v1/items", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let item = json!({ "name": "widget" });
+    let (status, body) = common::send_as(
+        &app,
+        Some(&reader),
+        Method::POST,
+        "/api/v1/items",
+        Some(item.clone()),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "forbidden");
+
+    let (status, _) = common::send_as(
+        &app,
+        Some(&writer),
+        Method::POST,
+        "/api/v1/items",
+        Some(item),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = common::send_as(
+        &app,
+        Some(&writer),
+        Method::GET,
+        "/api/v1/admin/api-keys",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn keys_can_be_listed_and_revoked() {
+    for (backend, app) in common::backends().await {
+        let (id, token) = issue(&app, json!(["read"])).await;
+
+        let (_, page) = common::send(&app, Method::GET, "/api/v1/admin/api-keys", None).await;
+        let keys = page["data"].as_array().unwrap();
+        assert_eq!(keys.len(), 2, "{backend}");
+        assert!(keys.iter().all(|k| k.get("token").is_none()), "{backend}");
+        assert!(
+            keys.iter().all(|k| k.get("token_hash").is_none()),
+            "{backend}"
+        );
+
+        let uri = format!("/api/v1/admin/api-keys/{id}");
+        let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+
+        let (status, _) =
+            common::send_as(&app, Some(&token), Method::GET, "/api/v1/items", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{backend}");
+
+        let (_, page) = common::send(&app, Method::GET, "/api/v1/admin/api-keys", None).await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 1, "{backend}");
+        let (_, page) = common::send(
+            &app,
+            Method::GET,
+            "/api/v1/admin/api-keys?include_revoked=true",
+            None,
+        )
+        .await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 2, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn unknown_scope_is_a_validation_error() {
+    let app = common::app().await;
+
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "x", "scopes": ["superuser"] })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["details"][0]["field"], "scopes[0]");
+}
@@ -0,0 +1,302 @@
+mod common;
+
+use api_app::{
+    AppState,
+    events::{FeedMessage, ResourceType},
+    storage::MemoryStorage,
+};
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+/// Uploads `body` to `/items/import` as the admin.
+async fn import(
+    app: &Router,
+    query: &str,
+    content_type: &str,
+    body: String,
+) -> (StatusCode, Value) {
+    let request = Request::post(format!("/api/v1/items/import{query}"))
+        .header(
+            header::AUTHORIZATION,
+            format!("Bearer {}", common::ADMIN_TOKEN),
+        )
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+/// Downloads an export as the admin.
+async fn export(app: &Router, uri: &str, accept: Option<&str>) -> (StatusCode, HeaderMap, String) {
+    let mut request = Request::get(uri).header(
+        header::AUTHORIZATION,
+        format!("Bearer {}", common::ADMIN_TOKEN),
+    );
+    if let Some(accept) = accept {
+        request = request.header(header::ACCEPT, accept);
+    }
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+async fn item_count(app: &Router) -> usize {
+    let (_, body) = common::send(app, Method::GET, "/api/v1/items?limit=200", None).await;
+    body["data"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn exports_span_every_page() {
+    for (backend, app) in common::backends().await {
+        // More rows than fit in one storage page.
+        let rows: String = (0..450)
+            .map(|n| {
+                format!(
+                    "{}\n",
+                    json!({ "name": format!("item {n:03}"), "quantity": n })
+                )
+            })
+            .collect();
+        let (status, report) = import(&app, "", "application/x-ndjson", rows).await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {report}");
+        assert_eq!(report["imported"], 450, "{backend}");
+
+        let (status, headers, body) = export(&app, "/api/v1/items/export", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_TYPE],
+            "application/x-ndjson",
+            "{backend}"
+        );
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"items.ndjson\"",
+            "{backend}"
+        );
+        let items: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(items.len(), 450, "{backend}");
+        assert_eq!(items[0]["name"], "item 000", "{backend}");
+        assert_eq!(items[449]["name"], "item 449", "{backend}");
+
+        // Filters and sort work as for the list endpoint.
+        let (_, _, body) = export(
+            &app,
+            "/api/v1/items/export?min_quantity=440&sort=-quantity",
+            None,
+        )
+        .await;
+        let names: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["name"].clone())
+            .collect();
+        assert_eq!(names.first().unwrap(), "item 449", "{backend}");
+        assert_eq!(names.len(), 10, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn csv_is_chosen_by_accept_or_query() {
+    let app = common::app().await;
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget, large", "description": "says \"hi\"", "quantity": 3 })),
+    )
+    .await;
+
+    let (status, headers, body) = export(&app, "/api/v1/items/export", Some("text/csv")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "text/csv; charset=utf-8");
+    let mut lines = body.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "id,name,description,quantity,version,created_at,updated_at,deleted_at"
+    );
+    let row = lines.next().unwrap();
+    assert!(
+        row.contains(",\"widget, large\",\"says \"\"hi\"\"\",3,1,"),
+        "{row}"
+    );
+    assert_eq!(lines.next(), None);
+
+    let (_, headers, _) =
+        export(&app, "/api/v1/items/export?format=ndjson", Some("text/csv")).await;
+    assert_eq!(headers[header::CONTENT_TYPE], "application/x-ndjson");
+
+    // An empty CSV export still names its columns.
+    let (_, _, body) = export(&app, "/api/v1/items/export?format=csv&name=missing", None).await;
+    assert_eq!(body.lines().count(), 1);
+}
+
+#[tokio::test]
+async fn exports_import_back_unchanged() {
+    let source = common::app().await;
+    for (name, description) in [("widget", Some("small")), ("gadget", None)] {
+        common::send(
+            &source,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": name, "description": description, "quantity": 7 })),
+        )
+        .await;
+    }
+
+    for (format, content_type) in [("ndjson", "application/x-ndjson"), ("csv", "text/csv")] {
+        let (_, _, exported) = export(
+            &source,
+            &format!("/api/v1/items/export?format={format}"),
+            None,
+        )
+        .await;
+        let target = common::app().await;
+        let (status, report) = import(&target, "", content_type, exported).await;
+        assert_eq!(status, StatusCode::OK, "{format}: {report}");
+        assert_eq!(report, json!({ "imported": 2, "failed": 0, "errors": [] }));
+
+        let (_, page) = common::send(&target, Method::GET, "/api/v1/items", None).await;
+        let items = page["data"].as_array().unwrap();
+        assert_eq!(items[0]["name"], "widget", "{format}");
+        assert_eq!(items[0]["description"], "small", "{format}");
+        assert_eq!(items[1]["description"], Value::Null, "{format}");
+        assert_eq!(items[1]["quantity"], 7, "{format}");
+        assert_eq!(items[1]["version"], 1, "{format}");
+    }
+}
+
+#[tokio::test]
+async fn atomic_imports_reject_any_invalid_row() {
+    for (backend, app) in common::backends().await {
+        let rows = [
+            r#"{"name": "widget"}"#,
+            r#"{"name": "", "quantity": -1}"#,
+            "",
+            r#"{"name": "gadget", "colour": "red"}"#,
+            "{not json",
+        ]
+        .join("\n");
+        let (status, report) = import(&app, "", "application/x-ndjson", rows).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert_eq!(report["imported"], 0, "{backend}");
+        assert_eq!(report["failed"], 3, "{backend}");
+        let errors = report["errors"].as_array().unwrap();
+        assert_eq!(errors[0]["line"], 2, "{backend}");
+        assert_eq!(errors[0]["errors"][0]["field"], "name", "{backend}");
+        assert_eq!(errors[0]["errors"][1]["field"], "quantity", "{backend}");
+        assert_eq!(errors[1]["line"], 4, "{backend}");
+        assert_eq!(errors[1]["errors"][0]["code"], "unknown_field", "{backend}");
+        assert_eq!(errors[2]["line"], 5, "{backend}");
+        assert_eq!(errors[2]["errors"][0]["code"], "malformed", "{backend}");
+
+        assert_eq!(item_count(&app).await, 0, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn partial_imports_keep_the_valid_rows() {
+    let app = common::app().await;
+    let rows = "name,quantity\nwidget,1\ngadget,lots\n\"multi\nline\",2\nshort\n".to_owned();
+    let (status, report) = import(&app, "?mode=partial", "text/csv", rows).await;
+    assert_eq!(status, StatusCode::OK, "{report}");
+    assert_eq!(report["imported"], 2);
+    assert_eq!(report["failed"], 2);
+    assert_eq!(report["errors"][0]["line"], 3);
+    assert_eq!(report["errors"][0]["errors"][0]["field"], "quantity");
+    assert_eq!(report["errors"][0]["errors"][0]["code"], "invalid_type");
+    assert_eq!(report["errors"][1]["line"], 6);
+    assert_eq!(report["errors"][1]["errors"][0]["code"], "malformed");
+
+    let (_, page) = common::send(&app, Method::GET, "/api/v1/items", None).await;
+    assert_eq!(page["data"][1]["name"], "multi\nline");
+}
+
+#[tokio::test]
+async fn reports_list_only_the_first_failures() {
+    let app = common::app().await;
+    let rows = "{\"name\": \"\"}\n".repeat(150);
+    let (status, report) = import(&app, "", "application/x-ndjson", rows).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(report["failed"], 150);
+    assert_eq!(report["errors"].as_array().unwrap().len(), 100);
+}
+
+#[tokio::test]
+async fn imports_ask_feed_subscribers_to_resync() {
+    let state = AppState::new(MemoryStorage::new());
+    let mut subscription = state.events.subscribe(None, ResourceType::ALL.to_vec());
+    let app = common::app_from(state).await;
+
+    let (status, _) = import(
+        &app,
+        "",
+        "application/x-ndjson",
+        "{\"name\": \"widget\"}\n".into(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(subscription.next().await, Some(FeedMessage::Resync));
+}
+
+#[tokio::test]
+async fn imports_over_the_size_limit_are_refused() {
+    let row = "{\"name\": \"widget\"}\n";
+    let state = AppState::new(MemoryStorage::new()).with_max_import_size(3 * row.len() as u64);
+    let app = common::app_from(state).await;
+
+    let (status, report) = import(&app, "", "application/x-ndjson", row.repeat(3)).await;
+    assert_eq!(status, StatusCode::OK, "{report}");
+
+    let (status, body) = import(&app, "", "application/x-ndjson", row.repeat(4)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["error"]["code"], "payload_too_large");
+    assert_eq!(item_count(&app).await, 3);
+}
+
+#[tokio::test]
+async fn imports_need_a_known_format_and_write_access() {
+    let app = common::app().await;
+    let (status, body) = import(&app, "", "application/json", "[]".into()).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(body["error"]["code"], "unsupported_media_type");
+
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "reader", "scopes": ["read"] })),
+    )
+    .await;
+    let (status, _, _) = common::send_with(
+        &app,
+        Method::POST,
+        "/api/v1/items/import",
+        &[
+            (
+                "authorization",
+                &format!("Bearer {}", issued["token"].as_str().unwrap()),
+            ),
+            ("content-type", "text/csv"),
+        ],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
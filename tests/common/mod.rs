@@ -0,0 +1,134 @@
+#![allow(dead_code)]
+
+pub mod server;
+
+use api_app::{
+    AppState, auth, router,
+    storage::{MemoryStorage, SqliteStorage, Storage},
+};
+use axum::{
+    Router,
+    body::Body,
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tower::ServiceExt;
+
+/// Admin token every test app is bootstrapped with.
+pub const ADMIN_TOKEN: &str = "ak_test-admin-token";
+
+/// Router over `state` with [`ADMIN_TOKEN`] already registered.
+pub async fn app_from(state: AppState) -> Router {
+    auth::bootstrap_admin(&state, ADMIN_TOKEN).await.unwrap();
+    router(state)
+}
+
+/// Router over `storage` with [`ADMIN_TOKEN`] already registered.
+pub async fn app_with(storage: impl Storage + 'static) -> Router {
+    app_from(AppState::new(storage)).await
+}
+
+/// Router backed by a fresh in-memory store.
+pub async fn app() -> Router {
+    app_with(MemoryStorage::new()).await
+}
+
+/// One router per storage backend, for tests that must hold for all of them.
+pub async fn backends() -> Vec<(&'static str, Router)> {
+    vec![
+        ("memory", app().await),
+        (
+            "sqlite",
+            app_with(SqliteStorage::open_in_memory().unwrap()).await,
+        ),
+    ]
+}
+
+/// Sends a request as the admin with an optional JSON body and returns
+/// status and parsed body.
+pub async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    send_as(app, Some(ADMIN_TOKEN), method, uri, body).await
+}
+
+/// Like [`send`], with an explicit bearer token (or none at all).
+pub async fn send_as(
+    app: &Router,
+    token: Option<&str>,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut headers = Vec::new();
+    let bearer;
+    if let Some(token) = token {
+        bearer = format!("Bearer {token}");
+        headers.push((header::AUTHORIZATION.as_str(), bearer.as_str()));
+    }
+    let (status, _, json) = exchange(app, method, uri, &headers, body).await;
+    (status, json)
+}
+
+/// Like [`send`], with extra request headers, also returning the response
+/// headers. The admin token and `application/json` are used unless the
+/// headers say otherwise.
+pub async fn send_with(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: Option<Value>,
+) -> (StatusCode, HeaderMap, Value) {
+    let bearer = format!("Bearer {ADMIN_TOKEN}");
+    let mut all = Vec::new();
+    if !has_header(headers, header::AUTHORIZATION) {
+        all.push((header::AUTHORIZATION.as_str(), bearer.as_str()));
+    }
+    all.extend_from_slice(headers);
+    exchange(app, method, uri, &all, body).await
+}
+
+fn has_header(headers: &[(&str, &str)], wanted: header::HeaderName) -> bool {
+    headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case(wanted.as_str()))
+}
+
+async fn exchange(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: Option<Value>,
+) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = match body {
+        Some(json) if has_header(headers, header::CONTENT_TYPE) => {
+            request.body(Body::from(json.to_string()))
+        }
+        Some(json) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let json = if bytes.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&bytes).unwrap()
+    };
+    (status, headers, json)
+}
@@ -0,0 +1,96 @@
+//! The whole service on an ephemeral port, spoken to over real HTTP, for
+//! end-to-end tests and benchmarks.
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use api_app::{AppState, router, server, webhooks};
+use reqwest::{Method, StatusCode, header::HeaderMap};
+use serde_json::Value;
+use tokio::{net::TcpListener, sync::oneshot, task::JoinHandle};
+
+use super::ADMIN_TOKEN;
+
+/// A running server, wired up like `main` does it: admin key bootstrapped,
+/// webhook worker started, graceful shutdown on [`TestServer::stop`].
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub client: reqwest::Client,
+    stop: oneshot::Sender<()>,
+    server: JoinHandle<io::Result<()>>,
+    worker: JoinHandle<()>,
+}
+
+impl TestServer {
+    pub async fn start(state: AppState) -> Self {
+        api_app::auth::bootstrap_admin(&state, ADMIN_TOKEN)
+            .await
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let worker = tokio::spawn(webhooks::run(state.clone()));
+        let server = tokio::spawn(server::serve(
+            listener,
+            router(state.clone()),
+            server::drain(state.readiness.clone(), Duration::ZERO, async {
+                stopped.await.ok();
+            }),
+        ));
+        Self {
+            addr,
+            client: reqwest::Client::new(),
+            stop,
+            server,
+            worker,
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr)
+    }
+
+    /// A request to `path` as the admin.
+    pub fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, self.url(path))
+            .bearer_auth(ADMIN_TOKEN)
+    }
+
+    /// Sends a request as the admin with an optional JSON body and returns
+    /// status, headers and parsed body.
+    pub async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, HeaderMap, Value) {
+        let mut request = self.request(method, path);
+        if let Some(json) = body {
+            request = request
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(json.to_string());
+        }
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response.bytes().await.unwrap();
+        let json = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, headers, json)
+    }
+
+    /// Shuts down gracefully and waits until the listener is closed and
+    /// the webhook worker has finished.
+    pub async fn stop(self) {
+        self.stop.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            self.server.await.unwrap().unwrap();
+            self.worker.await.unwrap();
+        })
+        .await
+        .expect("server stops promptly");
+    }
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use api_app::{AppState, storage::MemoryStorage};
+use axum::{
+    Router,
+    http::{Method, StatusCode, header},
+};
+use serde_json::{Value, json};
+
+/// Creates an item and returns its URI and `ETag`.
+async fn create(app: &Router) -> (String, String) {
+    let (status, headers, body) = common::send_with(
+        app,
+        Method::POST,
+        "/api/v1/items",
+        &[],
+        Some(json!({ "name": "widget", "quantity": 1 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let etag = headers[header::ETAG].to_str().unwrap().to_owned();
+    (
+        format!("/api/v1/items/{}", body["id"].as_str().unwrap()),
+        etag,
+    )
+}
+
+fn replacement(quantity: i64) -> Option<Value> {
+    Some(json!({ "name": "widget", "quantity": quantity }))
+}
+
+#[tokio::test]
+async fn items_carry_their_version_as_etag() {
+    for (backend, app) in common::backends().await {
+        let (uri, etag) = create(&app).await;
+        assert_eq!(etag, r#""1""#, "{backend}");
+
+        let (status, headers, body) = common::send_with(&app, Method::GET, &uri, &[], None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(headers[header::ETAG], etag, "{backend}");
+        assert_eq!(body["version"], 1, "{backend}");
+
+        let (status, headers, body) =
+            common::send_with(&app, Method::PUT, &uri, &[], replacement(2)).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(headers[header::ETAG], r#""2""#, "{backend}");
+        assert_eq!(body["version"], 2, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn get_honours_if_none_match() {
+    for (backend, app) in common::backends().await {
+        let (uri, etag) = create(&app).await;
+
+        for tags in [etag.as_str(), r#""7", W/"1""#, "*"] {
+            let (status, headers, body) =
+                common::send_with(&app, Method::GET, &uri, &[("if-none-match", tags)], None).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED, "{backend}: {tags}");
+            assert_eq!(headers[header::ETAG], etag, "{backend}: {tags}");
+            assert_eq!(body, Value::Null, "{backend}: {tags}");
+        }
+
+        let (status, _, body) = common::send_with(
+            &app,
+            Method::GET,
+            &uri,
+            &[("if-none-match", r#""7""#)],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(body["name"], "widget", "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn stale_if_match_is_rejected() {
+    for (backend, app) in common::backends().await {
+        let (uri, etag) = create(&app).await;
+        let (status, _, _) = common::send_with(
+            &app,
+            Method::PUT,
+            &uri,
+            &[("if-match", &etag)],
+            replacement(2),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+
+        // A second writer still holding version 1 loses.
+        let (status, _, body) = common::send_with(
+            &app,
+            Method::PUT,
+            &uri,
+            &[("if-match", &etag)],
+            replacement(3),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{backend}");
+        assert_eq!(body["error"]["code"], "precondition_failed", "{backend}");
+
+        let (status, _, _) =
+            common::send_with(&app, Method::DELETE, &uri, &[("if-match", &etag)], None).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{backend}");
+
+        // Weak tags never match `If-Match`.
+        let (status, _, _) = common::send_with(
+            &app,
+            Method::DELETE,
+            &uri,
+            &[("if-match", r#"W/"2""#)],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED, "{backend}");
+
+        let (_, _, body) = common::send_with(&app, Method::GET, &uri, &[], None).await;
+        assert_eq!(body["quantity"], 2, "{backend}");
+
+        let (status, _, _) = common::send_with(
+            &app,
+            Method::DELETE,
+            &uri,
+            &[("if-match", r#""1", "2""#)],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn if_match_on_a_missing_item_is_not_found() {
+    let app = common::app().await;
+    let (uri, etag) = create(&app).await;
+    common::send_with(&app, Method::DELETE, &uri, &[], None).await;
+
+    let (status, _, _) = common::send_with(
+        &app,
+        Method::PUT,
+        &uri,
+        &[("if-match", &etag)],
+        replacement(2),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn malformed_tags_are_rejected() {
+    let app = common::app().await;
+    let (uri, _) = create(&app).await;
+
+    let (status, _, body) = common::send_with(
+        &app,
+        Method::PUT,
+        &uri,
+        &[("if-match", "1")],
+        replacement(2),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn if_match_can_be_required() {
+    let app =
+        common::app_from(AppState::new(MemoryStorage::new()).with_required_if_match(true)).await;
+    let (uri, etag) = create(&app).await;
+
+    let (status, _, body) = common::send_with(&app, Method::PUT, &uri, &[], replacement(2)).await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+    assert_eq!(body["error"]["code"], "precondition_required");
+
+    let (status, _, _) = common::send_with(&app, Method::DELETE, &uri, &[], None).await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+
+    let (status, _, _) = common::send_with(
+        &app,
+        Method::PUT,
+        &uri,
+        &[("if-match", &etag)],
+        replacement(2),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) =
+        common::send_with(&app, Method::DELETE, &uri, &[("if-match", "*")], None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
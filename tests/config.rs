@@ -0,0 +1,324 @@
+use std::{io::Write, net::SocketAddr, time::Duration};
+
+use api_app::{
+    auth::jwt::SigningAlgorithm,
+    config::{Cli, Config, ConfigError},
+    logging::LogFormat,
+    rate_limit::Quota,
+};
+use clap::Parser;
+
+fn cli(args: &[&str]) -> Cli {
+    Cli::try_parse_from(std::iter::once("api-app").chain(args.iter().copied())).unwrap()
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn config_file(contents: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+    file
+}
+
+fn problems(err: ConfigError) -> Vec<String> {
+    match err {
+        ConfigError::Invalid(problems) => problems,
+        other => panic!("expected validation errors, got {other}"),
+    }
+}
+
+#[test]
+fn defaults_apply_without_any_source() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert_eq!(
+        config.server.addr(),
+        "127.0.0.1:8080".parse::<SocketAddr>().unwrap()
+    );
+    assert_eq!(config.database.path, None);
+    assert_eq!(config.log.level, "info");
+    assert!(!config.auth.jwt.enabled());
+    assert_eq!(config.rate_limits.api, Some(Quota::per_minute(600)));
+}
+
+#[test]
+fn file_env_and_flags_override_in_order() {
+    let file = config_file(
+        r#"
+        [server]
+        bind = "0.0.0.0"
+        port = 7000
+
+        [log]
+        level = "debug"
+
+        [rate_limits]
+        api = "5/s"
+        admin = "off"
+        "#,
+    );
+    let path = file.path().to_str().unwrap();
+
+    let config = Config::load(
+        &cli(&["--config", path, "--port", "9000"]),
+        env(&[
+            ("API_APP_PORT", "8000"),
+            ("API_APP_LOG_LEVEL", "warn"),
+            ("API_APP_LOG_FORMAT", "json"),
+            ("API_APP_TRUSTED_PROXIES", "2"),
+            ("UNRELATED", "ignored"),
+        ]),
+    )
+    .unwrap();
+
+    // File only.
+    assert_eq!(config.server.bind.to_string(), "0.0.0.0");
+    assert_eq!(config.rate_limits.api, "5/s".parse().ok());
+    assert_eq!(config.rate_limits.admin, None);
+    // Env over file.
+    assert_eq!(config.log.level, "warn");
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert_eq!(config.rate_limits.trusted_proxies, 2);
+    // Flag over env and file.
+    assert_eq!(config.server.port, 9000);
+    // Untouched by any layer.
+    assert_eq!(config.rate_limits.auth, Some(Quota::per_minute(10)));
+}
+
+#[test]
+fn jwt_settings_come_from_every_layer() {
+    let file = config_file(
+        r#"
+        [auth.jwt]
+        algorithm = "RS256"
+        private_key = "tests/fixtures/rsa_private.pem"
+        public_key = "tests/fixtures/rsa_public.pem"
+        access_ttl = 300
+        "#,
+    );
+    let config = Config::load(
+        &cli(&[
+            "--config",
+            file.path().to_str().unwrap(),
+            "--jwt-leeway",
+            "5",
+        ]),
+        env(&[("API_APP_JWT_REFRESH_TTL", "3600")]),
+    )
+    .unwrap();
+
+    let jwt = &config.auth.jwt;
+    assert_eq!(jwt.algorithm, SigningAlgorithm::Rs256);
+    assert_eq!(
+        (jwt.access_ttl, jwt.refresh_ttl, jwt.leeway),
+        (300, 3600, 5)
+    );
+    assert!(jwt.build().unwrap().is_some());
+}
+
+#[test]
+fn all_problems_are_reported_together() {
+    let err = Config::load(
+        &cli(&["--jwt-algorithm", "EdDSA", "--jwt-secret", "s3cret"]),
+        env(&[
+            ("API_APP_JWT_ACCESS_TTL", "0"),
+            ("API_APP_BOOTSTRAP_KEY", "no-prefix"),
+        ]),
+    )
+    .unwrap_err();
+
+    let problems = problems(err);
+    for expected in [
+        "auth.jwt.secret is not used with EdDSA",
+        "auth.jwt.private_key is required for EdDSA",
+        "auth.jwt.public_key is required for EdDSA",
+        "auth.jwt token lifetimes must be positive",
+        "auth.bootstrap_key must start with `ak_`",
+    ] {
+        assert!(
+            problems.iter().any(|p| p == expected),
+            "missing `{expected}` in {problems:?}"
+        );
+    }
+}
+
+#[test]
+fn keys_must_match_the_algorithm() {
+    let err = Config::load(
+        &cli(&["--jwt-private-key", "missing.pem"]),
+        env(&[("API_APP_JWT_ALGORITHM", "RS512")]),
+    )
+    .unwrap_err();
+    assert!(problems(err).contains(&"auth.jwt.private_key: missing.pem is not a file".to_owned()));
+
+    let err = Config::load(
+        &cli(&[
+            "--jwt-private-key",
+            "tests/fixtures/rsa_private.pem",
+            "--jwt-public-key",
+            "tests/fixtures/rsa_public.pem",
+        ]),
+        env(&[]),
+    )
+    .unwrap_err();
+    assert!(problems(err).contains(&"auth.jwt.secret is required for HS256".to_owned()));
+}
+
+#[test]
+fn malformed_values_are_rejected() {
+    let err = Config::load(&cli(&[]), env(&[("API_APP_PORT", "eighty")])).unwrap_err();
+    assert_eq!(problems(err), ["API_APP_PORT: `eighty` is not an integer"]);
+
+    let err = Config::load(&cli(&[]), env(&[("API_APP_REQUIRE_IF_MATCH", "maybe")])).unwrap_err();
+    assert_eq!(
+        problems(err),
+        ["API_APP_REQUIRE_IF_MATCH: `maybe` is not a boolean"]
+    );
+
+    let err = Config::load(&cli(&["--rate-limit-api", "lots"]), env(&[])).unwrap_err();
+    assert!(matches!(err, ConfigError::Parse(msg) if msg.contains("invalid quota")));
+
+    let file = config_file("[server]\nhost = \"example.com\"\n");
+    let err =
+        Config::load(&cli(&["--config", file.path().to_str().unwrap()]), env(&[])).unwrap_err();
+    assert!(matches!(err, ConfigError::Parse(msg) if msg.contains("unknown field `host`")));
+
+    let err = Config::load(&cli(&["--config", "does-not-exist.toml"]), env(&[])).unwrap_err();
+    assert!(matches!(err, ConfigError::Read { .. }));
+}
+
+#[test]
+fn idempotency_window_is_configurable() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert_eq!(config.api.idempotency_ttl(), Duration::from_secs(86_400));
+
+    let config = Config::load(
+        &cli(&["--idempotency-ttl", "60"]),
+        env(&[("API_APP_IDEMPOTENCY_TTL", "3600")]),
+    )
+    .unwrap();
+    assert_eq!(config.api.idempotency_ttl(), Duration::from_secs(60));
+
+    let err = Config::load(&cli(&[]), env(&[("API_APP_IDEMPOTENCY_TTL", "0")])).unwrap_err();
+    assert_eq!(problems(err), ["api.idempotency_ttl must be positive"]);
+}
+
+#[test]
+fn require_if_match_is_a_switch() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert!(!config.api.require_if_match);
+
+    let config = Config::load(&cli(&[]), env(&[("API_APP_REQUIRE_IF_MATCH", "true")])).unwrap();
+    assert!(config.api.require_if_match);
+
+    let config = Config::load(&cli(&["--require-if-match"]), env(&[])).unwrap();
+    assert!(config.api.require_if_match);
+
+    let config = Config::load(
+        &cli(&["--require-if-match=false"]),
+        env(&[("API_APP_REQUIRE_IF_MATCH", "1")]),
+    )
+    .unwrap();
+    assert!(!config.api.require_if_match);
+}
+
+#[test]
+fn printed_config_redacts_secrets() {
+    let config = Config::load(
+        &cli(&["--bootstrap-key", "ak_super-secret-token"]),
+        env(&[("API_APP_JWT_SECRET", "hunter2hunter2")]),
+    )
+    .unwrap();
+
+    let printed = config.to_redacted_toml();
+    assert!(!printed.contains("super-secret"), "{printed}");
+    assert!(!printed.contains("hunter2"), "{printed}");
+    assert!(printed.contains(r#"secret = "<redacted>""#), "{printed}");
+    assert!(!format!("{config:?}").contains("hunter2"));
+
+    // The printed form is itself a valid config file.
+    let reparsed: toml::Table = toml::from_str(&printed).unwrap();
+    assert_eq!(reparsed["server"]["port"].as_integer(), Some(8080));
+}
+
+#[test]
+fn webhook_delivery_is_configurable() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    let settings = config.webhooks.settings();
+    assert_eq!(settings.max_attempts, 8);
+    assert_eq!(settings.retry_backoff, Duration::from_secs(30));
+
+    let config = Config::load(
+        &cli(&["--webhook-max-attempts", "3", "--webhook-timeout", "2"]),
+        env(&[
+            ("API_APP_WEBHOOK_MAX_ATTEMPTS", "5"),
+            ("API_APP_WEBHOOK_RETRY_BACKOFF", "10"),
+        ]),
+    )
+    .unwrap();
+    let settings = config.webhooks.settings();
+    assert_eq!(settings.max_attempts, 3);
+    assert_eq!(settings.retry_backoff, Duration::from_secs(10));
+    assert_eq!(settings.timeout, Duration::from_secs(2));
+
+    let err = Config::load(&cli(&["--webhook-max-attempts", "0"]), env(&[])).unwrap_err();
+    assert_eq!(problems(err), ["webhooks settings must be positive"]);
+}
+
+#[test]
+fn compression_threshold_is_configurable() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert_eq!(config.api.compression_min_size, 1024);
+
+    let config = Config::load(
+        &cli(&["--compression-min-size", "256"]),
+        env(&[("API_APP_COMPRESSION_MIN_SIZE", "4096")]),
+    )
+    .unwrap();
+    assert_eq!(config.api.compression_min_size, 256);
+
+    let config = Config::load(&cli(&[]), env(&[("API_APP_COMPRESSION_MIN_SIZE", "4096")])).unwrap();
+    assert_eq!(config.api.compression_min_size, 4096);
+}
+
+#[test]
+fn import_size_limit_is_configurable() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert_eq!(config.api.max_import_size, 100 * 1024 * 1024);
+
+    let config = Config::load(
+        &cli(&["--max-import-size", "1024"]),
+        env(&[("API_APP_MAX_IMPORT_SIZE", "2048")]),
+    )
+    .unwrap();
+    assert_eq!(config.api.max_import_size, 1024);
+
+    let err = Config::load(&cli(&[]), env(&[("API_APP_MAX_IMPORT_SIZE", "0")])).unwrap_err();
+    assert_eq!(problems(err), ["api.max_import_size must be positive"]);
+}
+
+#[test]
+fn trash_retention_is_configurable() {
+    let config = Config::load(&cli(&[]), env(&[])).unwrap();
+    assert_eq!(
+        config.trash.settings().retention.as_secs(),
+        30 * 24 * 60 * 60
+    );
+
+    let config = Config::load(
+        &cli(&["--trash-retention", "3600"]),
+        env(&[
+            ("API_APP_TRASH_RETENTION", "60"),
+            ("API_APP_TRASH_PURGE_INTERVAL", "30"),
+        ]),
+    )
+    .unwrap();
+    assert_eq!(config.trash.retention, 3600);
+    assert_eq!(config.trash.purge_interval, 30);
+
+    let err = Config::load(&cli(&["--trash-purge-interval", "0"]), env(&[])).unwrap_err();
+    assert_eq!(problems(err), ["trash.purge_interval must be positive"]);
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::net::TcpStream;
+
+use api_app::{
+    AppState,
+    storage::{MemoryStorage, SqliteStorage},
+};
+use common::server::TestServer;
+use reqwest::{Method, StatusCode, header};
+use serde_json::json;
+
+fn states() -> Vec<(&'static str, AppState)> {
+    vec![
+        ("memory", AppState::new(MemoryStorage::new())),
+        (
+            "sqlite",
+            AppState::new(SqliteStorage::open_in_memory().unwrap()),
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn items_round_trip_over_http() {
+    for (backend, state) in states() {
+        let server = TestServer::start(state).await;
+
+        let (status, headers, item) = server
+            .send(
+                Method::POST,
+                "/api/v1/items",
+                Some(json!({ "name": "widget", "quantity": 2 })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}: {item}");
+        assert!(headers.contains_key("x-request-id"), "{backend}");
+        let etag = headers[header::ETAG].to_str().unwrap().to_owned();
+        let path = format!("/api/v1/items/{}", item["id"].as_str().unwrap());
+
+        let response = server
+            .request(Method::GET, &path)
+            .header(header::IF_NONE_MATCH, &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{backend}");
+
+        let response = server
+            .request(Method::PATCH, &path)
+            .header(header::IF_MATCH, &etag)
+            .header(header::CONTENT_TYPE, "application/merge-patch+json")
+            .body(json!({ "quantity": 3 }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{backend}");
+
+        // The old ETag no longer matches.
+        let response = server
+            .request(Method::DELETE, &path)
+            .header(header::IF_MATCH, &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::PRECONDITION_FAILED,
+            "{backend}"
+        );
+
+        let (status, _, page) = server.send(Method::GET, "/api/v1/items", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(page["data"][0]["quantity"], 3, "{backend}");
+
+        let (status, _, _) = server.send(Method::DELETE, &path, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let (status, _, body) = server.send(Method::GET, &path, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        assert_eq!(body["error"]["code"], "not_found", "{backend}");
+
+        server.stop().await;
+    }
+}
+
+#[tokio::test]
+async fn requests_without_credentials_are_challenged() {
+    let server = TestServer::start(AppState::new(MemoryStorage::new())).await;
+    let response = server
+        .client
+        .get(server.url("/api/v1/items"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+
+    let response = server
+        .client
+        .get(server.url("/healthz"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn sqlite_data_survives_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.db");
+
+    let server = TestServer::start(AppState::new(SqliteStorage::open(&path).unwrap())).await;
+    let (_, _, item) = server
+        .send(
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": "widget" })),
+        )
+        .await;
+    let addr = server.addr;
+    server.stop().await;
+    assert!(TcpStream::connect(addr).is_err());
+
+    let server = TestServer::start(AppState::new(SqliteStorage::open(&path).unwrap())).await;
+    let (status, _, fetched) = server
+        .send(
+            Method::GET,
+            &format!("/api/v1/items/{}", item["id"].as_str().unwrap()),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fetched, item);
+    server.stop().await;
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn missing_item_uses_error_body() {
+    let app = common::app().await;
+
+    let (status, body) = common::send(
+        &app,
+        Method::GET,
+        "/api/v1/items/0190f0e4-0000-7000-8000-000000000000",
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        body,
+        json!({ "error": { "code": "not_found", "message": "item not found" } })
+    );
+}
+
+#[tokio::test]
+async fn malformed_path_is_bad_request() {
+    let app = common::app().await;
+
+    let (status, body) = common::send(&app, Method::GET, "/api/v1/items/not-a-uuid", None).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn unknown_route_and_method_use_error_body() {
+    let app = common::app().await;
+
+    let (status, body) = common::send(&app, Method::GET, "/api/v1/nope", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"]["code"], "not_found");
+
+    let (status, body) = common::send(&app, Method::PATCH, "/api/v1/items", None).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(body["error"]["code"], "method_not_allowed");
+}
+
+#[tokio::test]
+async fn missing_content_type_is_unsupported_media_type() {
+    let app = common::app().await;
+
+    let (status, body) = common::send(&app, Method::POST, "/api/v1/items", None).await;
+
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(body["error"]["code"], "unsupported_media_type");
+}
@@ -0,0 +1,368 @@
+mod common;
+
+use std::time::Duration;
+
+use api_app::{AppState, storage::MemoryStorage};
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest};
+use tower::ServiceExt;
+
+/// One parsed Server-Sent Event.
+#[derive(Debug)]
+struct Sse {
+    id: Option<String>,
+    event: String,
+    data: Value,
+}
+
+/// An open `text/event-stream` response.
+struct EventStream {
+    body: Body,
+    buffer: String,
+}
+
+impl EventStream {
+    async fn open(app: &Router, token: &str, uri: &str, last_event_id: Option<&str>) -> Self {
+        let mut request =
+            Request::get(uri).header(header::AUTHORIZATION, format!("Bearer {token}"));
+        if let Some(id) = last_event_id {
+            request = request.header("last-event-id", id);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        Self {
+            body: response.into_body(),
+            buffer: String::new(),
+        }
+    }
+
+    /// The next event, skipping keep-alive comments; `None` once the stream
+    /// ends.
+    async fn next(&mut self) -> Option<Sse> {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                let mut event = Sse {
+                    id: None,
+                    event: "message".into(),
+                    data: Value::Null,
+                };
+                let mut seen = false;
+                for line in block.lines() {
+                    let Some((field, value)) = line.split_once(':') else {
+                        continue;
+                    };
+                    let value = value.strip_prefix(' ').unwrap_or(value);
+                    match field {
+                        "id" => event.id = Some(value.to_owned()),
+                        "event" => event.event = value.to_owned(),
+                        "data" => event.data = serde_json::from_str(value).unwrap(),
+                        _ => continue,
+                    }
+                    seen = true;
+                }
+                if seen {
+                    return Some(event);
+                }
+                continue;
+            }
+            let frame = tokio::time::timeout(Duration::from_secs(5), self.body.frame())
+                .await
+                .expect("an event within five seconds")?
+                .unwrap();
+            if let Ok(data) = frame.into_data() {
+                self.buffer.push_str(std::str::from_utf8(&data).unwrap());
+            }
+        }
+    }
+
+    /// Asserts nothing arrives for a moment.
+    async fn assert_quiet(&mut self) {
+        let pending = tokio::time::timeout(Duration::from_millis(100), self.next()).await;
+        assert!(pending.is_err(), "unexpected event {pending:?}");
+    }
+}
+
+async fn create_item(app: &Router, name: &str) -> String {
+    let (status, body) = common::send(
+        app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": name })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    body["id"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn item_changes_are_streamed() {
+    for (backend, app) in common::backends().await {
+        let mut events = EventStream::open(&app, common::ADMIN_TOKEN, "/api/v1/events", None).await;
+
+        let id = create_item(&app, "widget").await;
+        let uri = format!("/api/v1/items/{id}");
+        common::send(&app, Method::PUT, &uri, Some(json!({ "name": "gadget" }))).await;
+        common::send(&app, Method::DELETE, &uri, None).await;
+
+        let created = events.next().await.unwrap();
+        assert_eq!(created.event, "item.created", "{backend}");
+        assert_eq!(created.data["resource"], "item", "{backend}");
+        assert_eq!(created.data["action"], "created", "{backend}");
+        assert_eq!(created.data["resource_id"], id.as_str(), "{backend}");
+        assert_eq!(created.data["data"]["name"], "widget", "{backend}");
+        assert_eq!(
+            created.id.as_deref(),
+            Some(created.data["id"].to_string().as_str()),
+            "{backend}"
+        );
+
+        let updated = events.next().await.unwrap();
+        assert_eq!(updated.event, "item.updated", "{backend}");
+        assert_eq!(updated.data["data"]["name"], "gadget", "{backend}");
+        assert_eq!(updated.data["data"]["version"], 2, "{backend}");
+
+        let deleted = events.next().await.unwrap();
+        assert_eq!(deleted.event, "item.deleted", "{backend}");
+        assert_eq!(deleted.data["data"], Value::Null, "{backend}");
+
+        let ids: Vec<u64> = [&created, &updated, &deleted]
+            .iter()
+            .map(|event| event.data["id"].as_u64().unwrap())
+            .collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]), "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn failed_writes_publish_nothing() {
+    let app = common::app().await;
+    let mut events = EventStream::open(&app, common::ADMIN_TOKEN, "/api/v1/events", None).await;
+
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "" })),
+    )
+    .await;
+    let missing = format!("/api/v1/items/{}", uuid::Uuid::now_v7());
+    common::send(&app, Method::DELETE, &missing, None).await;
+
+    events.assert_quiet().await;
+}
+
+#[tokio::test]
+async fn reconnecting_clients_resume_after_the_last_seen_event() {
+    let app = common::app().await;
+    let mut events = EventStream::open(&app, common::ADMIN_TOKEN, "/api/v1/events", None).await;
+    create_item(&app, "first").await;
+    let first = events.next().await.unwrap();
+    drop(events);
+
+    // Changes made while disconnected.
+    create_item(&app, "second").await;
+    create_item(&app, "third").await;
+
+    let mut events = EventStream::open(
+        &app,
+        common::ADMIN_TOKEN,
+        "/api/v1/events",
+        first.id.as_deref(),
+    )
+    .await;
+    assert_eq!(events.next().await.unwrap().data["data"]["name"], "second");
+    assert_eq!(events.next().await.unwrap().data["data"]["name"], "third");
+    events.assert_quiet().await;
+
+    // The query parameter works too, for clients that cannot set headers.
+    let uri = format!("/api/v1/events?last_event_id={}", first.data["id"]);
+    let mut events = EventStream::open(&app, common::ADMIN_TOKEN, &uri, None).await;
+    assert_eq!(events.next().await.unwrap().data["data"]["name"], "second");
+}
+
+#[tokio::test]
+async fn unknown_positions_ask_for_a_resync() {
+    let app = common::app().await;
+    create_item(&app, "widget").await;
+
+    let mut events =
+        EventStream::open(&app, common::ADMIN_TOKEN, "/api/v1/events", Some("1")).await;
+    let resync = events.next().await.unwrap();
+    assert_eq!(resync.event, "resync");
+    assert_eq!(resync.id, None);
+
+    // Live events still follow.
+    create_item(&app, "gadget").await;
+    assert_eq!(events.next().await.unwrap().data["data"]["name"], "gadget");
+}
+
+#[tokio::test]
+async fn feeds_can_be_filtered_by_resource_type() {
+    let app = common::app().await;
+    let mut keys = EventStream::open(
+        &app,
+        common::ADMIN_TOKEN,
+        "/api/v1/events?types=api_key",
+        None,
+    )
+    .await;
+
+    create_item(&app, "widget").await;
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "reader", "scopes": ["read"] })),
+    )
+    .await;
+
+    let event = keys.next().await.unwrap();
+    assert_eq!(event.event, "api_key.created");
+    assert_eq!(event.data["data"]["name"], "reader");
+    assert!(event.data["data"].get("token").is_none());
+
+    // Readers only see what they may read.
+    let reader = issued["token"].as_str().unwrap();
+    let (status, body) = common::send_as(
+        &app,
+        Some(reader),
+        Method::GET,
+        "/api/v1/events?types=item,api_key",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "forbidden");
+
+    let mut items = EventStream::open(&app, reader, "/api/v1/events", None).await;
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "other", "scopes": ["read"] })),
+    )
+    .await;
+    create_item(&app, "gadget").await;
+    assert_eq!(items.next().await.unwrap().event, "item.created");
+
+    let (status, _) = common::send(&app, Method::GET, "/api/v1/events?types=widget", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn streams_end_when_shutdown_begins() {
+    let state = AppState::new(MemoryStorage::new());
+    let readiness = state.readiness.clone();
+    let app = common::app_from(state).await;
+    let mut events = EventStream::open(&app, common::ADMIN_TOKEN, "/api/v1/events", None).await;
+
+    readiness.begin_shutdown();
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test(start_paused = true)]
+async fn streams_end_when_their_key_is_revoked() {
+    let app = common::app().await;
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "reader", "scopes": ["read"] })),
+    )
+    .await;
+    let reader = issued["token"].as_str().unwrap();
+    let mut events = EventStream::open(&app, reader, "/api/v1/events", None).await;
+
+    create_item(&app, "widget").await;
+    assert_eq!(events.next().await.unwrap().event, "item.created");
+
+    let uri = format!("/api/v1/admin/api-keys/{}", issued["id"].as_str().unwrap());
+    let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // Revocation is noticed at the next periodic check.
+    tokio::time::advance(Duration::from_secs(31)).await;
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn changes_are_pushed_over_websocket() {
+    let state = AppState::new(MemoryStorage::new());
+    let readiness = state.readiness.clone();
+    let app = common::app_from(state).await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, app.clone()).into_future());
+
+    let mut request = format!("ws://{addr}/api/v1/events/ws?types=item")
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        header::AUTHORIZATION,
+        format!("Bearer {}", common::ADMIN_TOKEN).parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    let id = create_item(&app, "widget").await;
+    let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let message: Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+    assert_eq!(message["type"], "change");
+    assert_eq!(message["action"], "created");
+    assert_eq!(message["resource_id"], id.as_str());
+
+    // Clients may talk; the server ignores it.
+    socket
+        .send(tungstenite::Message::text("hello"))
+        .await
+        .unwrap();
+
+    readiness.begin_shutdown();
+    let close = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    match close {
+        tungstenite::Message::Close(Some(frame)) => assert_eq!(u16::from(frame.code), 1001),
+        other => panic!("expected a close frame, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn websocket_requires_credentials() {
+    let app = common::app().await;
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::serve(listener, app).into_future());
+
+    let err = tokio_tungstenite::connect_async(format!("ws://{addr}/api/v1/events/ws"))
+        .await
+        .unwrap_err();
+    match err {
+        tungstenite::Error::Http(response) => {
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED)
+        }
+        other => panic!("expected an HTTP error, got {other}"),
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use api_app::{
+    AppState, router, server,
+    storage::{MemoryStorage, SqliteStorage},
+};
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use tokio::{net::TcpListener, sync::oneshot};
+
+#[tokio::test]
+async fn probes_pass_on_a_healthy_service() {
+    for (backend, app) in common::backends().await {
+        let (status, body) = common::send_as(&app, None, Method::GET, "/healthz", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(body, json!({ "status": "ok" }), "{backend}");
+
+        let (status, body) = common::send_as(&app, None, Method::GET, "/readyz", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            body,
+            json!({ "status": "ready", "checks": { "storage": "ok", "lifecycle": "ok" } }),
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn readiness_fails_once_shutdown_begins() {
+    let state = AppState::new(MemoryStorage::new());
+    let readiness = state.readiness.clone();
+    let app = common::app_from(state).await;
+
+    readiness.begin_shutdown();
+
+    let (status, body) = common::send_as(&app, None, Method::GET, "/readyz", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "unavailable");
+    assert_eq!(body["checks"]["lifecycle"], "shutting_down");
+
+    let (status, _) = common::send_as(&app, None, Method::GET, "/healthz", None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn readiness_fails_with_pending_migrations() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("app.db");
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute(
+            "DELETE FROM schema_migrations WHERE version = (SELECT MAX(version) FROM schema_migrations)",
+            [],
+        )
+        .unwrap();
+
+    let (status, body) = common::send_as(&app, None, Method::GET, "/readyz", None).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["checks"]["storage"], "fail");
+    assert_eq!(body["checks"]["lifecycle"], "ok");
+}
+
+/// Status code of `GET path` over a fresh connection.
+async fn status_over_tcp(addr: SocketAddr, path: &'static str) -> u16 {
+    tokio::task::spawn_blocking(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response.split(' ').nth(1).unwrap().parse().unwrap()
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn server_drains_before_closing_the_listener() {
+    let state = AppState::new(MemoryStorage::new());
+    let readiness = state.readiness.clone();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(server::serve(
+        listener,
+        router(state),
+        server::drain(readiness.clone(), Duration::from_millis(500), async {
+            stopped.await.ok();
+        }),
+    ));
+
+    assert_eq!(status_over_tcp(addr, "/readyz").await, 200);
+    stop.send(()).unwrap();
+    while !readiness.is_shutting_down() {
+        tokio::task::yield_now().await;
+    }
+
+    // Still serving while draining, but no longer ready.
+    assert_eq!(status_over_tcp(addr, "/readyz").await, 503);
+    assert_eq!(status_over_tcp(addr, "/healthz").await, 200);
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server stops after the drain delay")
+        .unwrap()
+        .unwrap();
+    assert!(TcpStream::connect(addr).is_err());
+}
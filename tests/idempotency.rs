@@ -0,0 +1,273 @@
+mod common;
+
+use std::time::Duration;
+
+use api_app::{
+    AppState,
+    model::{IdempotencyRecord, StoredResponse},
+    storage::{IdempotencyStore, MemoryStorage, SqliteStorage},
+};
+use axum::{
+    Router,
+    http::{HeaderMap, Method, StatusCode},
+};
+use chrono::Utc;
+use serde_json::{Value, json};
+
+const ITEMS: &str = "/api/v1/items";
+
+async fn post(app: &Router, key: &str, body: Value) -> (StatusCode, HeaderMap, Value) {
+    common::send_with(
+        app,
+        Method::POST,
+        ITEMS,
+        &[("idempotency-key", key)],
+        Some(body),
+    )
+    .await
+}
+
+async fn item_count(app: &Router) -> usize {
+    let (_, page) = common::send(app, Method::GET, ITEMS, None).await;
+    page["data"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn repeated_requests_replay_the_first_response() {
+    for (backend, app) in common::backends().await {
+        let body = json!({ "name": "widget", "quantity": 1 });
+        let (status, headers, first) = post(&app, "order-1", body.clone()).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert!(headers.get("idempotent-replayed").is_none(), "{backend}");
+
+        let (status, headers, again) = post(&app, "order-1", body).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(again, first, "{backend}");
+        assert_eq!(headers["idempotent-replayed"], "true", "{backend}");
+        assert_eq!(headers["etag"], r#""1""#, "{backend}");
+        assert_eq!(headers["content-type"], "application/json", "{backend}");
+        assert_eq!(item_count(&app).await, 1, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn reusing_a_key_for_another_request_is_rejected() {
+    for (backend, app) in common::backends().await {
+        post(&app, "order-1", json!({ "name": "widget" })).await;
+
+        let (status, _, body) = post(&app, "order-1", json!({ "name": "gadget" })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert_eq!(body["error"]["code"], "idempotency_key_reused", "{backend}");
+
+        // The target is part of the request too.
+        let (_, _, created) = post(&app, "order-2", json!({ "name": "widget" })).await;
+        let uri = format!("{ITEMS}/{}", created["id"].as_str().unwrap());
+        let (status, _, _) = common::send_with(
+            &app,
+            Method::DELETE,
+            &uri,
+            &[("idempotency-key", "order-1")],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert_eq!(item_count(&app).await, 2, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn client_errors_are_replayed_too() {
+    let app = common::app().await;
+    let (status, _, first) = post(&app, "bad", json!({ "name": "" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, headers, again) = post(&app, "bad", json!({ "name": "" })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(again, first);
+    assert_eq!(headers["idempotent-replayed"], "true");
+}
+
+#[tokio::test]
+async fn deletes_are_replayed_without_running_again() {
+    let app = common::app().await;
+    let (_, _, created) = post(&app, "create", json!({ "name": "widget" })).await;
+    let uri = format!("{ITEMS}/{}", created["id"].as_str().unwrap());
+
+    for _ in 0..2 {
+        let (status, _, _) = common::send_with(
+            &app,
+            Method::DELETE,
+            &uri,
+            &[("idempotency-key", "delete")],
+            None,
+        )
+        .await;
+        // Without the key, the second attempt would be a 404.
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+}
+
+#[tokio::test]
+async fn admin_writes_never_replay_secrets() {
+    let app = common::app().await;
+    let keys = "/api/v1/admin/api-keys";
+    let (_, before) = common::send(&app, Method::GET, keys, None).await;
+
+    let issue = || {
+        common::send_with(
+            &app,
+            Method::POST,
+            keys,
+            &[("idempotency-key", "issue-ci")],
+            Some(json!({ "name": "ci", "scopes": ["read"] })),
+        )
+    };
+    let (status, _, issued) = issue().await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(issued["token"].is_string());
+
+    let (status, _, repeat) = issue().await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(repeat["error"]["code"], "conflict");
+    assert!(repeat.get("token").is_none());
+
+    let (_, after) = common::send(&app, Method::GET, keys, None).await;
+    assert_eq!(
+        after["data"].as_array().unwrap().len(),
+        before["data"].as_array().unwrap().len() + 1
+    );
+}
+
+#[tokio::test]
+async fn requests_without_a_key_are_not_deduplicated() {
+    let app = common::app().await;
+    for _ in 0..2 {
+        let (status, headers, _) = common::send_with(
+            &app,
+            Method::POST,
+            ITEMS,
+            &[],
+            Some(json!({ "name": "widget" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(headers.get("idempotent-replayed").is_none());
+    }
+    assert_eq!(item_count(&app).await, 2);
+}
+
+#[tokio::test]
+async fn keys_are_scoped_to_the_caller() {
+    let app = common::app().await;
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "mobile", "scopes": ["read", "write"] })),
+    )
+    .await;
+    let bearer = format!("Bearer {}", issued["token"].as_str().unwrap());
+
+    let body = json!({ "name": "widget" });
+    post(&app, "shared", body.clone()).await;
+    let (status, headers, _) = common::send_with(
+        &app,
+        Method::POST,
+        ITEMS,
+        &[("idempotency-key", "shared"), ("authorization", &bearer)],
+        Some(body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(headers.get("idempotent-replayed").is_none());
+    assert_eq!(item_count(&app).await, 2);
+}
+
+#[tokio::test]
+async fn responses_are_forgotten_after_the_window() {
+    let state = AppState::new(MemoryStorage::new()).with_idempotency_ttl(Duration::ZERO);
+    let app = common::app_from(state).await;
+    let body = json!({ "name": "widget" });
+
+    post(&app, "order-1", body.clone()).await;
+    let (status, headers, _) = post(&app, "order-1", body).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(headers.get("idempotent-replayed").is_none());
+    assert_eq!(item_count(&app).await, 2);
+}
+
+#[tokio::test]
+async fn malformed_keys_are_rejected() {
+    let app = common::app().await;
+    let long = "k".repeat(256);
+    for key in ["", "has space", long.as_str()] {
+        let (status, _, body) = post(&app, key, json!({ "name": "widget" })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{key:?}");
+        assert_eq!(body["error"]["code"], "bad_request");
+    }
+    assert_eq!(item_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn claims_are_exclusive_until_released_or_expired() {
+    let backends: Vec<(&str, Box<dyn IdempotencyStore>)> = vec![
+        ("memory", Box::new(MemoryStorage::new())),
+        ("sqlite", Box::new(SqliteStorage::open_in_memory().unwrap())),
+    ];
+    for (backend, store) in backends {
+        let claim = IdempotencyRecord {
+            scope: "key:1".into(),
+            key: "k".into(),
+            fingerprint: "f".into(),
+            response: None,
+            expires_at: Utc::now() + chrono::Duration::minutes(1),
+        };
+        assert_eq!(store.claim_idempotency_key(&claim).await.unwrap(), None);
+        assert_eq!(
+            store.claim_idempotency_key(&claim).await.unwrap(),
+            Some(claim.clone()),
+            "{backend}"
+        );
+
+        let response = StoredResponse {
+            status: 201,
+            headers: vec![("etag".into(), r#""1""#.into())],
+            body: b"{}".to_vec(),
+        };
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        store
+            .complete_idempotency_key("key:1", "k", &response, expires_at)
+            .await
+            .unwrap();
+        let stored = store.claim_idempotency_key(&claim).await.unwrap().unwrap();
+        assert_eq!(stored.response, Some(response), "{backend}");
+        assert_eq!(stored.expires_at, expires_at, "{backend}");
+
+        // Same key, other caller.
+        let other = IdempotencyRecord {
+            scope: "key:2".into(),
+            ..claim.clone()
+        };
+        assert_eq!(store.claim_idempotency_key(&other).await.unwrap(), None);
+
+        store.release_idempotency_key("key:1", "k").await.unwrap();
+        assert_eq!(store.claim_idempotency_key(&claim).await.unwrap(), None);
+
+        let stale = IdempotencyRecord {
+            key: "stale".into(),
+            expires_at: Utc::now() - chrono::Duration::seconds(1),
+            ..claim.clone()
+        };
+        assert_eq!(store.claim_idempotency_key(&stale).await.unwrap(), None);
+        // Expired claims count as absent.
+        let fresh = IdempotencyRecord {
+            key: "stale".into(),
+            ..claim.clone()
+        };
+        assert_eq!(
+            store.claim_idempotency_key(&fresh).await.unwrap(),
+            None,
+            "{backend}"
+        );
+    }
+}
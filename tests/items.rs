@@ -0,0 +1,72 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn item_lifecycle() {
+    let app = common::app().await;
+
+    let (status, created) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget", "quantity": 3 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["name"], "widget");
+    assert_eq!(created["quantity"], 3);
+    let uri = format!("/api/v1/items/{}", created["id"].as_str().unwrap());
+
+    let (status, fetched) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fetched, created);
+
+    let (status, listed) = common::send(&app, Method::GET, "/api/v1/items", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed["data"].as_array().unwrap().len(), 1);
+
+    let (status, updated) = common::send(
+        &app,
+        Method::PUT,
+        &uri,
+        Some(json!({ "name": "gadget", "description": "renamed", "quantity": 5 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["name"], "gadget");
+    assert_eq!(updated["description"], "renamed");
+    assert_eq!(updated["created_at"], created["created_at"]);
+
+    let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn list_is_ordered_by_creation() {
+    let app = common::app().await;
+    for name in ["a", "b", "c"] {
+        common::send(
+            &app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": name })),
+        )
+        .await;
+    }
+
+    let (_, listed) = common::send(&app, Method::GET, "/api/v1/items", None).await;
+    let names: Vec<_> = listed["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["name"].clone())
+        .collect();
+    assert_eq!(names, ["a", "b", "c"]);
+}
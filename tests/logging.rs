@@ -0,0 +1,108 @@
+mod common;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use api_app::logging::{self, LogFormat};
+use axum::{
+    Router,
+    body::Body,
+    http::{Request, Response, header},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+async fn get(app: &Router, uri: &str, request_id: Option<&str>) -> Response<Body> {
+    let mut request = Request::get(uri).header(
+        header::AUTHORIZATION,
+        format!("Bearer {}", common::ADMIN_TOKEN),
+    );
+    if let Some(id) = request_id {
+        request = request.header("x-request-id", id);
+    }
+    app.clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+fn request_id(response: &Response<Body>) -> &str {
+    response.headers()["x-request-id"].to_str().unwrap()
+}
+
+#[tokio::test]
+async fn request_ids_are_generated() {
+    let app = common::app().await;
+    let first = get(&app, "/api/v1/items", None).await;
+    let second = get(&app, "/api/v1/nowhere", None).await;
+
+    let first = request_id(&first);
+    Uuid::parse_str(first).unwrap();
+    assert_ne!(first, request_id(&second));
+}
+
+#[tokio::test]
+async fn client_request_ids_are_propagated() {
+    let app = common::app().await;
+    let response = get(&app, "/api/v1/items", Some("client-trace-42")).await;
+    assert_eq!(request_id(&response), "client-trace-42");
+}
+
+/// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Buffer {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Buffer {
+    fn lines(&self) -> Vec<Value> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn json_logs_describe_each_request() {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let _guard =
+        tracing::subscriber::set_default(logging::subscriber("info", LogFormat::Json, move || {
+            writer.clone()
+        }));
+
+    let app = common::app().await;
+    let uri = format!("/api/v1/items/{}", Uuid::now_v7());
+    let response = get(&app, &uri, Some("abc-123")).await;
+    assert_eq!(response.status(), 404);
+
+    let lines = buffer.lines();
+    let completed = lines
+        .iter()
+        .find(|line| line["message"] == "request completed")
+        .unwrap_or_else(|| panic!("no completion line in {lines:?}"));
+    assert_eq!(completed["level"], "INFO");
+    assert_eq!(completed["status"], 404);
+    assert!(completed["latency_ms"].as_f64().unwrap() >= 0.0);
+
+    let span = &completed["span"];
+    assert_eq!(span["name"], "request");
+    assert_eq!(span["method"], "GET");
+    assert_eq!(span["path"], uri);
+    assert_eq!(span["route"], "/api/v1/items/{id}");
+    assert_eq!(span["request_id"], "abc-123");
+}
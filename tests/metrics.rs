@@ -0,0 +1,130 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use http_body_util::BodyExt;
+use serde_json::json;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+async fn scrape(app: &Router) -> String {
+    let response = app
+        .clone()
+        .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain")
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+/// Value of the sample whose name and labels start with `prefix`.
+fn sample(metrics: &str, prefix: &str) -> f64 {
+    metrics
+        .lines()
+        .find(|line| line.starts_with(prefix))
+        .and_then(|line| line.rsplit(' ').next())
+        .unwrap_or_else(|| panic!("no sample `{prefix}` in\n{metrics}"))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route_template_and_status() {
+    for (backend, app) in common::backends().await {
+        for _ in 0..2 {
+            let uri = format!("/api/v1/items/{}", Uuid::now_v7());
+            let (status, _) = common::send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        }
+        common::send(
+            &app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": "widget" })),
+        )
+        .await;
+        common::send(&app, Method::GET, "/nowhere/at/all", None).await;
+
+        let metrics = scrape(&app).await;
+        assert_eq!(
+            sample(
+                &metrics,
+                r#"http_requests_total{method="GET",route="/api/v1/items/{id}",status="404"}"#
+            ),
+            2.0,
+            "{backend}"
+        );
+        assert_eq!(
+            sample(
+                &metrics,
+                r#"http_requests_total{method="POST",route="/api/v1/items",status="201"}"#
+            ),
+            1.0,
+            "{backend}"
+        );
+        assert_eq!(
+            sample(
+                &metrics,
+                r#"http_requests_total{method="GET",route="unmatched",status="404"}"#
+            ),
+            1.0,
+            "{backend}"
+        );
+        assert_eq!(
+            sample(
+                &metrics,
+                r#"http_request_duration_seconds_count{method="GET",route="/api/v1/items/{id}",status="404"}"#
+            ),
+            2.0,
+            "{backend}"
+        );
+        // The scrape itself is in flight while being rendered.
+        assert_eq!(
+            sample(&metrics, "http_requests_in_flight "),
+            1.0,
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn storage_operations_are_timed() {
+    for (backend, app) in common::backends().await {
+        common::send(&app, Method::GET, "/api/v1/items", None).await;
+        common::send(
+            &app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": "widget" })),
+        )
+        .await;
+
+        let metrics = scrape(&app).await;
+        for operation in ["list_items", "create_item", "find_api_key"] {
+            let prefix = format!(
+                r#"storage_operation_duration_seconds_count{{operation="{operation}",outcome="ok"}}"#
+            );
+            assert!(sample(&metrics, &prefix) >= 1.0, "{backend}: {operation}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn apps_do_not_share_metrics() {
+    let first = common::app().await;
+    let second = common::app().await;
+    common::send(&first, Method::GET, "/api/v1/items", None).await;
+
+    let metrics = scrape(&second).await;
+    assert!(!metrics.contains(r#"route="/api/v1/items""#), "{metrics}");
+}
@@ -0,0 +1,394 @@
+mod common;
+
+use std::io::Read;
+
+use api_app::{AppState, storage::MemoryStorage};
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use http_body_util::BodyExt;
+use serde_json::{Value, json};
+use tower::ServiceExt;
+
+/// Sends a request as the admin and returns the raw response.
+async fn fetch(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+    body: Option<Value>,
+) -> (StatusCode, HeaderMap, Bytes) {
+    let mut request = Request::builder().method(method).uri(uri).header(
+        header::AUTHORIZATION,
+        format!("Bearer {}", common::ADMIN_TOKEN),
+    );
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = match body {
+        Some(json) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(json.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, headers, bytes)
+}
+
+async fn create_items(app: &Router, count: usize) -> Vec<Value> {
+    let mut items = Vec::new();
+    for n in 0..count {
+        let (_, item) = common::send(
+            app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": format!("item {n}"), "description": "x".repeat(50), "quantity": n })),
+        )
+        .await;
+        items.push(item);
+    }
+    items
+}
+
+#[tokio::test]
+async fn json_responses_are_available_as_messagepack() {
+    let app = common::app().await;
+    create_items(&app, 2).await;
+    let (_, page) = common::send(&app, Method::GET, "/api/v1/items", None).await;
+
+    for accept in [
+        "application/msgpack",
+        "application/x-msgpack",
+        "application/json;q=0.5, application/vnd.msgpack",
+    ] {
+        let (status, headers, body) = fetch(
+            &app,
+            Method::GET,
+            "/api/v1/items",
+            &[("accept", accept)],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{accept}");
+        assert_eq!(headers[header::CONTENT_TYPE], "application/msgpack");
+        assert!(
+            headers
+                .get_all(header::VARY)
+                .iter()
+                .any(|value| value == "accept")
+        );
+        let decoded: Value = rmp_serde::from_slice(&body).unwrap();
+        assert_eq!(decoded, page, "{accept}");
+    }
+
+    // Writes and errors are translated too.
+    let (status, headers, body) = fetch(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        &[("accept", "application/msgpack")],
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(headers.contains_key(header::ETAG));
+    let created: Value = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(created["name"], "widget");
+
+    let (status, _, body) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items/00000000-0000-0000-0000-000000000000",
+        &[("accept", "application/msgpack")],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let error: Value = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(error["error"]["code"], "not_found");
+
+    // Without a preference, JSON it is.
+    let (_, headers, _) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items",
+        &[("accept", "*/*")],
+        None,
+    )
+    .await;
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+}
+
+#[tokio::test]
+async fn items_are_available_as_csv() {
+    for (backend, app) in common::backends().await {
+        let items = create_items(&app, 3).await;
+
+        let (status, headers, body) = fetch(
+            &app,
+            Method::GET,
+            "/api/v1/items?limit=2&sort=-quantity",
+            &[("accept", "text/csv, application/json;q=0.9")],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8",
+            "{backend}"
+        );
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines.len(), 3, "{backend}: {body}");
+        assert!(lines[0].starts_with("id,name,"), "{backend}");
+        assert!(lines[1].contains(",item 2,"), "{backend}");
+
+        // The cursor travels in `Link`, keeping the other parameters.
+        let link = headers[header::LINK].to_str().unwrap();
+        let next = link
+            .strip_prefix('<')
+            .and_then(|rest| rest.strip_suffix(">; rel=\"next\""))
+            .unwrap();
+        assert!(
+            next.starts_with("/api/v1/items?limit=2&sort=-quantity&cursor="),
+            "{next}"
+        );
+        let (_, headers, body) =
+            fetch(&app, Method::GET, next, &[("accept", "text/csv")], None).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(body.lines().count(), 2, "{backend}");
+        assert!(body.contains(",item 0,"), "{backend}");
+        assert!(!headers.contains_key(header::LINK), "{backend}");
+
+        let id = items[0]["id"].as_str().unwrap();
+        let (status, headers, body) = fetch(
+            &app,
+            Method::GET,
+            &format!("/api/v1/items/{id}"),
+            &[("accept", "text/csv")],
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(headers[header::ETAG], "\"1-csv\"", "{backend}");
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.lines().nth(1).unwrap().starts_with(id), "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn etags_are_specific_to_the_representation() {
+    let app =
+        common::app_from(AppState::new(MemoryStorage::new()).with_compression_min_size(1)).await;
+    let id = create_items(&app, 1).await[0]["id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+    let uri = format!("/api/v1/items/{id}");
+
+    for (accept, encoding, etag) in [
+        ("application/json", "identity", r#""1""#),
+        ("application/msgpack", "identity", r#""1-msgpack""#),
+        ("text/csv", "identity", r#""1-csv""#),
+        ("application/json", "gzip", r#""1-gzip""#),
+        ("application/msgpack", "br", r#""1-msgpack-br""#),
+    ] {
+        let representation = [("accept", accept), ("accept-encoding", encoding)];
+        let (status, headers, _) = fetch(&app, Method::GET, &uri, &representation, None).await;
+        assert_eq!(status, StatusCode::OK, "{accept} {encoding}");
+        assert_eq!(headers[header::ETAG], etag, "{accept} {encoding}");
+
+        let conditional = [representation.as_slice(), &[("if-none-match", etag)]].concat();
+        let (status, headers, body) = fetch(&app, Method::GET, &uri, &conditional, None).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED, "{accept} {encoding}");
+        assert_eq!(headers[header::ETAG], etag, "{accept} {encoding}");
+        assert!(body.is_empty(), "{accept} {encoding}");
+    }
+
+    // Another representation's tag does not validate this one.
+    let (status, _, _) = fetch(
+        &app,
+        Method::GET,
+        &uri,
+        &[
+            ("accept", "application/msgpack"),
+            ("if-none-match", r#""1""#),
+        ],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A copy held without content coding is answered before the body is
+    // built, even when this response would have been compressed.
+    let (status, headers, _) = fetch(
+        &app,
+        Method::GET,
+        &uri,
+        &[("accept-encoding", "gzip"), ("if-none-match", r#""1""#)],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers[header::ETAG], r#""1""#);
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+
+    // Writes accept the tag of any representation.
+    let (status, headers, _) = fetch(
+        &app,
+        Method::PUT,
+        &uri,
+        &[
+            ("accept-encoding", "identity"),
+            ("if-match", r#""1-msgpack-br""#),
+        ],
+        Some(json!({ "name": "renamed" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::ETAG], r#""2""#);
+}
+
+#[tokio::test]
+async fn unsupported_types_are_not_acceptable() {
+    let app = common::app().await;
+
+    let (status, _, body) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items",
+        &[("accept", "application/xml")],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+    let error: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error["error"]["code"], "not_acceptable");
+
+    // Refused writes are not carried out.
+    let (status, _, _) = fetch(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        &[("accept", "text/csv")],
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+    let (_, page) = common::send(&app, Method::GET, "/api/v1/items", None).await;
+    assert_eq!(page["data"], json!([]));
+
+    // Each resource has only the representations that make sense for it.
+    for (uri, accept) in [
+        ("/api/v1/admin/api-keys", "text/csv"),
+        ("/api/v1/items/export", "application/json"),
+    ] {
+        let (status, _, _) = fetch(&app, Method::GET, uri, &[("accept", accept)], None).await;
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE, "{uri}");
+    }
+    let (status, _, _) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items",
+        &[("accept", "text/csv;q=0, application/json;q=0")],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+
+    // Errors are still reported rather than turned into a 406.
+    let (status, body) =
+        common::send_as(&app, None, Method::GET, "/api/v1/admin/api-keys", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{body}");
+}
+
+#[tokio::test]
+async fn large_responses_are_compressed() {
+    let app = common::app().await;
+    create_items(&app, 20).await;
+
+    let (status, headers, body) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items",
+        &[("accept-encoding", "gzip")],
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+    assert!(
+        headers
+            .get_all(header::VARY)
+            .iter()
+            .any(|value| value == "accept-encoding")
+    );
+    let mut json = String::new();
+    flate2::read::GzDecoder::new(&body[..])
+        .read_to_string(&mut json)
+        .unwrap();
+    let page: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(page["data"].as_array().unwrap().len(), 20);
+
+    for encoding in ["br", "zstd"] {
+        let (_, headers, _) = fetch(
+            &app,
+            Method::GET,
+            "/api/v1/items",
+            &[("accept-encoding", encoding)],
+            None,
+        )
+        .await;
+        assert_eq!(headers[header::CONTENT_ENCODING], encoding);
+    }
+
+    // Small responses are not worth it.
+    let (_, headers, _) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items?limit=1",
+        &[("accept-encoding", "gzip")],
+        None,
+    )
+    .await;
+    assert!(!headers.contains_key(header::CONTENT_ENCODING));
+}
+
+#[tokio::test]
+async fn compression_threshold_is_configurable_and_spares_event_streams() {
+    let app =
+        common::app_from(AppState::new(MemoryStorage::new()).with_compression_min_size(1)).await;
+    let (_, headers, _) = fetch(
+        &app,
+        Method::GET,
+        "/api/v1/items?limit=1",
+        &[("accept-encoding", "gzip")],
+        None,
+    )
+    .await;
+    assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+
+    // Event streams would be held back in the encoder.
+    let request = Request::get("/api/v1/events")
+        .header(
+            header::AUTHORIZATION,
+            format!("Bearer {}", common::ADMIN_TOKEN),
+        )
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use api_app::{
+    AppState,
+    auth::jwt::{Jwt, JwtSettings, KeyMaterial, SigningAlgorithm},
+    storage::MemoryStorage,
+};
+use axum::http::{Method, StatusCode};
+use serde_json::Value;
+
+/// The committed document client SDKs are generated from.
+const SPEC_FILE: &str = "openapi.json";
+
+/// The spec as served by an app with every optional feature enabled.
+async fn served_spec() -> Value {
+    let jwt = Jwt::new(
+        SigningAlgorithm::Hs256,
+        KeyMaterial::Secret(b"test-secret".to_vec()),
+        JwtSettings::default(),
+    )
+    .unwrap();
+    let app = common::app_from(AppState::new(MemoryStorage::new()).with_jwt(jwt)).await;
+    let (status, spec) = common::send_as(&app, None, Method::GET, "/api/openapi.json", None).await;
+    assert_eq!(status, StatusCode::OK);
+    spec
+}
+
+/// Fails when the committed spec no longer matches the code. Run with
+/// `UPDATE_OPENAPI=1` to rewrite it after an intentional API change.
+#[tokio::test]
+async fn committed_spec_is_up_to_date() {
+    let spec = served_spec().await;
+    let rendered = serde_json::to_string_pretty(&spec).unwrap() + "\n";
+
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(SPEC_FILE, rendered).unwrap();
+        return;
+    }
+    let committed = std::fs::read_to_string(SPEC_FILE).unwrap_or_default();
+    assert!(
+        committed == rendered,
+        "{SPEC_FILE} is out of date; rerun with UPDATE_OPENAPI=1 and commit the result"
+    );
+}
+
+#[tokio::test]
+async fn spec_describes_every_operation() {
+    let spec = served_spec().await;
+    assert_eq!(spec["openapi"], "3.1.0");
+
+    let operations: Vec<String> = spec["paths"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(path, item)| {
+            item.as_object()
+                .unwrap()
+                .keys()
+                .map(move |method| format!("{} {path}", method.to_uppercase()))
+        })
+        .collect();
+    for expected in [
+        "GET /api/v1/items",
+        "POST /api/v1/items",
+        "GET /api/v1/items/{id}",
+        "PUT /api/v1/items/{id}",
+        "DELETE /api/v1/items/{id}",
+        "GET /api/v1/admin/api-keys",
+        "POST /api/v1/admin/api-keys",
+        "DELETE /api/v1/admin/api-keys/{id}",
+        "POST /api/v1/admin/users",
+        "POST /api/v1/auth/login",
+        "POST /api/v1/auth/refresh",
+        "POST /api/v1/auth/logout",
+    ] {
+        assert!(
+            operations.contains(&expected.to_owned()),
+            "{expected} missing from {operations:?}"
+        );
+    }
+
+    let create = &spec["paths"]["/api/v1/items"]["post"];
+    assert_eq!(create["security"][0]["bearer"][0], "write");
+    assert_eq!(
+        create["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/ItemInput"
+    );
+    for status in ["201", "400", "401", "403", "415", "422", "429"] {
+        assert!(create["responses"][status].is_object(), "{status}");
+    }
+    assert_eq!(
+        spec["components"]["schemas"]["ItemInput"]["properties"]["name"]["maxLength"],
+        200
+    );
+}
+
+#[tokio::test]
+async fn session_routes_are_only_documented_when_enabled() {
+    let app = common::app().await;
+    let (_, spec) = common::send_as(&app, None, Method::GET, "/api/openapi.json", None).await;
+    assert!(spec["paths"]["/api/v1/items"].is_object());
+    assert!(spec["paths"]["/api/v1/auth/login"].is_null());
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+async fn seed(app: &Router) {
+    for (name, quantity) in [
+        ("pear", 4),
+        ("apple", 9),
+        ("fig", 1),
+        ("Banana", 9),
+        ("cherry", 0),
+    ] {
+        let (status, _) = common::send(
+            app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": name, "quantity": quantity })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+}
+
+fn names(page: &Value) -> Vec<String> {
+    page["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["name"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+/// Follows `next_cursor` until exhausted, returning every name in order.
+async fn walk(app: &Router, query: &str) -> Vec<String> {
+    let mut all = Vec::new();
+    let mut uri = format!("/api/v1/items?{query}");
+    loop {
+        let (status, page) = common::send(app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{page}");
+        all.extend(names(&page));
+        match page["next_cursor"].as_str() {
+            Some(cursor) => uri = format!("/api/v1/items?{query}&cursor={cursor}"),
+            None => return all,
+        }
+    }
+}
+
+#[tokio::test]
+async fn pages_follow_the_requested_sort() {
+    for (backend, app) in common::backends().await {
+        seed(&app).await;
+
+        assert_eq!(
+            walk(&app, "limit=2").await,
+            ["pear", "apple", "fig", "Banana", "cherry"],
+            "{backend}"
+        );
+        assert_eq!(
+            walk(&app, "limit=2&sort=name").await,
+            ["Banana", "apple", "cherry", "fig", "pear"],
+            "{backend}"
+        );
+        assert_eq!(
+            walk(&app, "limit=2&sort=-quantity").await[..2],
+            ["Banana", "apple"],
+            "{backend}"
+        );
+        assert_eq!(
+            walk(&app, "limit=3&sort=quantity:desc").await.len(),
+            5,
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn filters_combine() {
+    for (backend, app) in common::backends().await {
+        seed(&app).await;
+
+        assert_eq!(
+            walk(&app, "sort=name&name=AN").await,
+            ["Banana"],
+            "{backend}"
+        );
+        assert_eq!(
+            walk(&app, "sort=name&min_quantity=1&max_quantity=4").await,
+            ["fig", "pear"],
+            "{backend}"
+        );
+
+        // Case is ignored beyond ASCII too.
+        common::send(
+            &app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": "Äpfel", "quantity": 9 })),
+        )
+        .await;
+        for needle in ["%C3%A4pf", "%C3%84PF"] {
+            assert_eq!(
+                walk(&app, &format!("name={needle}")).await,
+                ["Äpfel"],
+                "{backend}: {needle}"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn last_page_has_no_cursor() {
+    let app = common::app().await;
+    seed(&app).await;
+
+    let (_, page) = common::send(&app, Method::GET, "/api/v1/items?limit=5", None).await;
+
+    assert_eq!(names(&page).len(), 5);
+    assert_eq!(page["next_cursor"], Value::Null);
+}
+
+#[tokio::test]
+async fn invalid_paging_parameters_are_rejected() {
+    let app = common::app().await;
+    seed(&app).await;
+
+    let (status, body) = common::send(
+        &app,
+        Method::GET,
+        "/api/v1/items?limit=0&sort=colour&cursor=garbage",
+        None,
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<_> = body["error"]["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["limit", "sort", "cursor"]);
+}
+
+#[tokio::test]
+async fn cursor_is_bound_to_its_sort_order() {
+    let app = common::app().await;
+    seed(&app).await;
+
+    let (_, page) = common::send(&app, Method::GET, "/api/v1/items?limit=1&sort=name", None).await;
+    let cursor = page["next_cursor"].as_str().unwrap();
+
+    let uri = format!("/api/v1/items?sort=quantity&cursor={cursor}");
+    let (status, _) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use axum::{
+    Router,
+    http::{Method, StatusCode, header},
+};
+use serde_json::{Value, json};
+
+const MERGE: &str = "application/merge-patch+json";
+const JSON_PATCH: &str = "application/json-patch+json";
+
+async fn create(app: &Router) -> String {
+    let (status, body) = common::send(
+        app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget", "description": "blue", "quantity": 3 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    format!("/api/v1/items/{}", body["id"].as_str().unwrap())
+}
+
+async fn patch(
+    app: &Router,
+    uri: &str,
+    content_type: &str,
+    extra: &[(&str, &str)],
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut headers = vec![(header::CONTENT_TYPE.as_str(), content_type)];
+    headers.extend_from_slice(extra);
+    let (status, _, body) = common::send_with(app, Method::PATCH, uri, &headers, Some(body)).await;
+    (status, body)
+}
+
+#[tokio::test]
+async fn merge_patch_changes_only_the_given_fields() {
+    for (backend, app) in common::backends().await {
+        let uri = create(&app).await;
+
+        let (status, body) = patch(&app, &uri, MERGE, &[], json!({ "quantity": 7 })).await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {body}");
+        assert_eq!(body["name"], "widget", "{backend}");
+        assert_eq!(body["description"], "blue", "{backend}");
+        assert_eq!(body["quantity"], 7, "{backend}");
+        assert_eq!(body["version"], 2, "{backend}");
+
+        // `null` removes an optional field.
+        let (status, body) = patch(&app, &uri, MERGE, &[], json!({ "description": null })).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(body["description"], Value::Null, "{backend}");
+
+        let (_, stored) = common::send(&app, Method::GET, &uri, None).await;
+        assert_eq!(stored, body, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn json_patch_applies_operations_in_order() {
+    for (backend, app) in common::backends().await {
+        let uri = create(&app).await;
+
+        let (status, body) = patch(
+            &app,
+            &uri,
+            JSON_PATCH,
+            &[],
+            json!([
+                { "op": "test", "path": "/quantity", "value": 3 },
+                { "op": "replace", "path": "/quantity", "value": 4 },
+                { "op": "copy", "from": "/name", "path": "/description" },
+            ]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {body}");
+        assert_eq!(body["quantity"], 4, "{backend}");
+        assert_eq!(body["description"], "widget", "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn failed_operations_change_nothing() {
+    for (backend, app) in common::backends().await {
+        let uri = create(&app).await;
+
+        for ops in [
+            json!([
+                { "op": "replace", "path": "/quantity", "value": 4 },
+                { "op": "test", "path": "/name", "value": "gadget" },
+            ]),
+            json!([{ "op": "remove", "path": "/colour" }]),
+        ] {
+            let (status, body) = patch(&app, &uri, JSON_PATCH, &[], ops).await;
+            assert_eq!(status, StatusCode::CONFLICT, "{backend}");
+            assert_eq!(body["error"]["code"], "conflict", "{backend}");
+        }
+
+        let (_, stored) = common::send(&app, Method::GET, &uri, None).await;
+        assert_eq!(stored["quantity"], 3, "{backend}");
+        assert_eq!(stored["version"], 1, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn patched_items_are_validated_like_new_ones() {
+    let app = common::app().await;
+    let uri = create(&app).await;
+
+    let (status, body) = patch(
+        &app,
+        &uri,
+        MERGE,
+        &[],
+        json!({ "name": "", "quantity": -1 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let mut fields: Vec<_> = body["error"]["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|detail| detail["field"].as_str().unwrap().to_owned())
+        .collect();
+    fields.sort();
+    assert_eq!(fields, ["name", "quantity"]);
+
+    // Removing a required field or adding an unknown one is just as invalid.
+    for ops in [
+        json!([{ "op": "remove", "path": "/name" }]),
+        json!([{ "op": "add", "path": "/colour", "value": "red" }]),
+    ] {
+        let (status, _) = patch(&app, &uri, JSON_PATCH, &[], ops).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let (_, stored) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(stored["version"], 1);
+}
+
+#[tokio::test]
+async fn patch_honours_if_match() {
+    let app = common::app().await;
+    let uri = create(&app).await;
+
+    let (status, _) = patch(
+        &app,
+        &uri,
+        MERGE,
+        &[("if-match", r#""2""#)],
+        json!({ "quantity": 9 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let (status, body) = patch(
+        &app,
+        &uri,
+        MERGE,
+        &[("if-match", r#""1""#)],
+        json!({ "quantity": 9 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 2);
+}
+
+#[tokio::test]
+async fn patch_documents_are_checked() {
+    let app = common::app().await;
+    let uri = create(&app).await;
+
+    let (status, body) = patch(
+        &app,
+        &uri,
+        "application/json",
+        &[],
+        json!({ "quantity": 1 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(body["error"]["code"], "unsupported_media_type");
+
+    let (status, _) = patch(&app, &uri, MERGE, &[], json!([1, 2])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = patch(&app, &uri, JSON_PATCH, &[], json!([{ "op": "frobnicate" }])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let missing = format!("/api/v1/items/{}", uuid::Uuid::now_v7());
+    let (status, _) = patch(&app, &missing, MERGE, &[], json!({ "quantity": 1 })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
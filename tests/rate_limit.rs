@@ -0,0 +1,220 @@
+mod common;
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use api_app::{
+    AppState,
+    rate_limit::{ManualClock, Quota, RateLimitConfig, RateLimits},
+    storage::MemoryStorage,
+};
+use axum::{
+    Router,
+    body::Body,
+    extract::ConnectInfo,
+    http::{Method, Request, StatusCode, header},
+    response::Response,
+};
+use serde_json::json;
+use tower::ServiceExt;
+
+async fn app(clock: Arc<ManualClock>, trusted_proxies: usize) -> Router {
+    let config = RateLimitConfig {
+        api: Some(Quota::per_minute(2)),
+        auth: Some(Quota::per_minute(2)),
+        trusted_proxies,
+        ..RateLimitConfig::default()
+    };
+    let state = AppState::new(MemoryStorage::new())
+        .with_rate_limits(RateLimits::with_clock(&config, clock));
+    common::app_from(state).await
+}
+
+async fn get(app: &Router, token: &str) -> Response {
+    let request = Request::get("/api/v1/items")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+fn header(response: &Response, name: &str) -> String {
+    response.headers()[name].to_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn requests_beyond_the_quota_are_rejected_until_refilled() {
+    let clock = Arc::new(ManualClock::new());
+    let app = app(clock.clone(), 0).await;
+
+    let first = get(&app, common::ADMIN_TOKEN).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(header(&first, "x-ratelimit-limit"), "2");
+    assert_eq!(header(&first, "x-ratelimit-remaining"), "1");
+    assert_eq!(header(&first, "x-ratelimit-reset"), "30");
+
+    assert_eq!(
+        get(&app, common::ADMIN_TOKEN).await.status(),
+        StatusCode::OK
+    );
+
+    let limited = get(&app, common::ADMIN_TOKEN).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&limited, "retry-after"), "30");
+    assert_eq!(header(&limited, "x-ratelimit-remaining"), "0");
+
+    clock.advance(Duration::from_secs(29));
+    assert_eq!(
+        get(&app, common::ADMIN_TOKEN).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(
+        get(&app, common::ADMIN_TOKEN).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn each_client_has_its_own_bucket() {
+    let clock = Arc::new(ManualClock::new());
+    let app = app(clock, 0).await;
+    let (_, key) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "other", "scopes": ["read"] })),
+    )
+    .await;
+    let other = key["token"].as_str().unwrap();
+
+    for _ in 0..2 {
+        get(&app, common::ADMIN_TOKEN).await;
+    }
+    assert_eq!(
+        get(&app, common::ADMIN_TOKEN).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(get(&app, other).await.status(), StatusCode::OK);
+}
+
+/// Like [`get`], as if from `peer`, optionally through a proxy that set
+/// `X-Forwarded-For`.
+async fn get_from(app: &Router, token: &str, peer: &str, forwarded: Option<&str>) -> Response {
+    let mut request = Request::get("/api/v1/items")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+    let peer: IpAddr = peer.parse().unwrap();
+    request
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::new(peer, 40000)));
+    if let Some(forwarded) = forwarded {
+        request
+            .headers_mut()
+            .insert("x-forwarded-for", forwarded.parse().unwrap());
+    }
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn failed_authentication_is_limited_by_ip() {
+    let clock = Arc::new(ManualClock::new());
+    let app = app(clock.clone(), 0).await;
+    let client = "203.0.113.7";
+
+    for _ in 0..2 {
+        let response = get_from(&app, "ak_guess", client, None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    let limited = get_from(&app, "ak_guess", client, None).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&limited, "retry-after"), "30");
+
+    // Valid credentials from the same address still work, and other
+    // addresses have budgets of their own.
+    assert_eq!(
+        get_from(&app, common::ADMIN_TOKEN, client, None)
+            .await
+            .status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        get_from(&app, "ak_guess", "203.0.113.8", None)
+            .await
+            .status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(
+        get_from(&app, "ak_guess", client, None).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn clients_behind_trusted_proxies_are_told_apart() {
+    let clock = Arc::new(ManualClock::new());
+    let app = app(clock, 1).await;
+    let proxy = "10.0.0.1";
+
+    for _ in 0..2 {
+        get_from(&app, "ak_guess", proxy, Some("198.51.100.1, 203.0.113.7")).await;
+    }
+    // The entry the client wrote itself does not get it a fresh bucket.
+    let limited = get_from(&app, "ak_guess", proxy, Some("198.51.100.2, 203.0.113.7")).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let other = get_from(&app, "ak_guess", proxy, Some("203.0.113.8")).await;
+    assert_eq!(other.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn unknown_addresses_share_no_bucket() {
+    let clock = Arc::new(ManualClock::new());
+    let app = app(clock, 0).await;
+
+    for _ in 0..3 {
+        assert_eq!(
+            get(&app, "ak_guess").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
+
+#[tokio::test]
+async fn unlimited_groups_carry_no_headers() {
+    let clock = Arc::new(ManualClock::new());
+    let app = app(clock, 0).await;
+
+    let request = Request::get("/api/v1/admin/api-keys")
+        .header(
+            header::AUTHORIZATION,
+            format!("Bearer {}", common::ADMIN_TOKEN),
+        )
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("x-ratelimit-limit"));
+}
+
+#[test]
+fn quotas_parse() {
+    assert_eq!("120/min".parse::<Quota>().unwrap(), Quota::per_minute(120));
+    assert_eq!(
+        "10/30".parse::<Quota>().unwrap(),
+        Quota {
+            requests: 10,
+            period: Duration::from_secs(30)
+        }
+    );
+    assert!("0/min".parse::<Quota>().is_err());
+    assert!("ten/min".parse::<Quota>().is_err());
+}
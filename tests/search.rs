@@ -0,0 +1,290 @@
+mod common;
+
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+async fn create(app: &Router, name: &str, description: Option<&str>) -> Value {
+    let (status, item) = common::send(
+        app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": name, "description": description })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{item}");
+    item
+}
+
+/// The names of the hits for `q`, best first.
+async fn search(app: &Router, q: &str) -> Vec<String> {
+    let (status, page) = common::send(
+        app,
+        Method::GET,
+        &format!("/api/v1/search?q={}", urlencoding(q)),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{q}: {page}");
+    page["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|hit| hit["item"]["name"].as_str().unwrap().to_owned())
+        .collect()
+}
+
+fn urlencoding(q: &str) -> String {
+    q.replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('"', "%22")
+        .replace('*', "%2A")
+}
+
+async fn seed(app: &Router) {
+    create(
+        app,
+        "Red widget",
+        Some("A small red widget for the kitchen"),
+    )
+    .await;
+    create(app, "Blue gadget", Some("Widgets sold separately")).await;
+    create(app, "Widget stand", None).await;
+    create(app, "Garden hose", Some("Keeps the red roses watered")).await;
+}
+
+#[tokio::test]
+async fn words_phrases_and_prefixes_match() {
+    for (backend, app) in common::backends().await {
+        seed(&app).await;
+
+        let mut hits = search(&app, "widget").await;
+        hits.sort();
+        assert_eq!(hits, ["Red widget", "Widget stand"], "{backend}");
+
+        let mut hits = search(&app, "WIDG*").await;
+        hits.sort();
+        assert_eq!(
+            hits,
+            ["Blue gadget", "Red widget", "Widget stand"],
+            "{backend}"
+        );
+
+        // Every term has to match, in either field.
+        assert_eq!(
+            search(&app, "red kitchen").await,
+            ["Red widget"],
+            "{backend}"
+        );
+        assert_eq!(
+            search(&app, "\"widget stand\"").await,
+            ["Widget stand"],
+            "{backend}"
+        );
+        assert!(
+            search(&app, "\"stand widget\"").await.is_empty(),
+            "{backend}"
+        );
+        // Punctuation splits words, and FTS5 syntax means nothing.
+        assert_eq!(
+            search(&app, "red-widget").await,
+            ["Red widget"],
+            "{backend}"
+        );
+        assert!(search(&app, "red OR hose").await.is_empty(), "{backend}");
+        assert!(search(&app, "name:hose").await.is_empty(), "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn results_are_ranked_and_highlighted() {
+    for (backend, app) in common::backends().await {
+        seed(&app).await;
+
+        let (_, page) = common::send(&app, Method::GET, "/api/v1/search?q=red", None).await;
+        let hits = page["data"].as_array().unwrap();
+        assert_eq!(hits.len(), 2, "{backend}: {page}");
+        // A match in the name counts for more.
+        assert_eq!(hits[0]["item"]["name"], "Red widget", "{backend}");
+        assert!(
+            hits[0]["score"].as_f64().unwrap() > hits[1]["score"].as_f64().unwrap(),
+            "{backend}: {page}"
+        );
+        assert_eq!(
+            hits[0]["highlights"],
+            json!({
+                "name": "<mark>Red</mark> widget",
+                "description": "A small <mark>red</mark> widget for the kitchen",
+            }),
+            "{backend}"
+        );
+        assert_eq!(
+            hits[1]["highlights"],
+            json!({
+                "name": "Garden hose",
+                "description": "Keeps the <mark>red</mark> roses watered",
+            }),
+            "{backend}"
+        );
+
+        let (_, page) = common::send(
+            &app,
+            Method::GET,
+            "/api/v1/search?q=%22red%20widget%22",
+            None,
+        )
+        .await;
+        assert_eq!(
+            page["data"][0]["highlights"]["name"], "<mark>Red widget</mark>",
+            "{backend}"
+        );
+
+        // Long descriptions are cut down to the part that matched.
+        let filler = "lorem ipsum dolor sit amet ".repeat(6);
+        create(
+            &app,
+            "Lamp",
+            Some(&format!("{filler}with a brass base, {filler}")),
+        )
+        .await;
+        let (_, page) = common::send(&app, Method::GET, "/api/v1/search?q=brass", None).await;
+        let snippet = page["data"][0]["highlights"]["description"]
+            .as_str()
+            .unwrap();
+        assert!(snippet.starts_with('…'), "{backend}: {snippet}");
+        assert!(snippet.ends_with('…'), "{backend}: {snippet}");
+        assert!(
+            snippet.contains("<mark>brass</mark>"),
+            "{backend}: {snippet}"
+        );
+        assert_eq!(page["data"][0]["highlights"]["name"], "Lamp", "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn highlights_escape_item_text() {
+    for (backend, app) in common::backends().await {
+        create(
+            &app,
+            "<script>alert('hi')</script> widget",
+            Some("Tom & Jerry's \"widget\" <b>box</b>"),
+        )
+        .await;
+
+        let (_, page) = common::send(&app, Method::GET, "/api/v1/search?q=widget", None).await;
+        assert_eq!(
+            page["data"][0]["highlights"],
+            json!({
+                "name": "&lt;script&gt;alert(&#39;hi&#39;)&lt;/script&gt; <mark>widget</mark>",
+                "description": "Tom &amp; Jerry&#39;s &quot;<mark>widget</mark>&quot; &lt;b&gt;box&lt;/b&gt;",
+            }),
+            "{backend}: {page}"
+        );
+        // The item itself is returned as stored.
+        assert_eq!(
+            page["data"][0]["item"]["name"], "<script>alert('hi')</script> widget",
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn index_follows_writes() {
+    for (backend, app) in common::backends().await {
+        let item = create(&app, "Widget", Some("blue")).await;
+        let uri = format!("/api/v1/items/{}", item["id"].as_str().unwrap());
+
+        common::send(
+            &app,
+            Method::PUT,
+            &uri,
+            Some(json!({ "name": "Gadget", "description": "green" })),
+        )
+        .await;
+        assert!(search(&app, "widget").await.is_empty(), "{backend}");
+        assert!(search(&app, "blue").await.is_empty(), "{backend}");
+        assert_eq!(search(&app, "gadget green").await, ["Gadget"], "{backend}");
+
+        common::send_with(
+            &app,
+            Method::PATCH,
+            &uri,
+            &[("content-type", "application/merge-patch+json")],
+            Some(json!({ "quantity": 5 })),
+        )
+        .await;
+        assert_eq!(search(&app, "gadget").await, ["Gadget"], "{backend}");
+
+        common::send(&app, Method::DELETE, &uri, None).await;
+        assert!(search(&app, "gadget").await.is_empty(), "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn results_are_paginated() {
+    for (backend, app) in common::backends().await {
+        for n in 0..5 {
+            create(&app, &format!("widget {n}"), None).await;
+        }
+
+        let mut seen = Vec::new();
+        let mut uri = "/api/v1/search?q=widget&limit=2".to_owned();
+        loop {
+            let (status, page) = common::send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::OK, "{backend}: {page}");
+            for hit in page["data"].as_array().unwrap() {
+                seen.push(hit["item"]["id"].as_str().unwrap().to_owned());
+            }
+            let Some(cursor) = page["next_cursor"].as_str() else {
+                break;
+            };
+            uri = format!("/api/v1/search?q=widget&limit=2&cursor={cursor}");
+        }
+        assert_eq!(seen.len(), 5, "{backend}");
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5, "{backend}");
+
+        // A cursor only continues the query it came from.
+        let (_, page) =
+            common::send(&app, Method::GET, "/api/v1/search?q=widget&limit=2", None).await;
+        let cursor = page["next_cursor"].as_str().unwrap();
+        let (status, body) = common::send(
+            &app,
+            Method::GET,
+            &format!("/api/v1/search?q=widg*&cursor={cursor}"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert_eq!(body["error"]["details"][0]["field"], "cursor", "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn invalid_queries_are_rejected() {
+    let app = common::app().await;
+    for (query, field, code) in [
+        ("", "q", "missing"),
+        ("?q=%20--%20*", "q", "invalid_value"),
+        ("?q=widget&limit=0", "limit", "out_of_range"),
+        ("?q=widget&cursor=nope", "cursor", "invalid_value"),
+    ] {
+        let (status, body) =
+            common::send(&app, Method::GET, &format!("/api/v1/search{query}"), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{query}: {body}");
+        assert_eq!(body["error"]["details"][0]["field"], field, "{query}");
+        assert_eq!(body["error"]["details"][0]["code"], code, "{query}");
+    }
+
+    let long = "a".repeat(501);
+    let (status, body) =
+        common::send(&app, Method::GET, &format!("/api/v1/search?q={long}"), None).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["details"][0]["code"], "too_long");
+
+    let (status, _) = common::send_as(&app, None, Method::GET, "/api/v1/search?q=x", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
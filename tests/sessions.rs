@@ -0,0 +1,234 @@
+mod common;
+
+use std::time::Duration;
+
+use api_app::{
+    AppState,
+    auth::{
+        Scope,
+        jwt::{Jwt, JwtSettings, KeyMaterial, SigningAlgorithm},
+    },
+    model::User,
+    storage::{MemoryStorage, SqliteStorage, Storage},
+};
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+fn hmac() -> Jwt {
+    Jwt::new(
+        SigningAlgorithm::Hs256,
+        KeyMaterial::Secret(b"test-secret".to_vec()),
+        JwtSettings::default(),
+    )
+    .unwrap()
+}
+
+fn key_pair(algorithm: SigningAlgorithm, name: &str) -> Jwt {
+    let fixture = |file: &str| std::fs::read(format!("tests/fixtures/{name}_{file}.pem")).unwrap();
+    Jwt::new(
+        algorithm,
+        KeyMaterial::Pem {
+            private: fixture("private"),
+            public: fixture("public"),
+        },
+        JwtSettings::default(),
+    )
+    .unwrap()
+}
+
+fn user(scopes: Vec<Scope>) -> User {
+    User {
+        id: uuid::Uuid::now_v7(),
+        username: "alice".into(),
+        scopes,
+        created_at: chrono::Utc::now(),
+    }
+}
+
+async fn app(storage: impl Storage + 'static, jwt: Jwt) -> Router {
+    let app = common::app_from(AppState::new(storage).with_jwt(jwt)).await;
+    let (status, _) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/users",
+        Some(json!({ "username": "alice", "password": "correct horse", "scopes": ["read"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    app
+}
+
+async fn login(app: &Router, password: &str) -> (StatusCode, Value) {
+    common::send_as(
+        app,
+        None,
+        Method::POST,
+        "/api/v1/auth/login",
+        Some(json!({ "username": "alice", "password": password })),
+    )
+    .await
+}
+
+async fn refresh(app: &Router, token: &Value) -> (StatusCode, Value) {
+    common::send_as(
+        app,
+        None,
+        Method::POST,
+        "/api/v1/auth/refresh",
+        Some(json!({ "refresh_token": token })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn access_tokens_work_with_every_key_type() {
+    for jwt in [
+        hmac(),
+        key_pair(SigningAlgorithm::Rs256, "rsa"),
+        key_pair(SigningAlgorithm::EdDsa, "ed25519"),
+    ] {
+        let app = app(MemoryStorage::new(), jwt).await;
+
+        let (status, session) = login(&app, "correct horse").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(session["token_type"], "Bearer");
+        let token = session["access_token"].as_str().unwrap();
+
+        let (status, body) =
+            common::send_as(&app, Some(token), Method::GET, "/api/v1/items", None).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        // The user only has the read scope.
+        let (status, _) = common::send_as(
+            &app,
+            Some(token),
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": "x" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
+
+#[tokio::test]
+async fn wrong_password_is_unauthorized() {
+    let app = app(MemoryStorage::new(), hmac()).await;
+
+    let (status, body) = login(&app, "wrong").await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "unauthorized");
+}
+
+#[tokio::test]
+async fn tokens_signed_with_another_key_are_rejected() {
+    let app = app(MemoryStorage::new(), hmac()).await;
+    let other = Jwt::new(
+        SigningAlgorithm::Hs256,
+        KeyMaterial::Secret(b"other-secret".to_vec()),
+        JwtSettings::default(),
+    )
+    .unwrap();
+    let forged = other.issue(&user(vec![Scope::Admin]));
+    let (status, _) =
+        common::send_as(&app, Some(&forged), Method::GET, "/api/v1/items", None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn expiry_honours_the_configured_leeway() {
+    let settings = JwtSettings {
+        access_ttl: Duration::ZERO,
+        leeway: Duration::ZERO,
+        ..JwtSettings::default()
+    };
+    let strict = Jwt::new(
+        SigningAlgorithm::Hs256,
+        KeyMaterial::Secret(b"s".to_vec()),
+        settings.clone(),
+    )
+    .unwrap();
+    let lenient = Jwt::new(
+        SigningAlgorithm::Hs256,
+        KeyMaterial::Secret(b"s".to_vec()),
+        JwtSettings {
+            leeway: Duration::from_secs(60),
+            ..settings
+        },
+    )
+    .unwrap();
+    let token = strict.issue(&user(vec![]));
+    std::thread::sleep(Duration::from_millis(1100));
+
+    assert!(strict.verify(&token).is_err());
+    assert!(lenient.verify(&token).is_ok());
+}
+
+#[tokio::test]
+async fn refresh_rotates_and_detects_reuse() {
+    for app in [
+        app(MemoryStorage::new(), hmac()).await,
+        app(SqliteStorage::open_in_memory().unwrap(), hmac()).await,
+    ] {
+        let (_, first) = login(&app, "correct horse").await;
+
+        let (status, second) = refresh(&app, &first["refresh_token"]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(second["refresh_token"], first["refresh_token"]);
+
+        // Replaying the rotated token revokes the whole family.
+        let (status, _) = refresh(&app, &first["refresh_token"]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = refresh(&app, &second["refresh_token"]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn logout_revokes_the_refresh_token() {
+    let app = app(MemoryStorage::new(), hmac()).await;
+    let (_, session) = login(&app, "correct horse").await;
+
+    let (status, _) = common::send_as(
+        &app,
+        None,
+        Method::POST,
+        "/api/v1/auth/logout",
+        Some(json!({ "refresh_token": session["refresh_token"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = refresh(&app, &session["refresh_token"]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn duplicate_usernames_conflict() {
+    let app = app(SqliteStorage::open_in_memory().unwrap(), hmac()).await;
+
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/users",
+        Some(json!({ "username": "alice", "password": "another one", "scopes": ["read"] })),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "conflict");
+}
+
+#[tokio::test]
+async fn session_routes_are_absent_without_jwt() {
+    let app = common::app().await;
+
+    let (status, _) = login(&app, "whatever").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use api_app::storage::{SqliteStorage, migrations};
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn items_survive_reopening_the_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.db");
+
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+    let (status, created) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget", "description": "blue", "quantity": 2 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    drop(app);
+
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+    let uri = format!("/api/v1/items/{}", created["id"].as_str().unwrap());
+    let (status, fetched) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fetched, created);
+}
+
+#[tokio::test]
+async fn sqlite_item_lifecycle() {
+    let app = common::app_with(SqliteStorage::open_in_memory().unwrap()).await;
+
+    let (_, created) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    let uri = format!("/api/v1/items/{}", created["id"].as_str().unwrap());
+
+    let (status, updated) = common::send(
+        &app,
+        Method::PUT,
+        &uri,
+        Some(json!({ "name": "gadget", "quantity": 7 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(updated["quantity"], 7);
+
+    let (_, listed) = common::send(&app, Method::GET, "/api/v1/items", None).await;
+    assert_eq!(listed, json!({ "data": [updated], "next_cursor": null }));
+
+    let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn search_survives_vacuum() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.db");
+
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+    for name in ["alpha", "beta", "gamma"] {
+        common::send(
+            &app,
+            Method::POST,
+            "/api/v1/items",
+            Some(json!({ "name": name })),
+        )
+        .await;
+    }
+    drop(app);
+
+    // The gap left by the delete is what VACUUM would close by renumbering
+    // implicit rowids.
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute("DELETE FROM items WHERE name = 'alpha'", [])
+        .unwrap();
+    conn.execute_batch("VACUUM").unwrap();
+    drop(conn);
+
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+    for name in ["beta", "gamma"] {
+        let (status, page) =
+            common::send(&app, Method::GET, &format!("/api/v1/search?q={name}"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["data"][0]["item"]["name"], name, "{page}");
+        assert_eq!(page["data"].as_array().unwrap().len(), 1, "{page}");
+    }
+}
+
+#[test]
+fn migrations_are_idempotent() {
+    let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+    assert_eq!(
+        migrations::apply(&mut conn).unwrap(),
+        migrations::latest_version()
+    );
+    assert_eq!(
+        migrations::apply(&mut conn).unwrap(),
+        migrations::latest_version()
+    );
+}
@@ -0,0 +1,264 @@
+mod common;
+
+use std::time::Duration;
+
+use api_app::{
+    AppState,
+    storage::{MemoryStorage, SqliteStorage},
+    trash::{self, TrashSettings},
+};
+use axum::{
+    Router,
+    http::{Method, StatusCode},
+};
+use serde_json::{Value, json};
+
+fn states(retention: Duration) -> Vec<(&'static str, AppState)> {
+    let settings = TrashSettings {
+        retention,
+        purge_interval: Duration::from_millis(10),
+    };
+    vec![
+        (
+            "memory",
+            AppState::new(MemoryStorage::new()).with_trash_settings(settings.clone()),
+        ),
+        (
+            "sqlite",
+            AppState::new(SqliteStorage::open_in_memory().unwrap()).with_trash_settings(settings),
+        ),
+    ]
+}
+
+async fn create(app: &Router, name: &str) -> String {
+    let (status, item) = common::send(
+        app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": name })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{item}");
+    item["id"].as_str().unwrap().to_owned()
+}
+
+/// Names of the items on a page of a list or of search results.
+async fn names(app: &Router, uri: &str) -> Vec<String> {
+    let (status, page) = common::send(app, Method::GET, uri, None).await;
+    assert_eq!(status, StatusCode::OK, "{uri}: {page}");
+    page["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            let item = entry.get("item").unwrap_or(entry);
+            item["name"].as_str().unwrap().to_owned()
+        })
+        .collect()
+}
+
+async fn delete(app: &Router, id: &str) {
+    let (status, body) =
+        common::send(app, Method::DELETE, &format!("/api/v1/items/{id}"), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{body}");
+}
+
+async fn restore(app: &Router, id: &str) -> (StatusCode, Value) {
+    common::send(
+        app,
+        Method::POST,
+        &format!("/api/v1/items/{id}/restore"),
+        None,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn deleted_items_are_hidden_but_listed_for_admins() {
+    for (backend, app) in common::backends().await {
+        create(&app, "kept widget").await;
+        let id = create(&app, "deleted widget").await;
+        delete(&app, &id).await;
+
+        let uri = format!("/api/v1/items/{id}");
+        let (status, _) = common::send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        let (status, _) = common::send(&app, Method::PUT, &uri, Some(json!({ "name": "x" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+
+        assert_eq!(
+            names(&app, "/api/v1/items").await,
+            ["kept widget"],
+            "{backend}"
+        );
+        assert_eq!(
+            names(&app, "/api/v1/search?q=widget").await,
+            ["kept widget"],
+            "{backend}"
+        );
+
+        let (_, page) = common::send(
+            &app,
+            Method::GET,
+            "/api/v1/items?include_deleted=true&sort=created_at",
+            None,
+        )
+        .await;
+        let items = page["data"].as_array().unwrap();
+        assert_eq!(items.len(), 2, "{backend}");
+        assert_eq!(items[0]["deleted_at"], Value::Null, "{backend}");
+        assert_eq!(items[1]["id"], id.as_str(), "{backend}");
+        assert!(items[1]["deleted_at"].is_string(), "{backend}");
+        assert_eq!(items[1]["version"], 2, "{backend}");
+
+        // The trash is for admins only.
+        let (_, key) = common::send(
+            &app,
+            Method::POST,
+            "/api/v1/admin/api-keys",
+            Some(json!({ "name": "writer", "scopes": ["read", "write"] })),
+        )
+        .await;
+        let token = key["token"].as_str();
+        let (status, _) = common::send_as(
+            &app,
+            token,
+            Method::GET,
+            "/api/v1/items?include_deleted=true",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{backend}");
+        let (status, _) = common::send_as(
+            &app,
+            token,
+            Method::POST,
+            &format!("/api/v1/items/{id}/restore"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn deleted_items_can_be_restored() {
+    for (backend, app) in common::backends().await {
+        let id = create(&app, "widget").await;
+        delete(&app, &id).await;
+
+        let (status, item) = restore(&app, &id).await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {item}");
+        assert_eq!(item["deleted_at"], Value::Null, "{backend}");
+        assert_eq!(item["version"], 3, "{backend}");
+
+        let (status, fetched) =
+            common::send(&app, Method::GET, &format!("/api/v1/items/{id}"), None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(fetched, item, "{backend}");
+        assert_eq!(
+            names(&app, "/api/v1/search?q=widget").await,
+            ["widget"],
+            "{backend}"
+        );
+
+        let (status, body) = restore(&app, &id).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{backend}");
+        assert_eq!(body["error"]["code"], "conflict", "{backend}");
+        let (status, _) = restore(&app, "00000000-0000-0000-0000-000000000000").await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+
+        let (_, page) = common::send(
+            &app,
+            Method::GET,
+            &format!("/api/v1/admin/audit-log?resource_id={id}&sort=occurred_at"),
+            None,
+        )
+        .await;
+        let actions: Vec<&str> = page["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            actions,
+            ["item.created", "item.deleted", "item.restored"],
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn items_are_purged_after_the_retention_period() {
+    for (backend, state) in states(Duration::from_secs(60 * 60)) {
+        let app = common::app_from(state.clone()).await;
+        let id = create(&app, "widget").await;
+        delete(&app, &id).await;
+
+        assert_eq!(trash::purge(&state).await.unwrap(), 0, "{backend}");
+        let (status, _) = restore(&app, &id).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+    }
+
+    for (backend, state) in states(Duration::ZERO) {
+        let app = common::app_from(state.clone()).await;
+        let kept = create(&app, "kept").await;
+        let id = create(&app, "widget").await;
+        delete(&app, &id).await;
+
+        assert_eq!(trash::purge(&state).await.unwrap(), 1, "{backend}");
+        let (status, _) = restore(&app, &id).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        let (_, page) = common::send(
+            &app,
+            Method::GET,
+            "/api/v1/admin/audit-log?actor=system",
+            None,
+        )
+        .await;
+        assert_eq!(page["data"].as_array().unwrap().len(), 1, "{backend}");
+        assert_eq!(page["data"][0]["action"], "item.purged", "{backend}");
+        assert_eq!(
+            page["data"][0]["changes"]["count"]["before"], 1,
+            "{backend}"
+        );
+        assert_eq!(
+            names(&app, "/api/v1/items?include_deleted=true").await,
+            ["kept"],
+            "{backend}"
+        );
+        let (status, _) =
+            common::send(&app, Method::GET, &format!("/api/v1/items/{kept}"), None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn the_trash_is_purged_in_the_background() {
+    for (backend, state) in states(Duration::ZERO) {
+        let app = common::app_from(state.clone()).await;
+        let id = create(&app, "widget").await;
+        delete(&app, &id).await;
+
+        let worker = tokio::spawn(trash::run(state.clone()));
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !names(&app, "/api/v1/items?include_deleted=true")
+                .await
+                .is_empty()
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{backend}: the trash was not purged"));
+
+        state.readiness.begin_shutdown();
+        tokio::time::timeout(Duration::from_secs(5), worker)
+            .await
+            .expect("the purge task stops on shutdown")
+            .unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{Value, json};
+
+async fn create(body: Value) -> (StatusCode, Value) {
+    common::send(
+        &common::app().await,
+        Method::POST,
+        "/api/v1/items",
+        Some(body),
+    )
+    .await
+}
+
+fn details(body: &Value) -> Vec<(String, String)> {
+    body["error"]["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| {
+            (
+                d["field"].as_str().unwrap().to_owned(),
+                d["code"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn missing_field_is_reported() {
+    let (status, body) = create(json!({ "quantity": 1 })).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "validation_failed");
+    assert_eq!(details(&body), [("name".to_owned(), "missing".to_owned())]);
+}
+
+#[tokio::test]
+async fn wrong_type_is_reported() {
+    let (status, body) = create(json!({ "name": "x", "quantity": "many" })).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        details(&body),
+        [("quantity".to_owned(), "invalid_type".to_owned())]
+    );
+}
+
+#[tokio::test]
+async fn unknown_field_is_reported() {
+    let (status, body) = create(json!({ "name": "x", "colour": "red" })).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        details(&body),
+        [("colour".to_owned(), "unknown_field".to_owned())]
+    );
+}
+
+#[tokio::test]
+async fn every_rule_violation_is_listed() {
+    let (status, body) = create(json!({
+        "name": "",
+        "description": "x".repeat(2001),
+        "quantity": -1,
+    }))
+    .await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(
+        details(&body),
+        [
+            ("description".to_owned(), "too_long".to_owned()),
+            ("name".to_owned(), "too_short".to_owned()),
+            ("quantity".to_owned(), "out_of_range".to_owned()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn syntax_error_is_bad_request() {
+    let app = common::app().await;
+    let request = axum::http::Request::post("/api/v1/items")
+        .header("content-type", "application/json")
+        .header("authorization", format!("Bearer {}", common::ADMIN_TOKEN))
+        .body(axum::body::Body::from("{ not json"))
+        .unwrap();
+
+    let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,377 @@
+mod common;
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use api_app::{
+    AppState,
+    storage::{MemoryStorage, SqliteStorage, Storage},
+    webhooks::{self, WebhookSettings},
+};
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode},
+    routing::post,
+};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tokio::{net::TcpListener, sync::mpsc};
+
+/// A request the receiver got.
+struct Received {
+    headers: HeaderMap,
+    body: Bytes,
+    at: Instant,
+}
+
+/// Local stand-in for an integrator's endpoint. Answers with the queued
+/// statuses in order, then 200.
+struct Receiver {
+    url: String,
+    requests: mpsc::UnboundedReceiver<Received>,
+}
+
+#[derive(Clone)]
+struct ReceiverState {
+    requests: mpsc::UnboundedSender<Received>,
+    statuses: Arc<Mutex<VecDeque<StatusCode>>>,
+}
+
+impl Receiver {
+    async fn start(statuses: &[StatusCode]) -> Self {
+        let (sender, requests) = mpsc::unbounded_channel();
+        let state = ReceiverState {
+            requests: sender,
+            statuses: Arc::new(Mutex::new(statuses.iter().copied().collect())),
+        };
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(state);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(axum::serve(listener, app).into_future());
+        Self { url, requests }
+    }
+
+    async fn next(&mut self) -> Received {
+        tokio::time::timeout(Duration::from_secs(5), self.requests.recv())
+            .await
+            .expect("a delivery within five seconds")
+            .unwrap()
+    }
+}
+
+async fn receive(
+    State(state): State<ReceiverState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    state
+        .requests
+        .send(Received {
+            headers,
+            body,
+            at: Instant::now(),
+        })
+        .unwrap();
+    state
+        .statuses
+        .lock()
+        .unwrap()
+        .pop_front()
+        .unwrap_or(StatusCode::OK)
+}
+
+fn fast_retries(max_attempts: u32) -> WebhookSettings {
+    WebhookSettings {
+        max_attempts,
+        retry_backoff: Duration::from_millis(50),
+        timeout: Duration::from_secs(2),
+        poll_interval: Duration::from_millis(10),
+    }
+}
+
+/// App over `storage` with the delivery worker running.
+async fn app_with_worker(storage: impl Storage + 'static, settings: WebhookSettings) -> Router {
+    let state = AppState::new(storage).with_webhook_settings(settings);
+    tokio::spawn(webhooks::run(state.clone()));
+    common::app_from(state).await
+}
+
+/// Registers a webhook, returning its id and secret.
+async fn register(app: &Router, url: &str, events: &[&str]) -> (String, String) {
+    let (status, body) = common::send(
+        app,
+        Method::POST,
+        "/api/v1/admin/webhooks",
+        Some(json!({ "url": url, "events": events })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    (
+        body["id"].as_str().unwrap().to_owned(),
+        body["secret"].as_str().unwrap().to_owned(),
+    )
+}
+
+/// Polls the delivery log until its newest entry satisfies `done`.
+async fn wait_for_delivery(app: &Router, webhook: &str, done: impl Fn(&Value) -> bool) -> Value {
+    let uri = format!("/api/v1/admin/webhooks/{webhook}/deliveries");
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let (_, page) = common::send(app, Method::GET, &uri, None).await;
+        if let Some(latest) = page["data"].get(0)
+            && done(latest)
+        {
+            return latest.clone();
+        }
+        assert!(
+            Instant::now() < deadline,
+            "delivery log never settled: {page}"
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+fn verify(secret: &str, received: &Received) {
+    let signature = received.headers["webhook-signature"].to_str().unwrap();
+    let (timestamp, mac) = signature
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split_once(",v1="))
+        .unwrap();
+    let mut expected = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    expected.update(format!("{timestamp}.").as_bytes());
+    expected.update(&received.body);
+    expected
+        .verify_slice(&hex::decode(mac).unwrap())
+        .expect("signature matches");
+    let age = chrono::Utc::now().timestamp() - timestamp.parse::<i64>().unwrap();
+    assert!((0..5).contains(&age), "{age}");
+}
+
+#[tokio::test]
+async fn subscribed_changes_are_delivered_signed() {
+    delivers_signed_changes("memory", MemoryStorage::new()).await;
+    delivers_signed_changes("sqlite", SqliteStorage::open_in_memory().unwrap()).await;
+}
+
+async fn delivers_signed_changes(backend: &str, storage: impl Storage + 'static) {
+    let mut receiver = Receiver::start(&[]).await;
+    let app = app_with_worker(storage, fast_retries(3)).await;
+    let (webhook, secret) = register(&app, &receiver.url, &["item.created"]).await;
+    assert!(secret.starts_with("whsec_"), "{backend}");
+
+    let (_, item) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    let uri = format!("/api/v1/items/{}", item["id"].as_str().unwrap());
+    // Not subscribed to.
+    common::send(&app, Method::PUT, &uri, Some(json!({ "name": "gadget" }))).await;
+
+    let received = receiver.next().await;
+    verify(&secret, &received);
+    assert_eq!(received.headers["content-type"], "application/json");
+    assert_eq!(
+        received.headers["webhook-event"], "item.created",
+        "{backend}"
+    );
+    let event: Value = serde_json::from_slice(&received.body).unwrap();
+    assert_eq!(event["action"], "created", "{backend}");
+    assert_eq!(event["data"], item, "{backend}");
+
+    let delivery = wait_for_delivery(&app, &webhook, |d| d["status"] == "succeeded").await;
+    assert_eq!(
+        received.headers["webhook-id"],
+        delivery["id"].as_str().unwrap(),
+        "{backend}"
+    );
+    assert_eq!(delivery["attempts"], 1, "{backend}");
+    assert_eq!(delivery["response_status"], 200, "{backend}");
+    assert_eq!(delivery["next_attempt_at"], Value::Null, "{backend}");
+    assert_eq!(delivery["payload"], event, "{backend}");
+
+    let (_, log) = common::send(
+        &app,
+        Method::GET,
+        &format!("/api/v1/admin/webhooks/{webhook}/deliveries"),
+        None,
+    )
+    .await;
+    assert_eq!(log["data"].as_array().unwrap().len(), 1, "{backend}");
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let error = StatusCode::INTERNAL_SERVER_ERROR;
+    let mut receiver = Receiver::start(&[error, error]).await;
+    let app = app_with_worker(MemoryStorage::new(), fast_retries(5)).await;
+    let (webhook, _) = register(&app, &receiver.url, &["item.created"]).await;
+
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    let first = receiver.next().await;
+    let second = receiver.next().await;
+    let third = receiver.next().await;
+    assert_eq!(first.body, third.body);
+    assert_eq!(first.headers["webhook-id"], third.headers["webhook-id"]);
+    // 50ms, then 100ms.
+    assert!(second.at - first.at >= Duration::from_millis(50));
+    assert!(third.at - second.at >= Duration::from_millis(100));
+
+    let delivery = wait_for_delivery(&app, &webhook, |d| d["status"] == "succeeded").await;
+    assert_eq!(delivery["attempts"], 3);
+    assert_eq!(delivery["error"], Value::Null);
+}
+
+#[tokio::test]
+async fn deliveries_are_given_up_after_the_last_attempt() {
+    // Nothing listens on a port we just released.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    drop(listener);
+
+    let app = app_with_worker(MemoryStorage::new(), fast_retries(2)).await;
+    let (webhook, _) = register(&app, &url, &["item.created", "item.deleted"]).await;
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+
+    let delivery = wait_for_delivery(&app, &webhook, |d| d["status"] == "failed").await;
+    assert_eq!(delivery["attempts"], 2);
+    assert_eq!(delivery["response_status"], Value::Null);
+    assert_eq!(delivery["next_attempt_at"], Value::Null);
+    assert!(
+        delivery["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("connection failed"),
+        "{delivery}"
+    );
+
+    let uri = format!("/api/v1/admin/webhooks/{webhook}/deliveries?status=pending");
+    let (_, pending) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(pending["data"], json!([]));
+}
+
+#[tokio::test]
+async fn queued_deliveries_survive_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("data.db");
+    let mut receiver = Receiver::start(&[]).await;
+
+    // No worker runs in the first instance.
+    let app = common::app_with(SqliteStorage::open(&path).unwrap()).await;
+    let (webhook, secret) = register(&app, &receiver.url, &["item.created"]).await;
+    common::send(
+        &app,
+        Method::POST,
+        "/api/v1/items",
+        Some(json!({ "name": "widget" })),
+    )
+    .await;
+    drop(app);
+
+    let app = app_with_worker(SqliteStorage::open(&path).unwrap(), fast_retries(3)).await;
+    verify(&secret, &receiver.next().await);
+    wait_for_delivery(&app, &webhook, |d| d["status"] == "succeeded").await;
+}
+
+#[tokio::test]
+async fn webhooks_can_be_managed_by_admins() {
+    let app = common::app().await;
+    let (id, _) = register(
+        &app,
+        "https://hooks.example.com/inventory",
+        &["item.updated", "item.created", "item.updated"],
+    )
+    .await;
+
+    let uri = format!("/api/v1/admin/webhooks/{id}");
+    let (status, webhook) = common::send(&app, Method::GET, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(webhook["events"], json!(["item.created", "item.updated"]));
+    assert!(webhook.get("secret").is_none());
+
+    let (_, page) = common::send(&app, Method::GET, "/api/v1/admin/webhooks", None).await;
+    assert_eq!(page["data"], json!([webhook]));
+
+    let (_, issued) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/api-keys",
+        Some(json!({ "name": "writer", "scopes": ["read", "write"] })),
+    )
+    .await;
+    let (status, _) = common::send_as(
+        &app,
+        issued["token"].as_str(),
+        Method::GET,
+        "/api/v1/admin/webhooks",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = common::send(&app, Method::GET, &format!("{uri}/deliveries"), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = common::send(&app, Method::DELETE, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn webhook_registrations_are_validated() {
+    let app = common::app().await;
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/webhooks",
+        Some(json!({ "url": "ftp://example.com/hook", "events": ["item.renamed"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    let fields: Vec<_> = body["error"]["details"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| {
+            (
+                error["field"].as_str().unwrap(),
+                error["code"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        fields,
+        [("events", "invalid_value"), ("url", "invalid_value")]
+    );
+
+    let (status, _) = common::send(
+        &app,
+        Method::POST,
+        "/api/v1/admin/webhooks",
+        Some(json!({ "url": "https://example.com/hook", "events": [] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}